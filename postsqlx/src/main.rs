@@ -44,7 +44,7 @@ async fn main() -> io::Result<()> {
             id: row.id,
             teacher_id: row.teacher_id,
             name: row.name,
-            time: Some(NaiveDateTime::from(row.time.unwrap())),
+            time: row.time,
         })
     }
     println!("Course = {:?}", course_list);
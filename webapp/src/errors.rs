@@ -21,15 +21,15 @@ impl AppError {
     fn error_response(&self) -> String {
         match self {
             AppError::ActixError(msg) => {
-                println!("Server error occurred: {:?}", msg);
+                tracing::error!(error = %msg, "Server error occurred");
                 "Internal server error".into()
             }
             AppError::TeraError(msg) => {
-                println!("Error in rendering the template {:?}", msg);
+                tracing::error!(error = %msg, "Error in rendering the template");
                 msg.into()
             }
             AppError::NotFound(msg) => {
-                println!("Not found error occurred: {:?}", msg);
+                tracing::info!(resource = %msg, "Not found");
                 "Not found".into()
             }
         }
@@ -54,7 +54,7 @@ impl error::ResponseError for AppError {
 
 impl Display for AppError {
     fn fmt(&self, f: &mut std::fmt::Formatter<'_>) -> std::fmt::Result {
-        write!(f, "{}", self)
+        write!(f, "{}", self.error_response())
     }
 }
 
@@ -1,13 +1,18 @@
-use actix_web::{web, Error, HttpResponse, Result};
+use actix_web::{http::header, web, Error, HttpRequest, HttpResponse, Result};
 use serde_json::json;
 
-use crate::{errors::AppError, models::{TeacherRegisterForm, TeacherResponse}};
+use crate::{
+    errors::AppError,
+    models::{CoursePageResponse, ServiceErrorResponse, TeacherRegisterForm, TeacherResponse},
+    money::locale_from_accept_language,
+    request_id::service_client,
+};
 
 pub async fn get_all_teachers(
     tmpl: web::Data<tera::Tera>
 ) -> Result<HttpResponse, Error> {
     // 用于网络请求的 Http 客户端
-    let awc_client = awc::Client::default();
+    let awc_client = service_client();
     let res = awc_client
         .get("http://localhost:3000/teachers/")
         .send()
@@ -28,6 +33,46 @@ pub async fn get_all_teachers(
 }
 
 
+// 老师的课程列表, 价格按请求头 Accept-Language 的语言格式化
+pub async fn get_teacher_courses(
+    tmpl: web::Data<tera::Tera>,
+    path: web::Path<i32>,
+    req: HttpRequest,
+) -> Result<HttpResponse, Error> {
+    let teacher_id = path.into_inner();
+    let awc_client = service_client();
+    let mut res = awc_client
+        .get(format!("http://localhost:3000/courses/{}", teacher_id))
+        .send()
+        .await
+        .map_err(|e| AppError::ActixError(e.to_string()))?;
+    if res.status() == awc::http::StatusCode::NOT_FOUND {
+        return Err(AppError::NotFound(format!("teacher {}", teacher_id)).into());
+    }
+    let page = res
+        .json::<CoursePageResponse>()
+        .await
+        .map_err(|e| AppError::ActixError(e.to_string()))?;
+
+    let locale = locale_from_accept_language(
+        req.headers()
+            .get(header::ACCEPT_LANGUAGE)
+            .and_then(|v| v.to_str().ok()),
+    );
+    let mut ctx = tera::Context::new();
+    ctx.insert("teacher_id", &teacher_id);
+    ctx.insert("courses", &page.items);
+    ctx.insert("total", &page.total);
+    ctx.insert("locale", &locale);
+
+    let s = tmpl
+        .render("courses.html", &ctx)
+        .map_err(|e| {
+            tracing::error!(error = ?e, "Error in rendering the template");
+            AppError::TeraError("Template Error".to_string())
+        })?;
+    Ok(HttpResponse::Ok().content_type("text/html").body(s))
+}
 
 pub async fn show_register_form(
     tmpl: web::Data<tera::Tera>
@@ -37,6 +82,7 @@ pub async fn show_register_form(
     ctx.insert("current_name", "");
     ctx.insert("current_imageurl", "");
     ctx.insert("current_profile", "");
+    ctx.insert("current_email", "");
     let s = tmpl
         .render("register.html", &ctx)
         .map_err(|_| AppError::TeraError("Template Error".to_string()))?;
@@ -48,40 +94,51 @@ pub async fn handle_register(
     tmpl: web::Data<tera::Tera>,
     params: web::Form<TeacherRegisterForm>,
 ) -> Result<HttpResponse, Error> {
-    let mut ctx = tera::Context::new();
-    let s;
+    let new_teacher = json!({
+        "name": &params.name,
+        "picture_url": &params.imageurl,
+        "profile": &params.profile,
+        "email": &params.email,
+        "password": &params.password,
+    });
+
+    let awc_client = service_client();
+
+    let mut res = awc_client
+        .post("http://localhost:3000/auth/register")
+        .send_json(&new_teacher)
+        .await
+        .unwrap();
+    let body = res.body().await?;
 
-    if params.name == "Dave" {
-        ctx.insert("error", "Dave already exist");
+    let s = if res.status().is_success() {
+        let teacher_response: TeacherResponse = serde_json::from_str(std::str::from_utf8(&body)?)?;
+        format!("Congratulations, You id is: {}", teacher_response.id)
+    } else {
+        // 邮箱已注册、密码太短等错误, 带着已填写的内容重新显示表单
+        let error = match serde_json::from_slice::<ServiceErrorResponse>(&body) {
+            Ok(e) if e.code == "conflict" => "This email is already registered".to_string(),
+            Ok(e) if e.code == "validation_failed" => e
+                .details
+                .iter()
+                .map(|d| format!("{} {}", d.field, d.message))
+                .collect::<Vec<_>>()
+                .join("; "),
+            Ok(e) => e.error_message,
+            Err(_) => "Registration failed".to_string(),
+        };
+        let mut ctx = tera::Context::new();
+        ctx.insert("error", &error);
         ctx.insert("current_name", &params.name);
         ctx.insert("current_imageurl", &params.imageurl);
         ctx.insert("current_profile", &params.profile);
-        s = tmpl
+        ctx.insert("current_email", &params.email);
+        tmpl
             .render("register.html", &ctx)
             .map_err(|e| {
-                println!("Error in rendering the template: {:?}", e);
+                tracing::error!(error = ?e, "Error in rendering the template");
                 AppError::TeraError("Template Error".to_string())
-            })?;
-    } else {
-        let new_teacher = json!({
-            "name": &params.name,
-            "picture_url": &params.imageurl,
-            "profile": &params.profile,
-        });
-
-        let awc_client = awc::Client::default();
-
-        let res = awc_client
-            .post("http://localhost:3000/teachers/")
-            .send_json(&new_teacher)
-            .await
-            .unwrap()
-            .body()
-            .await?;
-
-        let teacher_response: TeacherResponse = serde_json::from_str(&std::str::from_utf8(&res)?)?;
-        s = format!("Congratulations, You id is: {}", teacher_response.id);
-
-    }
+            })?
+    };
     Ok(HttpResponse::Ok().content_type("text/html").body(s))
 }
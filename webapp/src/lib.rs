@@ -1,4 +1,7 @@
 pub mod models;
 pub mod routes;
 pub mod handler;
+pub mod money;
 pub mod errors;
+pub mod request_id;
+pub mod telemetry;
@@ -1,27 +1,33 @@
 use std::{env, io::Result};
 use dotenv::dotenv;
-use actix_web::{web, App, HttpServer};
+use actix_web::{middleware::from_fn, web, App, HttpServer};
 use tera::Tera;
+use webapp::money::money_filter;
+use webapp::request_id::request_id_middleware;
 use webapp::routes::app_config;
+use webapp::telemetry::init_tracing;
 
 
 #[actix_web::main]
 async fn main()  -> Result<()>{
 
     dotenv().ok();
+    init_tracing();
     let host_port = env::var("HOST_PORT")
         .expect("HOST_PORT address is not set in .env file");
 
-    println!("Listening on : {}", &host_port);
+    tracing::info!(address = %host_port, "Listening");
 
 
     HttpServer::new(move || {
-        let tera = Tera::new(
+        let mut tera = Tera::new(
             concat!(env!("CARGO_MANIFEST_DIR"), "/static/**")
         ).unwrap();
+        tera.register_filter("money", money_filter);
         App::new()
             .app_data(web::Data::new(tera))
             .configure(app_config)
+            .wrap(from_fn(request_id_middleware))
     })
     .bind(&host_port)?
     .run()
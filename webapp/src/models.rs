@@ -5,6 +5,8 @@ pub struct TeacherRegisterForm {
     pub name: String,
     pub imageurl: String,
     pub profile: String,
+    pub email: String,
+    pub password: String,
 }
 
 #[derive(Serialize, Deserialize, Debug)]
@@ -15,4 +17,46 @@ pub struct TeacherResponse {
     pub profile: String, 
 }
 
+// 金额, amount 以最小货币单位表示, currency 为 ISO 4217 代码, 与 webservice 的 Money 一致
+#[derive(Serialize, Deserialize, Debug, Clone, PartialEq)]
+pub struct Money {
+    pub amount: i32,
+    pub currency: String,
+}
+
+// 课程页面用到的课程字段, 其他字段会被忽略
+#[derive(Serialize, Deserialize, Debug)]
+pub struct CourseResponse {
+    pub id: i32,
+    pub teacher_id: i32,
+    pub name: String,
+    pub description: Option<String>,
+    pub format: Option<String>,
+    pub duration: Option<String>,
+    pub price: Option<Money>,
+    pub language: Option<String>,
+    pub level: Option<String>,
+}
+
+// webservice 分页返回的课程列表
+#[derive(Serialize, Deserialize, Debug)]
+pub struct CoursePageResponse {
+    pub items: Vec<CourseResponse>,
+    pub total: i64,
+}
 
+// webservice 返回的错误信息, 根据 code 判断错误类型
+#[derive(Serialize, Deserialize, Debug)]
+pub struct ServiceErrorResponse {
+    pub code: String,
+    pub error_message: String,
+    pub request_id: Option<String>,
+    #[serde(default)]
+    pub details: Vec<ServiceFieldError>,
+}
+
+#[derive(Serialize, Deserialize, Debug)]
+pub struct ServiceFieldError {
+    pub field: String,
+    pub message: String,
+}
@@ -0,0 +1,146 @@
+use std::collections::HashMap;
+
+use tera::{Result, Value};
+
+use crate::models::Money;
+
+// 未指定语言时使用的格式
+pub const DEFAULT_LOCALE: &str = "en";
+
+// 货币的符号与小数位数, 与 webservice 支持的货币保持一致
+fn currency_info(currency: &str) -> (&str, u32) {
+    match currency {
+        "CNY" => ("¥", 2),
+        "USD" => ("$", 2),
+        "EUR" => ("€", 2),
+        "GBP" => ("£", 2),
+        "HKD" => ("HK$", 2),
+        "JPY" => ("JP¥", 0),
+        other => (other, 2),
+    }
+}
+
+// 不同语言的数字格式: (小数点, 千位分隔符, 货币符号是否在数字之后)
+fn number_format(locale: &str) -> (&'static str, &'static str, bool) {
+    let language = locale
+        .split(['-', '_'])
+        .next()
+        .unwrap_or_default()
+        .to_ascii_lowercase();
+    match language.as_str() {
+        "de" | "es" | "it" | "nl" | "pt" => (",", ".", true),
+        "fr" | "ru" | "pl" | "sv" => (",", "\u{a0}", true),
+        _ => (".", ",", false),
+    }
+}
+
+// 按语言格式化金额, 如 en 下 1234567 CNY 为 ¥12,345.67, de 下 1999 EUR 为 19,99 €
+pub fn format_money(money: &Money, locale: &str) -> String {
+    let (symbol, digits) = currency_info(&money.currency);
+    let (decimal, group, symbol_after) = number_format(locale);
+
+    let scale = 10_i64.pow(digits);
+    let amount = i64::from(money.amount);
+    let major = (amount / scale).unsigned_abs().to_string();
+    let mut number = String::new();
+    for (i, c) in major.chars().enumerate() {
+        if i > 0 && (major.len() - i) % 3 == 0 {
+            number.push_str(group);
+        }
+        number.push(c);
+    }
+    if digits > 0 {
+        let minor = (amount % scale).unsigned_abs();
+        number = format!("{}{}{:0width$}", number, decimal, minor, width = digits as usize);
+    }
+
+    let sign = if amount < 0 { "-" } else { "" };
+    if symbol_after {
+        format!("{}{}\u{a0}{}", sign, number, symbol)
+    } else {
+        format!("{}{}{}", sign, symbol, number)
+    }
+}
+
+// 从请求头 Accept-Language 中取权重最高的语言, 如 "de-DE,de;q=0.9,en;q=0.8" 返回 de-DE
+pub fn locale_from_accept_language(header: Option<&str>) -> String {
+    header
+        .unwrap_or_default()
+        .split(',')
+        .filter_map(|item| {
+            let mut parts = item.trim().split(';');
+            let tag = parts.next()?.trim();
+            let q = parts
+                .find_map(|p| p.trim().strip_prefix("q="))
+                .and_then(|q| q.parse::<f32>().ok())
+                .unwrap_or(1.0);
+            (!tag.is_empty() && tag != "*").then_some((tag, q))
+        })
+        .fold(None, |best: Option<(&str, f32)>, (tag, q)| match best {
+            Some((_, best_q)) if best_q >= q => best,
+            _ => Some((tag, q)),
+        })
+        .map_or_else(|| DEFAULT_LOCALE.to_string(), |(tag, _)| tag.to_string())
+}
+
+// Tera 过滤器, 用法: {{ course.price | money(locale=locale) }}, 价格为空时显示 -
+pub fn money_filter(value: &Value, args: &HashMap<String, Value>) -> Result<Value> {
+    if value.is_null() {
+        return Ok(Value::String("-".into()));
+    }
+    let money: Money = serde_json::from_value(value.clone())
+        .map_err(|e| tera::Error::msg(format!("Filter `money` received an invalid price: {}", e)))?;
+    let locale = args
+        .get("locale")
+        .and_then(Value::as_str)
+        .unwrap_or(DEFAULT_LOCALE);
+    Ok(Value::String(format_money(&money, locale)))
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    fn money(amount: i32, currency: &str) -> Money {
+        Money {
+            amount,
+            currency: currency.into(),
+        }
+    }
+
+    #[test]
+    fn groups_digits_per_locale() {
+        let price = money(123456789, "EUR");
+        assert_eq!(format_money(&price, "en"), "€1,234,567.89");
+        assert_eq!(format_money(&price, "de-DE"), "1.234.567,89\u{a0}€");
+        assert_eq!(format_money(&price, "fr"), "1\u{a0}234\u{a0}567,89\u{a0}€");
+        assert_eq!(format_money(&money(99999, "USD"), "en-US"), "$999.99");
+        assert_eq!(format_money(&money(100000, "USD"), "en-US"), "$1,000.00");
+    }
+
+    #[test]
+    fn jpy_has_no_minor_unit() {
+        assert_eq!(format_money(&money(1500, "JPY"), "en"), "JP¥1,500");
+        assert_eq!(format_money(&money(1234567, "JPY"), "de"), "1.234.567\u{a0}JP¥");
+    }
+
+    #[test]
+    fn negative_amounts_keep_the_sign_in_front() {
+        assert_eq!(format_money(&money(-123456, "CNY"), "en"), "-¥1,234.56");
+        assert_eq!(format_money(&money(-5, "GBP"), "en"), "-£0.05");
+        assert_eq!(format_money(&money(-1999, "EUR"), "fr"), "-19,99\u{a0}€");
+    }
+
+    #[test]
+    fn picks_the_language_with_the_highest_weight() {
+        let locale = |header: &str| locale_from_accept_language(Some(header));
+        assert_eq!(locale("de-DE,de;q=0.9,en;q=0.8"), "de-DE");
+        assert_eq!(locale("en;q=0.5, fr;q=0.9, de;q=0.7"), "fr");
+        // 权重相同时取先出现的, 没有 q 时权重为 1
+        assert_eq!(locale("fr;q=0.8,de;q=0.8"), "fr");
+        assert_eq!(locale("en;q=0.9,fr"), "fr");
+        assert_eq!(locale("*;q=1,de;q=0.1"), "de");
+        assert_eq!(locale(""), DEFAULT_LOCALE);
+        assert_eq!(locale_from_accept_language(None), DEFAULT_LOCALE);
+    }
+}
@@ -0,0 +1,12 @@
+use tracing_subscriber::EnvFilter;
+
+// 以 JSON 格式输出日志, 日志级别由 RUST_LOG 控制, 默认为 info
+pub fn init_tracing() {
+    let filter = EnvFilter::try_from_default_env().unwrap_or_else(|_| EnvFilter::new("info"));
+    let _ = tracing_subscriber::fmt()
+        .json()
+        .with_env_filter(filter)
+        .with_current_span(true)
+        .with_span_list(true)
+        .try_init();
+}
@@ -0,0 +1,5 @@
+// migrations 目录变化时重新编译, 保证 sqlx::migrate! 内嵌的是最新的脚本
+fn main() {
+    println!("cargo:rerun-if-changed=migrations");
+    println!("cargo:rerun-if-changed=migrations_sqlite");
+}
@@ -0,0 +1,23 @@
+-- 使用迁移之前手工建表的数据库保留原有的表和数据, 之后的迁移在原有的表上继续执行
+-- 老师表
+create table if not exists teacher (
+  id serial primary key,
+  name varchar(100),
+  picture_url varchar(255),
+  profile varchar(1000)
+);
+
+-- 课程表
+create table if not exists course (
+  id serial primary key,
+  teacher_id int not null,
+  name varchar(140) not null,
+  time timestamp default now(),
+  description varchar(2000),
+  format varchar(140),
+  structure varchar(100),
+  duration varchar(40),
+  price int,
+  language varchar(60),
+  level varchar(40)
+);
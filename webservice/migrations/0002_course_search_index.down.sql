@@ -0,0 +1 @@
+drop index if exists course_search_idx;
@@ -0,0 +1,6 @@
+-- 课程全文检索索引, 表达式需要与 dbaccess::course::COURSE_DOCUMENT 保持一致
+create index course_search_idx on course using gin ((
+  setweight(to_tsvector('english', coalesce(name, '')), 'A') ||
+  setweight(to_tsvector('english', coalesce(description, '')), 'B') ||
+  setweight(to_tsvector('english', coalesce(structure, '')), 'C')
+));
@@ -0,0 +1,3 @@
+alter table teacher
+  drop column if exists role,
+  drop column if exists password_hash;
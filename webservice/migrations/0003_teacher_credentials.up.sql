@@ -0,0 +1,4 @@
+-- 老师登录凭证, 未设置密码的老师无法登录
+alter table teacher
+  add column password_hash varchar(255),
+  add column role varchar(20) not null default 'teacher';
@@ -0,0 +1,2 @@
+drop index if exists teacher_email_key;
+alter table teacher drop column if exists email;
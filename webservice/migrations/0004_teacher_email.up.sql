@@ -0,0 +1,3 @@
+-- 老师登录邮箱, 忽略大小写唯一
+alter table teacher add column email varchar(255);
+create unique index teacher_email_key on teacher (lower(email));
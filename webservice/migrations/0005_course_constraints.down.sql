@@ -0,0 +1,3 @@
+alter table course
+  drop constraint if exists course_price_check,
+  drop constraint if exists course_teacher_id_fkey;
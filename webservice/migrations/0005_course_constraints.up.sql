@@ -0,0 +1,5 @@
+-- 课程必须属于一个存在的老师, 价格不能为负数
+-- 使用 not valid 只约束新写入的数据, 避免历史数据导致迁移失败
+alter table course
+  add constraint course_teacher_id_fkey foreign key (teacher_id) references teacher (id) not valid,
+  add constraint course_price_check check (price >= 0) not valid;
@@ -0,0 +1,4 @@
+drop index if exists teacher_deleted_at_idx;
+drop index if exists course_deleted_at_idx;
+alter table teacher drop column deleted_at;
+alter table course drop column deleted_at;
@@ -0,0 +1,7 @@
+-- 软删除: 删除时只记录删除时间, 查询时排除已删除的记录, 超过保留期限后由后台任务彻底删除
+alter table course add column deleted_at timestamp;
+alter table teacher add column deleted_at timestamp;
+
+-- 回收站列表与定期清理只查询已删除的记录
+create index course_deleted_at_idx on course (teacher_id, deleted_at) where deleted_at is not null;
+create index teacher_deleted_at_idx on teacher (deleted_at) where deleted_at is not null;
@@ -0,0 +1,5 @@
+alter table course
+  drop constraint course_price_currency_check,
+  drop constraint course_currency_check;
+update course set price = price / 100 where price is not null;
+alter table course drop column currency;
@@ -0,0 +1,18 @@
+-- 价格改为以最小货币单位(如分)保存, 并记录 ISO 4217 货币代码
+-- 原有的价格没有单位, 视为人民币元
+-- price 为 int, 超过 21474836 元的价格乘以 100 后会溢出, 提前给出明确的错误
+do $$
+begin
+  if exists (select 1 from course where price > 21474836) then
+    raise exception 'course.price above 21474836 cannot be converted to minor units';
+  end if;
+end
+$$;
+
+alter table course add column currency varchar(3);
+update course set price = price * 100, currency = 'CNY' where price is not null;
+
+-- 与 models::course::CURRENCIES 保持一致; 价格与货币同时为空或同时有值
+alter table course
+  add constraint course_currency_check check (currency in ('CNY', 'USD', 'EUR', 'GBP', 'HKD', 'JPY')),
+  add constraint course_price_currency_check check ((price is null) = (currency is null));
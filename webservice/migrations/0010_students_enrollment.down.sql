@@ -0,0 +1,3 @@
+drop table enrollment;
+alter table course drop column capacity;
+drop table student;
@@ -0,0 +1,21 @@
+-- 学生账号, 登录邮箱忽略大小写唯一
+create table student (
+  id serial primary key,
+  name varchar(100) not null,
+  email varchar(255) not null,
+  password_hash varchar(255) not null,
+  time timestamp default now()
+);
+create unique index student_email_key on student (lower(email));
+
+-- 课程的名额, 为空时不限制人数
+alter table course add column capacity int constraint course_capacity_check check (capacity >= 0);
+
+-- 选课记录, 课程被彻底删除时一起删除
+create table enrollment (
+  course_id int not null constraint enrollment_course_id_fkey references course (id) on delete cascade,
+  student_id int not null constraint enrollment_student_id_fkey references student (id) on delete cascade,
+  enrolled_at timestamp not null default now(),
+  primary key (course_id, student_id)
+);
+create index enrollment_student_id_idx on enrollment (student_id);
@@ -0,0 +1,2 @@
+drop table if exists course;
+drop table if exists teacher;
@@ -0,0 +1,23 @@
+-- 与 migrations 中的 Postgres 表结构一致, 已经存在的表同样保留, serial 对应 autoincrement, 保证删除后的 id 不会被再次使用
+-- 老师表
+create table if not exists teacher (
+  id integer primary key autoincrement,
+  name varchar(100),
+  picture_url varchar(255),
+  profile varchar(1000)
+);
+
+-- 课程表
+create table if not exists course (
+  id integer primary key autoincrement,
+  teacher_id int not null,
+  name varchar(140) not null,
+  time timestamp default current_timestamp,
+  description varchar(2000),
+  format varchar(140),
+  structure varchar(100),
+  duration varchar(40),
+  price int,
+  language varchar(60),
+  level varchar(40)
+);
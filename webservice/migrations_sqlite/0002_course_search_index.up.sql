@@ -0,0 +1,23 @@
+-- 课程全文检索, 使用 fts5 外部内容表, 列的顺序对应 Postgres 中的权重 A、B、C
+create virtual table course_fts using fts5(
+  name, description, structure,
+  content = 'course', content_rowid = 'id',
+  tokenize = 'porter unicode61'
+);
+insert into course_fts (course_fts) values ('rebuild');
+
+-- 通过触发器与 course 表保持同步
+create trigger course_fts_insert after insert on course begin
+  insert into course_fts (rowid, name, description, structure)
+  values (new.id, new.name, new.description, new.structure);
+end;
+create trigger course_fts_delete after delete on course begin
+  insert into course_fts (course_fts, rowid, name, description, structure)
+  values ('delete', old.id, old.name, old.description, old.structure);
+end;
+create trigger course_fts_update after update on course begin
+  insert into course_fts (course_fts, rowid, name, description, structure)
+  values ('delete', old.id, old.name, old.description, old.structure);
+  insert into course_fts (rowid, name, description, structure)
+  values (new.id, new.name, new.description, new.structure);
+end;
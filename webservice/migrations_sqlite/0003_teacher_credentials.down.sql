@@ -0,0 +1,2 @@
+alter table teacher drop column role;
+alter table teacher drop column password_hash;
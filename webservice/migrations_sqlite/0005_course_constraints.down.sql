@@ -0,0 +1,34 @@
+create table course_new (
+  id integer primary key autoincrement,
+  teacher_id int not null,
+  name varchar(140) not null,
+  time timestamp default current_timestamp,
+  description varchar(2000),
+  format varchar(140),
+  structure varchar(100),
+  duration varchar(40),
+  price int,
+  language varchar(60),
+  level varchar(40)
+);
+insert into course_new
+select id, teacher_id, name, time, description, format, structure, duration, price, language, level
+from course;
+drop table course;
+alter table course_new rename to course;
+
+-- 触发器随旧表一起删除, 重新创建
+create trigger course_fts_insert after insert on course begin
+  insert into course_fts (rowid, name, description, structure)
+  values (new.id, new.name, new.description, new.structure);
+end;
+create trigger course_fts_delete after delete on course begin
+  insert into course_fts (course_fts, rowid, name, description, structure)
+  values ('delete', old.id, old.name, old.description, old.structure);
+end;
+create trigger course_fts_update after update on course begin
+  insert into course_fts (course_fts, rowid, name, description, structure)
+  values ('delete', old.id, old.name, old.description, old.structure);
+  insert into course_fts (rowid, name, description, structure)
+  values (new.id, new.name, new.description, new.structure);
+end;
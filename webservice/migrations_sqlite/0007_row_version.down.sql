@@ -0,0 +1,2 @@
+alter table teacher drop column version;
+alter table course drop column version;
@@ -0,0 +1,3 @@
+-- 乐观锁: 每次修改或删除时版本号加一, 作为 ETag 返回, 客户端通过 If-Match 带回
+alter table course add column version integer not null default 1;
+alter table teacher add column version integer not null default 1;
@@ -0,0 +1,3 @@
+-- currency 的 check 约束都定义在该列上, 可以随列一起删除
+update course set price = price / 100 where price is not null;
+alter table course drop column currency;
@@ -0,0 +1,54 @@
+-- 价格改为以最小货币单位(如分)保存, 并记录 ISO 4217 货币代码
+-- 原有的价格没有单位, 视为人民币元
+-- price 读取为 i32, 超过 21474836 元的价格乘以 100 后会溢出;
+-- SQLite 不能在迁移中直接抛出错误, 借助临时表的 check 约束让迁移失败
+create temp table course_price_guard (
+  price int constraint course_price_fits_minor_units check (price <= 21474836)
+);
+insert into course_price_guard select price from course where price is not null;
+drop table course_price_guard;
+
+-- 新增列的 check 约束会检查已有的行, 价格与货币同时有值的约束需要重建 course 表
+-- currency 的取值与 models::course::CURRENCIES 保持一致
+create table course_new (
+  id integer primary key autoincrement,
+  teacher_id int not null constraint course_teacher_id_fkey references teacher (id),
+  name varchar(140) not null,
+  time timestamp default current_timestamp,
+  description varchar(2000),
+  format varchar(140),
+  structure varchar(100),
+  duration varchar(40),
+  price int constraint course_price_check check (price >= 0),
+  language varchar(60),
+  level varchar(40),
+  deleted_at timestamp,
+  version integer not null default 1,
+  currency varchar(3)
+    constraint course_currency_check check (currency in ('CNY', 'USD', 'EUR', 'GBP', 'HKD', 'JPY'))
+    constraint course_price_currency_check check ((price is null) = (currency is null))
+);
+insert into course_new
+select id, teacher_id, name, time, description, format, structure, duration,
+  price * 100, language, level, deleted_at, version,
+  case when price is not null then 'CNY' end
+from course;
+drop table course;
+alter table course_new rename to course;
+
+-- 索引与触发器随旧表一起删除, 重新创建
+create index course_deleted_at_idx on course (teacher_id, deleted_at) where deleted_at is not null;
+create trigger course_fts_insert after insert on course begin
+  insert into course_fts (rowid, name, description, structure)
+  values (new.id, new.name, new.description, new.structure);
+end;
+create trigger course_fts_delete after delete on course begin
+  insert into course_fts (course_fts, rowid, name, description, structure)
+  values ('delete', old.id, old.name, old.description, old.structure);
+end;
+create trigger course_fts_update after update on course begin
+  insert into course_fts (course_fts, rowid, name, description, structure)
+  values ('delete', old.id, old.name, old.description, old.structure);
+  insert into course_fts (rowid, name, description, structure)
+  values (new.id, new.name, new.description, new.structure);
+end;
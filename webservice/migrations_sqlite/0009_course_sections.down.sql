@@ -0,0 +1,2 @@
+drop table lesson;
+drop table section;
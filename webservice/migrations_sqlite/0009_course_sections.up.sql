@@ -0,0 +1,19 @@
+-- 课程的章节与课时, position 为从 1 开始的连续序号, 由代码维护
+-- 课程被彻底删除时一起删除其章节与课时
+create table section (
+  id integer primary key autoincrement,
+  course_id int not null constraint section_course_id_fkey references course (id) on delete cascade,
+  title varchar(140) not null,
+  position int not null
+);
+create index section_course_id_idx on section (course_id, position);
+
+create table lesson (
+  id integer primary key autoincrement,
+  section_id int not null constraint lesson_section_id_fkey references section (id) on delete cascade,
+  title varchar(140) not null,
+  content varchar(10000),
+  duration_minutes int constraint lesson_duration_check check (duration_minutes >= 0),
+  position int not null
+);
+create index lesson_section_id_idx on lesson (section_id, position);
@@ -0,0 +1,55 @@
+use actix_web::body::MessageBody;
+use actix_web::dev::{ServiceFactory, ServiceRequest, ServiceResponse};
+use actix_web::{web, App, Error};
+
+use crate::auth::JwtConfig;
+use crate::config::Settings;
+use crate::errors::AppError;
+use crate::lifecycle::Lifecycle;
+use crate::metrics::{Metrics, MetricsMiddleware};
+use crate::request_id::RequestIdMiddleware;
+use crate::routers::{auth_routes, course_routes, general_routes, student_routes, teacher_routes};
+use crate::state::AppState;
+
+
+// 构建完整的 App: 共享数据、提取器配置、所有路由和中间件
+// main 中每个 worker 调用一次, 测试中使用同样的函数保证与线上行为一致
+pub fn build_app(
+    shared_data: web::Data<AppState>,
+    jwt_config: web::Data<JwtConfig>,
+    metrics: web::Data<Metrics>,
+    lifecycle: web::Data<Lifecycle>,
+    settings: &Settings,
+) -> App<
+    impl ServiceFactory<
+        ServiceRequest,
+        Config = (),
+        Response = ServiceResponse<impl MessageBody>,
+        Error = Error,
+        InitError = (),
+    >,
+> {
+    App::new()
+        .app_data(shared_data)
+        .app_data(jwt_config)
+        .app_data(metrics.clone())
+        .app_data(lifecycle)
+        .app_data(web::JsonConfig::default().limit(settings.server.json_limit).error_handler(|_err, _req|{
+            AppError::InvalidaValue("Please provide valid Json Input".to_string()).into()
+        }))
+        // 只用于读取完整请求体的 web::Bytes, 即课程导入
+        .app_data(web::PayloadConfig::new(settings.server.import_limit))
+        .app_data(web::QueryConfig::default().error_handler(|err, _req| {
+            AppError::InvalidaValue(format!("Invalid query params: {}", err)).into()
+        }))
+        .configure(general_routes)
+        .configure(auth_routes)
+        .configure(course_routes)
+        .configure(teacher_routes)
+        .configure(student_routes)
+        // 跨域配置
+        .wrap(settings.cors.cors())
+        .wrap(MetricsMiddleware::new(metrics.into_inner()))
+        // 最后注册的中间件最先执行, 保证跨域等中间件中也能拿到请求 id
+        .wrap(RequestIdMiddleware)
+}
@@ -0,0 +1,210 @@
+use std::future::{ready, Ready};
+
+use actix_web::{dev::Payload, http::header::AUTHORIZATION, web, FromRequest, HttpRequest};
+use argon2::password_hash::{rand_core::OsRng, PasswordHash, PasswordHasher, PasswordVerifier, SaltString};
+use argon2::Argon2;
+use chrono::Utc;
+use jsonwebtoken::{decode, encode, DecodingKey, EncodingKey, Header, Validation};
+
+use crate::config::{AuthSettings, ConfigError};
+use crate::errors::AppError;
+use crate::models::auth::{Claims, Role};
+
+// 签发 JWT 的配置, 作为 app_data 挂载到 App 上
+#[derive(Clone)]
+pub struct JwtConfig {
+    secret: String,
+    // token 有效期(秒)
+    pub ttl: i64,
+}
+
+impl JwtConfig {
+    pub const DEFAULT_TTL: i64 = 3600;
+
+    pub fn new(secret: impl Into<String>, ttl: i64) -> Self {
+        JwtConfig {
+            secret: secret.into(),
+            ttl,
+        }
+    }
+
+    // 密钥没有配置或不安全时返回配置错误, 由调用方拒绝启动
+    pub fn from_settings(settings: &AuthSettings) -> Result<Self, ConfigError> {
+        Ok(JwtConfig::new(settings.jwt_secret()?, settings.jwt_ttl_secs))
+    }
+
+    pub fn issue_token(&self, id: i32, role: Role) -> Result<String, AppError> {
+        let now = Utc::now().timestamp();
+        let claims = Claims {
+            sub: id,
+            role,
+            iat: now,
+            exp: now + self.ttl,
+        };
+        encode(
+            &Header::default(),
+            &claims,
+            &EncodingKey::from_secret(self.secret.as_bytes()),
+        )
+        .map_err(|e| AppError::ActixError(e.to_string()))
+    }
+
+    pub fn verify_token(&self, token: &str) -> Result<Claims, AppError> {
+        decode::<Claims>(
+            token,
+            &DecodingKey::from_secret(self.secret.as_bytes()),
+            &Validation::default(),
+        )
+        .map(|data| data.claims)
+        .map_err(|_e| AppError::Unauthorized("Invalid or expired token".into()))
+    }
+}
+
+// 已登录的用户, 在 handler 参数中声明即要求请求携带合法的 Bearer token
+#[derive(Debug, Clone)]
+pub struct AuthUser {
+    // 与 Claims 的 sub 相同, 根据 role 为老师或学生的 id
+    pub id: i32,
+    pub role: Role,
+}
+
+impl AuthUser {
+    // 只有老师本人或者管理员可以修改该老师名下的数据
+    // 学生与老师的 id 来自不同的表, 需要同时检查角色
+    pub fn ensure_can_manage(&self, teacher_id: i32) -> Result<(), AppError> {
+        if self.role == Role::Admin || (self.role == Role::Teacher && self.id == teacher_id) {
+            Ok(())
+        } else {
+            Err(AppError::Forbidden(
+                "Not allowed to modify another teacher's data".into(),
+            ))
+        }
+    }
+
+    // 只有学生本人或者管理员可以查看和修改该学生的选课
+    pub fn ensure_student(&self, student_id: i32) -> Result<(), AppError> {
+        if self.role == Role::Admin || (self.role == Role::Student && self.id == student_id) {
+            Ok(())
+        } else {
+            Err(AppError::Forbidden(
+                "Not allowed to access another student's data".into(),
+            ))
+        }
+    }
+
+    pub fn ensure_admin(&self) -> Result<(), AppError> {
+        if self.role == Role::Admin {
+            Ok(())
+        } else {
+            Err(AppError::Forbidden("Admin role required".into()))
+        }
+    }
+
+    fn from_request_head(req: &HttpRequest) -> Result<Self, AppError> {
+        let config = req
+            .app_data::<web::Data<JwtConfig>>()
+            .ok_or_else(|| AppError::ActixError("JwtConfig is not configured".into()))?;
+        let token = req
+            .headers()
+            .get(AUTHORIZATION)
+            .and_then(|value| value.to_str().ok())
+            .and_then(|value| value.strip_prefix("Bearer "))
+            .ok_or_else(|| AppError::Unauthorized("Missing bearer token".into()))?;
+        let claims = config.verify_token(token.trim())?;
+        Ok(AuthUser {
+            id: claims.sub,
+            role: claims.role,
+        })
+    }
+}
+
+impl FromRequest for AuthUser {
+    type Error = AppError;
+    type Future = Ready<Result<Self, Self::Error>>;
+
+    fn from_request(req: &HttpRequest, _payload: &mut Payload) -> Self::Future {
+        ready(AuthUser::from_request_head(req))
+    }
+}
+
+pub fn hash_password(password: &str) -> Result<String, AppError> {
+    let salt = SaltString::generate(&mut OsRng);
+    Argon2::default()
+        .hash_password(password.as_bytes(), &salt)
+        .map(|hash| hash.to_string())
+        .map_err(|e| AppError::ActixError(e.to_string()))
+}
+
+pub fn verify_password(password: &str, password_hash: &str) -> bool {
+    PasswordHash::new(password_hash)
+        .map(|hash| {
+            Argon2::default()
+                .verify_password(password.as_bytes(), &hash)
+                .is_ok()
+        })
+        .unwrap_or(false)
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use actix_web::{http::StatusCode, test::TestRequest, ResponseError};
+
+    #[test]
+    fn password_hash_roundtrip() {
+        let hash = hash_password("secret-password").unwrap();
+        assert!(hash.starts_with("$argon2"));
+        assert!(verify_password("secret-password", &hash));
+        assert!(!verify_password("wrong-password", &hash));
+    }
+
+    #[actix_rt::test]
+    async fn extract_auth_user_from_bearer_token() {
+        let config = JwtConfig::new("test-secret", 60);
+        let token = config.issue_token(7, Role::Teacher).unwrap();
+        let req = TestRequest::default()
+            .app_data(web::Data::new(config))
+            .insert_header((AUTHORIZATION, format!("Bearer {}", token)))
+            .to_http_request();
+
+        let user = AuthUser::extract(&req).await.unwrap();
+        assert_eq!(user.id, 7);
+        assert!(user.ensure_can_manage(7).is_ok());
+        let err = user.ensure_can_manage(8).unwrap_err();
+        assert_eq!(err.status_code(), StatusCode::FORBIDDEN);
+        assert!(user.ensure_student(7).is_err());
+    }
+
+    #[test]
+    fn student_cannot_manage_teacher_with_same_id() {
+        let student = AuthUser { id: 7, role: Role::Student };
+        assert!(student.ensure_student(7).is_ok());
+        assert!(student.ensure_student(8).is_err());
+        let err = student.ensure_can_manage(7).unwrap_err();
+        assert_eq!(err.status_code(), StatusCode::FORBIDDEN);
+
+        let admin = AuthUser { id: 1, role: Role::Admin };
+        assert!(admin.ensure_student(7).is_ok());
+    }
+
+    #[actix_rt::test]
+    async fn reject_missing_or_forged_token() {
+        let config = JwtConfig::new("test-secret", 60);
+        let forged = JwtConfig::new("other-secret", 60)
+            .issue_token(7, Role::Admin)
+            .unwrap();
+
+        let req = TestRequest::default()
+            .app_data(web::Data::new(config.clone()))
+            .to_http_request();
+        let err = AuthUser::extract(&req).await.unwrap_err();
+        assert_eq!(err.status_code(), StatusCode::UNAUTHORIZED);
+
+        let req = TestRequest::default()
+            .app_data(web::Data::new(config))
+            .insert_header((AUTHORIZATION, format!("Bearer {}", forged)))
+            .to_http_request();
+        let err = AuthUser::extract(&req).await.unwrap_err();
+        assert_eq!(err.status_code(), StatusCode::UNAUTHORIZED);
+    }
+}
@@ -1,55 +1,196 @@
-use actix_cors::Cors;
-use actix_web::{http, web, App, HttpServer};
-use sqlx::postgres::PgPoolOptions;
+use std::io;
+use std::path::PathBuf;
+use std::time::Duration;
+
+use actix_web::{web, HttpServer};
+use clap::{Parser, Subcommand};
+use dotenv::dotenv;
+use webservice::app::build_app;
+use webservice::auth::JwtConfig;
+use webservice::config::{Overrides, Settings};
+use webservice::db::DbPool;
 use webservice::errors::AppError;
-use webservice::routers::{course_routes, general_routes, teacher_routes};
+use webservice::lifecycle::{wait_for_shutdown_signal, Lifecycle};
+use webservice::metrics::Metrics;
+use webservice::migration::{check_schema, migrate_down, migrate_up, migration_status};
 use webservice::state::AppState;
-use std::{env, io};
-use std::sync::Mutex;
-use dotenv::dotenv;
-use http::header::{AUTHORIZATION, ACCEPT, CONTENT_TYPE};
+use webservice::telemetry::init_tracing;
+use webservice::trash::run_purge_task;
+
+
+// 命令行参数会覆盖配置文件与环境变量中的同名配置
+#[derive(Parser, Debug)]
+#[command(version, about = "Course and teacher web service")]
+struct Cli {
+    /// Config file, defaults to config/webservice.toml when it exists
+    #[arg(long, env = "WEBSERVICE_CONFIG")]
+    config: Option<PathBuf>,
+    /// Address to listen on, may be repeated
+    #[arg(long)]
+    bind: Vec<String>,
+    /// Number of worker threads
+    #[arg(long)]
+    workers: Option<usize>,
+    /// Overrides database.url and DATABASE_URL
+    #[arg(long)]
+    database_url: Option<String>,
+    #[command(subcommand)]
+    command: Option<Command>,
+}
+
+#[derive(Subcommand, Debug)]
+enum Command {
+    /// Run database migrations without starting the server
+    Migrate {
+        #[command(subcommand)]
+        action: MigrateAction,
+    },
+}
+
+#[derive(Subcommand, Debug, Clone, Copy)]
+enum MigrateAction {
+    /// Apply all pending migrations
+    Up,
+    /// Revert the latest applied migration
+    Down,
+    /// List migrations and whether they are applied
+    Status,
+}
 
 
 #[actix_rt::main]
 async fn main() -> io::Result<()> {
     dotenv().ok();
+    init_tracing();
 
-    let database_url = env::var("DATABASE_URL")
-        .expect("DATABASE_URL is not set");
+    let cli = Cli::parse();
+    // 配置不合法时输出所有问题后退出
+    let settings = match Settings::load(&Overrides {
+        config_file: cli.config,
+        bind: cli.bind,
+        workers: cli.workers,
+        database_url: cli.database_url,
+    }) {
+        Ok(settings) => settings,
+        Err(e) => {
+            eprint!("{}", e);
+            std::process::exit(2);
+        }
+    };
 
-    let db_pool = PgPoolOptions::new().connect(&database_url)
+    // memory 存储时为 None
+    let db_pool = DbPool::connect(&settings.database)
         .await
-        .unwrap();
+        .map_err(|e| io::Error::other(format!("Failed to connect to database: {}", e)))?;
+
+    // `main migrate up|down|status` 只执行数据库迁移, 不启动服务
+    if let Some(Command::Migrate { action }) = cli.command {
+        let db_pool = db_pool
+            .ok_or_else(|| io::Error::other("Migrations require a postgres or sqlite database"))?;
+        return run_migrate(&db_pool, action).await;
+    }
+
+    // 签发与校验 token 的密钥, 只有启动服务时需要
+    let jwt_config = match JwtConfig::from_settings(&settings.auth) {
+        Ok(jwt_config) => web::Data::new(jwt_config),
+        Err(e) => {
+            eprint!("{}", e);
+            std::process::exit(2);
+        }
+    };
 
     // 挂载一个共享数据
-    let shared_data = web::Data::new(AppState {
-        health_check_response: "I'm OK.".to_string(),
-        visit_count: Mutex::new(0),
-        db: db_pool, 
-        // courses: Mutex::new(vec![]),
-    });
-    let app = move || { 
-        // 跨域配置
-        let cors = Cors::default()
-            // 允许某个域名
-            // .allowed_origin("http://localhost:8080/")
-            // 允许某些域名前缀(这里引入了一个闭包，只要闭包返回 true 的逻辑都被允许)
-            .allowed_origin_fn(|origin, _req_head| {
-                origin.as_bytes().starts_with(b"http://localhost")
-            })
-            .allowed_methods(vec!["GET", "DELETE", "PUT", "POST"])
-            .allowed_headers(vec![AUTHORIZATION, ACCEPT])
-            .allowed_header(CONTENT_TYPE)
-            .max_age(3600);
-        App::new()
-            .app_data(shared_data.clone())
-            .app_data(web::JsonConfig::default().error_handler(|_err, _req|{
-                AppError::InvalidaValue("Please provide valid Json Input".to_string()).into()
-            }))
-            .configure(general_routes)
-            .configure(course_routes)
-            .wrap(cors)
-            .configure(teacher_routes)
+    let shared_data = match &db_pool {
+        Some(db_pool) => {
+            // 数据库结构与当前版本不匹配时拒绝启动
+            check_schema(db_pool)
+                .await
+                .map_err(|e| io::Error::other(format!("Schema check failed: {:?}", e)))?;
+            web::Data::new(AppState::database(db_pool.clone()))
+        }
+        None => {
+            tracing::warn!("Using in-memory storage, data will be lost on shutdown");
+            web::Data::new(AppState::in_memory())
+        }
+    };
+    // 定期清理回收站中过期的课程和老师
+    let purge_task = actix_rt::spawn(run_purge_task(shared_data.clone(), settings.trash.clone()));
+    let lifecycle = web::Data::new(Lifecycle::default());
+    let shutdown_lifecycle = lifecycle.clone();
+    // 所有 worker 共享同一份指标
+    let metrics = web::Data::new(
+        Metrics::new().map_err(|e| io::Error::other(format!("Metrics setup failed: {}", e)))?,
+    );
+    let app_settings = settings.clone();
+    let app = move || {
+        build_app(
+            shared_data.clone(),
+            jwt_config.clone(),
+            metrics.clone(),
+            lifecycle.clone(),
+            &app_settings,
+        )
     };
-    HttpServer::new(app).bind("127.0.0.1:3000")?.run().await
+
+    // 自己处理关闭信号, 以便在停止接受连接之前先让就绪检查返回 503
+    let mut server = HttpServer::new(app)
+        .disable_signals()
+        .shutdown_timeout(settings.server.shutdown_timeout_secs);
+    if let Some(workers) = settings.server.workers {
+        server = server.workers(workers);
+    }
+    for address in &settings.server.bind {
+        server = server.bind(address)?;
+    }
+    tracing::info!(bind = ?settings.server.bind, "Starting webservice");
+    let server = server.run();
+
+    let handle = server.handle();
+    let shutdown_delay = Duration::from_secs(settings.server.shutdown_delay_secs);
+    actix_rt::spawn(async move {
+        let signal = wait_for_shutdown_signal().await;
+        tracing::info!(signal, "Shutdown started");
+        shutdown_lifecycle.begin_shutdown();
+        if shutdown_delay > Duration::ZERO {
+            actix_rt::time::sleep(shutdown_delay).await;
+        }
+        tracing::info!("Stopping server, draining in-flight requests");
+        // 停止接受新连接, 等待正在处理的请求完成, 最多等待 shutdown_timeout_secs
+        handle.stop(true).await;
+    });
+    server.await?;
+    // 清理任务会使用连接池, 需要在关闭连接池之前停止
+    purge_task.abort();
+
+    // 所有 worker 退出后再关闭连接池, 等待连接归还并正常断开
+    if let Some(db_pool) = db_pool {
+        db_pool.close().await;
+    }
+    tracing::info!("Shutdown complete");
+    Ok(())
+}
+
+async fn run_migrate(db_pool: &DbPool, action: MigrateAction) -> io::Result<()> {
+    let to_io_err = |e: AppError| io::Error::other(format!("{:?}", e));
+    match action {
+        MigrateAction::Up => {
+            migrate_up(db_pool).await.map_err(to_io_err)?;
+            println!("Migrations applied");
+        }
+        MigrateAction::Down => match migrate_down(db_pool).await.map_err(to_io_err)? {
+            Some(version) => println!("Reverted migration {}", version),
+            None => println!("No migration to revert"),
+        },
+        MigrateAction::Status => {
+            for s in migration_status(db_pool).await.map_err(to_io_err)? {
+                let state = match (s.applied, s.checksum_ok) {
+                    (true, true) => "applied",
+                    (true, false) => "modified",
+                    (false, _) => "pending",
+                };
+                println!("{:>14} {:<9} {}", s.version, state, s.description);
+            }
+        }
+    }
+    Ok(())
 }
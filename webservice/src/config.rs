@@ -0,0 +1,541 @@
+use std::collections::HashMap;
+use std::env;
+use std::fmt::{self, Display};
+use std::net::ToSocketAddrs;
+use std::path::PathBuf;
+use std::time::Duration;
+
+use actix_cors::Cors;
+use actix_web::http::header::{HeaderName, ACCEPT, AUTHORIZATION, CONTENT_TYPE, ETAG, IF_MATCH};
+use actix_web::http::Method;
+use config::{Config, Environment, File};
+use serde::{Deserialize, Serialize};
+use sqlx::pool::PoolOptions;
+use sqlx::Database;
+
+use crate::auth::JwtConfig;
+use crate::request_id::REQUEST_ID_HEADER;
+
+// 未指定配置文件时尝试读取的文件, 不存在时只使用默认值
+pub const DEFAULT_CONFIG_FILE: &str = "config/webservice.toml";
+// 环境变量前缀, 层级之间使用 __ 分隔, 如 WEBSERVICE__SERVER__WORKERS=4
+pub const ENV_PREFIX: &str = "WEBSERVICE";
+const ENV_SEPARATOR: &str = "__";
+// 列表类型的配置在环境变量中使用逗号分隔
+const ENV_LIST_KEYS: [&str; 3] = ["server.bind", "cors.allowed_origins", "cors.allowed_methods"];
+
+// 服务配置, 优先级从低到高: 默认值、配置文件、环境变量、命令行参数
+#[derive(Deserialize, Debug, Clone, PartialEq, Default)]
+#[serde(default, deny_unknown_fields)]
+pub struct Settings {
+    pub server: ServerSettings,
+    pub database: DatabaseSettings,
+    pub cors: CorsSettings,
+    pub trash: TrashSettings,
+    pub auth: AuthSettings,
+}
+
+#[derive(Deserialize, Debug, Clone, PartialEq)]
+#[serde(default, deny_unknown_fields)]
+pub struct ServerSettings {
+    // 监听地址, 可以有多个
+    pub bind: Vec<String>,
+    // worker 线程数, 不设置时与 CPU 核数相同
+    pub workers: Option<usize>,
+    // Json 请求体的最大字节数
+    pub json_limit: usize,
+    // 导入课程时 CSV 或 NDJSON 请求体的最大字节数
+    pub import_limit: usize,
+    // 收到关闭信号后, 就绪检查先返回 503 并继续处理请求的时间(秒), 留给负载均衡摘除实例
+    pub shutdown_delay_secs: u64,
+    // 停止接受连接后等待正在处理的请求完成的最长时间(秒)
+    pub shutdown_timeout_secs: u64,
+}
+
+impl Default for ServerSettings {
+    fn default() -> Self {
+        ServerSettings {
+            bind: vec!["127.0.0.1:3000".into()],
+            workers: None,
+            json_limit: 2 * 1024 * 1024,
+            import_limit: 10 * 1024 * 1024,
+            shutdown_delay_secs: 0,
+            shutdown_timeout_secs: 30,
+        }
+    }
+}
+
+// 数据的存储方式, memory 不需要数据库, 进程退出后数据丢失, 只用于开发和测试
+// sqlite 需要编译时启用 sqlite feature
+#[derive(Deserialize, Serialize, Debug, Clone, Copy, PartialEq)]
+#[serde(rename_all = "lowercase")]
+pub enum StorageBackend {
+    Postgres,
+    Sqlite,
+    Memory,
+}
+
+#[derive(Deserialize, Debug, Clone, PartialEq)]
+#[serde(default, deny_unknown_fields)]
+pub struct DatabaseSettings {
+    // 不设置时根据 url 判断, 见 backend()
+    pub backend: Option<StorageBackend>,
+    // 不设置时使用环境变量 DATABASE_URL, 使用 memory 存储时不需要
+    pub url: Option<String>,
+    pub max_connections: u32,
+    pub min_connections: u32,
+    // 等待空闲连接的超时时间(秒)
+    pub acquire_timeout_secs: u64,
+    // 连接空闲多久后关闭(秒)
+    pub idle_timeout_secs: Option<u64>,
+    // 连接最长使用多久后重建(秒)
+    pub max_lifetime_secs: Option<u64>,
+}
+
+impl Default for DatabaseSettings {
+    fn default() -> Self {
+        DatabaseSettings {
+            backend: None,
+            url: None,
+            max_connections: 10,
+            min_connections: 0,
+            acquire_timeout_secs: 30,
+            idle_timeout_secs: Some(600),
+            max_lifetime_secs: Some(1800),
+        }
+    }
+}
+
+impl DatabaseSettings {
+    // url 以 sqlite: 开头时使用 SQLite, 其他情况使用 Postgres
+    pub fn backend(&self) -> StorageBackend {
+        match (self.backend, &self.url) {
+            (Some(backend), _) => backend,
+            (None, Some(url)) if url.starts_with("sqlite:") => StorageBackend::Sqlite,
+            _ => StorageBackend::Postgres,
+        }
+    }
+
+    pub fn pool_options<DB: Database>(&self) -> PoolOptions<DB> {
+        PoolOptions::new()
+            .max_connections(self.max_connections)
+            .min_connections(self.min_connections)
+            .acquire_timeout(Duration::from_secs(self.acquire_timeout_secs))
+            .idle_timeout(self.idle_timeout_secs.map(Duration::from_secs))
+            .max_lifetime(self.max_lifetime_secs.map(Duration::from_secs))
+    }
+}
+
+#[derive(Deserialize, Debug, Clone, PartialEq)]
+#[serde(default, deny_unknown_fields)]
+pub struct CorsSettings {
+    // 允许的来源, 以 * 结尾时按前缀匹配, 如 http://localhost* 允许本机的所有端口
+    pub allowed_origins: Vec<String>,
+    pub allowed_methods: Vec<String>,
+    // 预检请求的缓存时间(秒)
+    pub max_age: usize,
+}
+
+impl Default for CorsSettings {
+    fn default() -> Self {
+        CorsSettings {
+            allowed_origins: vec!["http://localhost*".into()],
+            allowed_methods: ["GET", "DELETE", "PUT", "PATCH", "POST"].map(String::from).to_vec(),
+            max_age: 3600,
+        }
+    }
+}
+
+impl CorsSettings {
+    pub fn cors(&self) -> Cors {
+        let origins = self.allowed_origins.clone();
+        Cors::default()
+            .allowed_origin_fn(move |origin, _req_head| {
+                origin
+                    .to_str()
+                    .map(|origin| origins.iter().any(|allowed| origin_matches(allowed, origin)))
+                    .unwrap_or(false)
+            })
+            .allowed_methods(self.allowed_methods.iter().map(String::as_str))
+            .allowed_headers(vec![AUTHORIZATION, ACCEPT, IF_MATCH])
+            .allowed_header(CONTENT_TYPE)
+            // 允许浏览器中的脚本读取请求 id 和 ETag
+            .expose_headers(vec![HeaderName::from_static(REQUEST_ID_HEADER), ETAG])
+            .max_age(self.max_age)
+    }
+}
+
+fn origin_matches(allowed: &str, origin: &str) -> bool {
+    match allowed.strip_suffix('*') {
+        Some(prefix) => origin.starts_with(prefix),
+        None => origin == allowed,
+    }
+}
+
+// 回收站: 删除的课程和老师保留一段时间后由后台任务彻底删除
+#[derive(Deserialize, Debug, Clone, PartialEq)]
+#[serde(default, deny_unknown_fields)]
+pub struct TrashSettings {
+    // 删除后保留的天数, 超过后不能再恢复
+    pub retention_days: u32,
+    // 清理任务的执行间隔(秒)
+    pub purge_interval_secs: u64,
+}
+
+impl Default for TrashSettings {
+    fn default() -> Self {
+        TrashSettings {
+            retention_days: 30,
+            purge_interval_secs: 3600,
+        }
+    }
+}
+
+impl TrashSettings {
+    pub fn retention(&self) -> chrono::Duration {
+        chrono::Duration::days(self.retention_days.into())
+    }
+
+    pub fn purge_interval(&self) -> Duration {
+        Duration::from_secs(self.purge_interval_secs)
+    }
+}
+
+// 签发 token 的配置
+#[derive(Deserialize, Debug, Clone, PartialEq)]
+#[serde(default, deny_unknown_fields)]
+pub struct AuthSettings {
+    // 签名密钥, 不设置时使用环境变量 JWT_SECRET, 启动服务时必须配置
+    pub jwt_secret: Option<String>,
+    // token 有效期(秒)
+    pub jwt_ttl_secs: i64,
+}
+
+impl Default for AuthSettings {
+    fn default() -> Self {
+        AuthSettings {
+            jwt_secret: None,
+            jwt_ttl_secs: JwtConfig::DEFAULT_TTL,
+        }
+    }
+}
+
+// .env.example 中的占位密钥, 不能用于签发 token
+pub const PLACEHOLDER_JWT_SECRET: &str = "change-me-in-production";
+
+impl AuthSettings {
+    // 密钥缺失、为空或仍是占位值时拒绝启动, 否则任何人都可以伪造 token
+    pub fn jwt_secret(&self) -> Result<&str, ConfigError> {
+        let secret = self.jwt_secret.as_deref().unwrap_or_default();
+        if secret.trim().is_empty() {
+            Err(ConfigError(vec![
+                "auth.jwt_secret is not set, configure it or set JWT_SECRET".to_string(),
+            ]))
+        } else if secret == PLACEHOLDER_JWT_SECRET {
+            Err(ConfigError(vec![
+                "auth.jwt_secret must not be the placeholder from .env.example".to_string(),
+            ]))
+        } else {
+            Ok(secret)
+        }
+    }
+}
+
+// 命令行参数, 优先级最高
+#[derive(Debug, Clone, Default)]
+pub struct Overrides {
+    pub config_file: Option<PathBuf>,
+    pub bind: Vec<String>,
+    pub workers: Option<usize>,
+    pub database_url: Option<String>,
+}
+
+// 配置无法读取或不合法, 包含所有发现的问题
+#[derive(Debug, Clone, PartialEq)]
+pub struct ConfigError(pub Vec<String>);
+
+impl Display for ConfigError {
+    fn fmt(&self, f: &mut fmt::Formatter<'_>) -> fmt::Result {
+        writeln!(f, "Invalid configuration:")?;
+        for problem in &self.0 {
+            writeln!(f, "  - {}", problem)?;
+        }
+        Ok(())
+    }
+}
+
+impl std::error::Error for ConfigError {}
+
+impl Settings {
+    // 读取并校验配置, database.url 没有配置时使用环境变量 DATABASE_URL
+    // auth.jwt_secret 没有配置时使用环境变量 JWT_SECRET
+    pub fn load(overrides: &Overrides) -> Result<Self, ConfigError> {
+        let mut settings = Self::build(overrides, None)?;
+        if settings.database.url.is_none() {
+            settings.database.url = env::var("DATABASE_URL").ok();
+        }
+        if settings.auth.jwt_secret.is_none() {
+            settings.auth.jwt_secret = env::var("JWT_SECRET").ok();
+        }
+        settings.validate()?;
+        Ok(settings)
+    }
+
+    // env 为 None 时读取进程的环境变量
+    fn build(
+        overrides: &Overrides,
+        env: Option<HashMap<String, String>>,
+    ) -> Result<Self, ConfigError> {
+        let file = match &overrides.config_file {
+            Some(path) => File::from(path.as_path()).required(true),
+            None => File::with_name(DEFAULT_CONFIG_FILE).required(false),
+        };
+        let mut environment = Environment::with_prefix(ENV_PREFIX)
+            .prefix_separator(ENV_SEPARATOR)
+            .separator(ENV_SEPARATOR)
+            .list_separator(",")
+            .try_parsing(true)
+            .source(env);
+        for key in ENV_LIST_KEYS {
+            environment = environment.with_list_parse_key(key);
+        }
+
+        let load_error = |e: config::ConfigError| ConfigError(vec![e.to_string()]);
+        let mut settings: Settings = Config::builder()
+            .add_source(file)
+            .add_source(environment)
+            .build()
+            .and_then(|c| c.try_deserialize())
+            .map_err(load_error)?;
+
+        if !overrides.bind.is_empty() {
+            settings.server.bind = overrides.bind.clone();
+        }
+        if let Some(workers) = overrides.workers {
+            settings.server.workers = Some(workers);
+        }
+        if let Some(url) = &overrides.database_url {
+            settings.database.url = Some(url.clone());
+        }
+        Ok(settings)
+    }
+
+    pub fn validate(&self) -> Result<(), ConfigError> {
+        let mut problems = Vec::new();
+
+        if self.server.bind.is_empty() {
+            problems.push("server.bind must contain at least one address".to_string());
+        }
+        for address in &self.server.bind {
+            if address.to_socket_addrs().is_err() {
+                problems.push(format!(
+                    "server.bind `{}` is not a valid address, expected host:port such as 127.0.0.1:3000",
+                    address
+                ));
+            }
+        }
+        if self.server.workers == Some(0) {
+            problems.push("server.workers must be greater than 0".to_string());
+        }
+        if self.server.json_limit == 0 {
+            problems.push("server.json_limit must be greater than 0".to_string());
+        }
+        if self.server.import_limit == 0 {
+            problems.push("server.import_limit must be greater than 0".to_string());
+        }
+
+        // memory 存储不使用 database.url
+        match (self.database.backend(), &self.database.url) {
+            (StorageBackend::Memory, _) => {}
+            (_, None) => problems.push(
+                "database.url is not set, configure it or set DATABASE_URL".to_string(),
+            ),
+            (StorageBackend::Postgres, Some(url))
+                if !url.starts_with("postgres://") && !url.starts_with("postgresql://") =>
+            {
+                problems.push("database.url must start with postgres://".to_string())
+            }
+            (StorageBackend::Sqlite, Some(url)) if !url.starts_with("sqlite:") => {
+                problems.push("database.url must start with sqlite:".to_string())
+            }
+            _ => {}
+        }
+        if self.database.backend() == StorageBackend::Sqlite && !cfg!(feature = "sqlite") {
+            problems.push(
+                "database.backend sqlite requires building with --features sqlite".to_string(),
+            );
+        }
+        if self.database.max_connections == 0 {
+            problems.push("database.max_connections must be greater than 0".to_string());
+        }
+        if self.database.min_connections > self.database.max_connections {
+            problems.push(format!(
+                "database.min_connections ({}) must not exceed database.max_connections ({})",
+                self.database.min_connections, self.database.max_connections
+            ));
+        }
+        if self.database.acquire_timeout_secs == 0 {
+            problems.push("database.acquire_timeout_secs must be greater than 0".to_string());
+        }
+
+        if self.cors.allowed_origins.is_empty() {
+            problems.push("cors.allowed_origins must contain at least one origin".to_string());
+        }
+        for origin in &self.cors.allowed_origins {
+            if !origin.starts_with("http://") && !origin.starts_with("https://") {
+                problems.push(format!(
+                    "cors.allowed_origins `{}` must start with http:// or https://",
+                    origin
+                ));
+            }
+        }
+        for method in &self.cors.allowed_methods {
+            if method.parse::<Method>().is_err() {
+                problems.push(format!("cors.allowed_methods `{}` is not an HTTP method", method));
+            }
+        }
+
+        if self.trash.retention_days == 0 {
+            problems.push("trash.retention_days must be greater than 0".to_string());
+        }
+        if self.trash.purge_interval_secs == 0 {
+            problems.push("trash.purge_interval_secs must be greater than 0".to_string());
+        }
+
+        if self.auth.jwt_ttl_secs <= 0 {
+            problems.push("auth.jwt_ttl_secs must be greater than 0".to_string());
+        }
+
+        if problems.is_empty() {
+            Ok(())
+        } else {
+            Err(ConfigError(problems))
+        }
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use std::io::Write;
+
+    use super::*;
+
+    fn env_map(vars: &[(&str, &str)]) -> Option<HashMap<String, String>> {
+        Some(vars.iter().map(|(k, v)| (k.to_string(), v.to_string())).collect())
+    }
+
+    #[test]
+    fn file_then_env_then_cli() {
+        let path = env::temp_dir().join(format!("webservice-config-{}.toml", std::process::id()));
+        let mut file = std::fs::File::create(&path).unwrap();
+        writeln!(
+            file,
+            r#"
+[server]
+bind = ["127.0.0.1:4000"]
+workers = 2
+
+[database]
+url = "postgres://file@localhost/web_actix"
+max_connections = 5
+
+[cors]
+allowed_origins = ["https://example.com"]
+"#
+        )
+        .unwrap();
+
+        let overrides = Overrides {
+            config_file: Some(path.clone()),
+            workers: Some(8),
+            ..Default::default()
+        };
+        let env = env_map(&[
+            ("WEBSERVICE__DATABASE__MAX_CONNECTIONS", "20"),
+            ("WEBSERVICE__CORS__ALLOWED_ORIGINS", "https://a.com,http://localhost*"),
+            ("WEBSERVICE__SERVER__WORKERS", "4"),
+        ]);
+        let settings = Settings::build(&overrides, env).unwrap();
+        std::fs::remove_file(&path).ok();
+
+        assert_eq!(settings.server.bind, ["127.0.0.1:4000"]);
+        assert_eq!(settings.server.workers, Some(8));
+        assert_eq!(settings.database.url.as_deref(), Some("postgres://file@localhost/web_actix"));
+        assert_eq!(settings.database.max_connections, 20);
+        assert_eq!(settings.cors.allowed_origins, ["https://a.com", "http://localhost*"]);
+        assert_eq!(settings.cors.max_age, 3600);
+        assert!(settings.validate().is_ok());
+    }
+
+    #[test]
+    fn validation_lists_every_problem() {
+        let mut settings = Settings::default();
+        settings.server.bind = vec!["not an address".into()];
+        settings.server.workers = Some(0);
+        settings.database.url = None;
+        settings.database.min_connections = 20;
+        settings.cors.allowed_methods = vec!["GET".into(), "NOT A METHOD".into()];
+        settings.trash.purge_interval_secs = 0;
+
+        let err = settings.validate().unwrap_err();
+        assert_eq!(err.0.len(), 6, "{}", err);
+        assert!(err.to_string().contains("server.bind `not an address`"));
+    }
+
+    #[test]
+    fn memory_backend_needs_no_database_url() {
+        let env = env_map(&[("WEBSERVICE__DATABASE__BACKEND", "memory")]);
+        let settings = Settings::build(&Overrides::default(), env).unwrap();
+        assert_eq!(settings.database.backend(), StorageBackend::Memory);
+        assert!(settings.validate().is_ok());
+
+        let env = env_map(&[("WEBSERVICE__DATABASE__BACKEND", "mysql")]);
+        assert!(Settings::build(&Overrides::default(), env).is_err());
+    }
+
+    #[test]
+    fn sqlite_backend_from_url() {
+        let overrides = Overrides {
+            database_url: Some("sqlite://web_actix.db".into()),
+            ..Default::default()
+        };
+        let settings = Settings::build(&overrides, env_map(&[])).unwrap();
+        assert_eq!(settings.database.backend(), StorageBackend::Sqlite);
+        // 未启用 sqlite feature 时提示重新编译
+        assert_eq!(settings.validate().is_ok(), cfg!(feature = "sqlite"));
+
+        let env = env_map(&[("WEBSERVICE__DATABASE__BACKEND", "sqlite")]);
+        let err = Settings::build(&Overrides::default(), env).unwrap().validate().unwrap_err();
+        assert!(err.to_string().contains("database.url"), "{}", err);
+    }
+
+    #[test]
+    fn unknown_keys_are_rejected() {
+        let env = env_map(&[("WEBSERVICE__SERVER__WORKER", "4")]);
+        let err = Settings::build(&Overrides::default(), env).unwrap_err();
+        assert!(err.to_string().contains("worker"), "{}", err);
+    }
+
+    #[test]
+    fn jwt_secret_is_required() {
+        let mut auth = AuthSettings::default();
+        assert!(auth.jwt_secret().is_err());
+        for secret in ["", "  ", PLACEHOLDER_JWT_SECRET] {
+            auth.jwt_secret = Some(secret.into());
+            let err = auth.jwt_secret().unwrap_err();
+            assert!(err.to_string().contains("auth.jwt_secret"), "{}", err);
+        }
+
+        let env = env_map(&[("WEBSERVICE__AUTH__JWT_SECRET", "a-real-secret")]);
+        let settings = Settings::build(&Overrides::default(), env).unwrap();
+        assert_eq!(settings.auth.jwt_secret(), Ok("a-real-secret"));
+    }
+
+    #[test]
+    fn origin_prefix_matching() {
+        assert!(origin_matches("http://localhost*", "http://localhost:8080"));
+        assert!(!origin_matches("http://localhost*", "http://example.com"));
+        assert!(origin_matches("https://example.com", "https://example.com"));
+        assert!(!origin_matches("https://example.com", "https://example.com.evil"));
+    }
+}
@@ -0,0 +1,211 @@
+use actix_web::web::Bytes;
+use chrono::NaiveDateTime;
+use serde::{Deserialize, Serialize};
+use validator::Validate;
+
+use crate::errors::{AppError, FieldError};
+use crate::models::course::{Course, CourseFileFormat, CourseRecord, CreateCourse, Money};
+
+// 一次最多导入的课程数
+pub const MAX_IMPORT_ROWS: usize = 1000;
+
+// 解析并校验导入的每一行, 返回转换后的课程与所有行的错误
+// 行号从 1 开始: CSV 为表头之后的第几条记录, NDJSON 为第几行(空行不计入课程但计入行号)
+pub fn parse_courses(
+    format: CourseFileFormat,
+    teacher_id: i32,
+    body: &[u8],
+) -> (Vec<CreateCourse>, Vec<FieldError>) {
+    let records = match format {
+        CourseFileFormat::Csv => read_csv(body),
+        CourseFileFormat::Ndjson => read_ndjson(body),
+    };
+
+    let mut errors = Vec::new();
+    if records.is_empty() {
+        errors.push(FieldError::new("rows", "must contain at least one row"));
+    } else if records.len() > MAX_IMPORT_ROWS {
+        errors.push(FieldError::new(
+            "rows",
+            format!("must contain at most {} rows", MAX_IMPORT_ROWS),
+        ));
+    }
+
+    let mut courses = Vec::with_capacity(records.len());
+    for (row, record) in records {
+        let course = match record {
+            Ok(record) => record.into_course(teacher_id),
+            Err(message) => {
+                errors.push(FieldError::new(format!("rows[{}]", row), message));
+                continue;
+            }
+        };
+        match course.validate().map_err(AppError::from) {
+            Err(AppError::ValidationFailed(details)) => errors.extend(details.into_iter().map(|e| {
+                FieldError::new(format!("rows[{}].{}", row, e.field), e.message)
+            })),
+            _ => courses.push(course),
+        }
+    }
+    (courses, errors)
+}
+
+// CSV 不支持嵌套的结构, 价格拆分为 price(最小货币单位) 与 currency 两列
+#[derive(Deserialize)]
+struct CsvRecord {
+    name: String,
+    description: Option<String>,
+    format: Option<String>,
+    structure: Option<String>,
+    duration: Option<String>,
+    price: Option<i32>,
+    currency: Option<String>,
+    language: Option<String>,
+    level: Option<String>,
+    capacity: Option<i32>,
+}
+
+impl TryFrom<CsvRecord> for CourseRecord {
+    type Error = String;
+
+    fn try_from(record: CsvRecord) -> Result<Self, Self::Error> {
+        let price = match (record.price, record.currency) {
+            (Some(amount), Some(currency)) => Some(Money::new(amount, currency)),
+            (None, None) => None,
+            _ => return Err("price and currency must be provided together".into()),
+        };
+        Ok(CourseRecord {
+            name: record.name,
+            description: record.description,
+            format: record.format,
+            structure: record.structure,
+            duration: record.duration,
+            price,
+            language: record.language,
+            level: record.level,
+            capacity: record.capacity,
+        })
+    }
+}
+
+#[derive(Serialize)]
+struct CsvCourse<'a> {
+    teacher_id: i32,
+    id: i32,
+    name: &'a str,
+    time: Option<NaiveDateTime>,
+    description: Option<&'a str>,
+    format: Option<&'a str>,
+    structure: Option<&'a str>,
+    duration: Option<&'a str>,
+    price: Option<i32>,
+    currency: Option<&'a str>,
+    language: Option<&'a str>,
+    level: Option<&'a str>,
+    capacity: Option<i32>,
+    version: i32,
+}
+
+impl<'a> From<&'a Course> for CsvCourse<'a> {
+    fn from(course: &'a Course) -> Self {
+        CsvCourse {
+            teacher_id: course.teacher_id,
+            id: course.id,
+            name: &course.name,
+            time: course.time,
+            description: course.description.as_deref(),
+            format: course.format.as_deref(),
+            structure: course.structure.as_deref(),
+            duration: course.duration.as_deref(),
+            price: course.price.as_ref().map(|p| p.amount),
+            currency: course.price.as_ref().map(|p| p.currency.as_str()),
+            language: course.language.as_deref(),
+            level: course.level.as_deref(),
+            capacity: course.capacity,
+            version: course.version,
+        }
+    }
+}
+
+fn read_csv(body: &[u8]) -> Vec<(usize, Result<CourseRecord, String>)> {
+    csv::ReaderBuilder::new()
+        .trim(csv::Trim::All)
+        .from_reader(body)
+        .deserialize::<CsvRecord>()
+        .enumerate()
+        .map(|(index, record)| {
+            (index + 1, record.map_err(|e| e.to_string()).and_then(CourseRecord::try_from))
+        })
+        .collect()
+}
+
+fn read_ndjson(body: &[u8]) -> Vec<(usize, Result<CourseRecord, String>)> {
+    body.split(|b| *b == b'\n')
+        .enumerate()
+        .filter(|(_, line)| !line.trim_ascii().is_empty())
+        .map(|(index, line)| (index + 1, serde_json::from_slice(line).map_err(|e| e.to_string())))
+        .collect()
+}
+
+// 把一批课程编码为导出文件的一部分, CSV 只在第一批写入表头
+pub fn encode_courses(
+    format: CourseFileFormat,
+    courses: &[Course],
+    header: bool,
+) -> Result<Bytes, AppError> {
+    let mut buf = Vec::new();
+    match format {
+        CourseFileFormat::Csv => {
+            let mut writer = csv::WriterBuilder::new().has_headers(header).from_writer(&mut buf);
+            for course in courses {
+                writer.serialize(CsvCourse::from(course)).map_err(|e| AppError::ActixError(e.to_string()))?;
+            }
+            writer.flush().map_err(|e| AppError::ActixError(e.to_string()))?;
+        }
+        CourseFileFormat::Ndjson => {
+            for course in courses {
+                serde_json::to_writer(&mut buf, course).map_err(|e| AppError::ActixError(e.to_string()))?;
+                buf.push(b'\n');
+            }
+        }
+    }
+    Ok(Bytes::from(buf))
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    #[test]
+    fn parse_reports_errors_per_row() {
+        let csv = "name,price,currency,level\nRust basics,100,CNY,Beginner\n ,-1,CNY,Beginner\nAsync Rust,abc,CNY,\nFree,,USD,\n";
+        let (courses, errors) = parse_courses(CourseFileFormat::Csv, 1, csv.as_bytes());
+        assert_eq!(courses.len(), 1);
+        assert_eq!(courses[0].teacher_id, 1);
+        assert_eq!(courses[0].level.as_deref(), Some("Beginner"));
+        assert_eq!(courses[0].price, Some(Money::new(100, "CNY")));
+        let fields: Vec<&str> = errors.iter().map(|e| e.field.as_str()).collect();
+        assert_eq!(fields, ["rows[2].name", "rows[2].price", "rows[3]", "rows[4]"]);
+
+        let ndjson = "{\"name\": \"Rust basics\", \"price\": null}\n\n{\"name\": \"Web\", \"level\": \"Expert\"}\n{\"name\": \"Go\", \"price\": {\"amount\": 500, \"currency\": \"XYZ\"}}\n";
+        let (courses, errors) = parse_courses(CourseFileFormat::Ndjson, 1, ndjson.as_bytes());
+        assert_eq!(courses.len(), 1);
+        assert_eq!(
+            errors,
+            [
+                FieldError::new("rows[3].level", "must be one of: Beginner, Intermediate, Advanced"),
+                FieldError::new("rows[4].price", "must be one of: CNY, USD, EUR, GBP, HKD, JPY"),
+            ]
+        );
+
+        let (_, errors) = parse_courses(CourseFileFormat::Csv, 1, b"name,price\n");
+        assert_eq!(errors[0].field, "rows");
+    }
+
+    #[test]
+    fn csv_content_type() {
+        assert_eq!(CourseFileFormat::from_content_type("text/csv; charset=utf-8"), Some(CourseFileFormat::Csv));
+        assert_eq!(CourseFileFormat::from_content_type("application/x-ndjson"), Some(CourseFileFormat::Ndjson));
+        assert_eq!(CourseFileFormat::from_content_type("application/json"), None);
+    }
+}
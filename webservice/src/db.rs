@@ -0,0 +1,80 @@
+#[cfg(feature = "sqlite")]
+use std::str::FromStr;
+
+use sqlx::postgres::PgPool;
+#[cfg(feature = "sqlite")]
+use sqlx::sqlite::{SqliteConnectOptions, SqlitePool};
+
+use crate::config::{DatabaseSettings, StorageBackend};
+
+// 数据库连接池, 使用哪种数据库由 database.backend 决定
+// 只用于连接管理、迁移和健康检查, 读写数据都通过 repository 中的存储接口
+#[derive(Debug, Clone)]
+pub enum DbPool {
+    Postgres(PgPool),
+    #[cfg(feature = "sqlite")]
+    Sqlite(SqlitePool),
+}
+
+// 对每种连接池执行同样的代码, 如 with_pool!(pool, p => p.size())
+macro_rules! with_pool {
+    ($pool:expr, $p:ident => $body:expr) => {
+        match $pool {
+            $crate::db::DbPool::Postgres($p) => $body,
+            #[cfg(feature = "sqlite")]
+            $crate::db::DbPool::Sqlite($p) => $body,
+        }
+    };
+}
+pub(crate) use with_pool;
+
+impl DbPool {
+    // memory 存储不需要连接池, 返回 None
+    pub async fn connect(settings: &DatabaseSettings) -> Result<Option<DbPool>, sqlx::Error> {
+        // validate 已经保证使用数据库时 url 存在
+        let url = settings.url.as_deref().unwrap_or_default();
+        let pool = match settings.backend() {
+            StorageBackend::Postgres => DbPool::Postgres(settings.pool_options().connect(url).await?),
+            #[cfg(feature = "sqlite")]
+            StorageBackend::Sqlite => {
+                // 数据库文件不存在时自动创建, sqlx 默认开启外键检查
+                let options = SqliteConnectOptions::from_str(url)?.create_if_missing(true);
+                DbPool::Sqlite(settings.pool_options().connect_with(options).await?)
+            }
+            #[cfg(not(feature = "sqlite"))]
+            StorageBackend::Sqlite => {
+                return Err(sqlx::Error::Configuration(
+                    "SQLite support is not compiled in, build with --features sqlite".into(),
+                ))
+            }
+            StorageBackend::Memory => return Ok(None),
+        };
+        Ok(Some(pool))
+    }
+
+    pub fn backend(&self) -> StorageBackend {
+        match self {
+            DbPool::Postgres(_) => StorageBackend::Postgres,
+            #[cfg(feature = "sqlite")]
+            DbPool::Sqlite(_) => StorageBackend::Sqlite,
+        }
+    }
+
+    // 连接池当前持有的连接数, 包括空闲和正在使用的
+    pub fn size(&self) -> u32 {
+        with_pool!(self, p => p.size())
+    }
+
+    pub fn num_idle(&self) -> usize {
+        with_pool!(self, p => p.num_idle())
+    }
+
+    pub fn max_connections(&self) -> u32 {
+        with_pool!(self, p => p.options().get_max_connections())
+    }
+
+    // 等待所有连接归还后关闭
+    pub async fn close(&self) {
+        with_pool!(self, p => p.close().await)
+    }
+}
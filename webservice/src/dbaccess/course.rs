@@ -1,54 +1,223 @@
-use crate::models::course::{Course, CreateCourse, UpdateCourse};
+use crate::models::course::{
+    Course, CoursePage, CourseRow, CourseQuery, CourseSearchHit, CourseSearchPage, CourseSearchQuery,
+    CourseSort, CreateCourse, PatchCourse, SortKey, SortOrder,
+};
 
-use sqlx::postgres::PgPool;
+use chrono::Duration;
+use sqlx::postgres::{PgConnection, Postgres};
+use sqlx::Connection;
+use sqlx::QueryBuilder;
+use crate::dbaccess::COURSE_TEACHER_FKEY;
 use crate::errors::AppError;
+use crate::etag::{check_version, IfMatch};
+use tracing::instrument;
 
+// 全文检索的文档, 需要与 migrations 中 course_search_idx 的表达式一致才能使用索引
+const COURSE_DOCUMENT: &str = "(\
+    setweight(to_tsvector('english', coalesce(name, '')), 'A') || \
+    setweight(to_tsvector('english', coalesce(description, '')), 'B') || \
+    setweight(to_tsvector('english', coalesce(structure, '')), 'C'))";
+
+const HEADLINE_OPTIONS: &str = "StartSel=<mark>, StopSel=</mark>, MaxWords=35, MinWords=15";
+
+#[instrument(skip_all, fields(teacher_id = teacher_id))]
 pub async fn get_course_for_teacher_db(
-    pool: &PgPool, 
-    teacher_id: i32
-) -> Result<Vec<Course>, AppError> {
+    conn: &mut PgConnection, 
+    teacher_id: i32,
+    query: &CourseQuery,
+) -> Result<CoursePage, AppError> {
+    query.validate()?;
+    let limit = query.limit();
+    let offset = query.offset();
 
-    let rows: Vec<Course> = sqlx::query_as!(
-        Course,
-        r#"Select * from course where teacher_id = $1"#,
-        teacher_id,
-    )
-        .fetch_all(pool)
+    let mut count_builder = QueryBuilder::new("select count(*) from course");
+    push_course_filters(&mut count_builder, teacher_id, query);
+    let total: i64 = count_builder
+        .build_query_scalar()
+        .fetch_one(&mut *conn)
+        .await?;
+
+    let sort_expr = match query.sort {
+        CourseSort::Time => "coalesce(time, 'epoch'::timestamp)",
+        CourseSort::Name => "name",
+        CourseSort::Price => "coalesce(price, 0)",
+    };
+    let (direction, compare) = match query.order {
+        SortOrder::Asc => ("asc", " > "),
+        SortOrder::Desc => ("desc", " < "),
+    };
+
+    let mut builder = QueryBuilder::new("select * from course");
+    push_course_filters(&mut builder, teacher_id, query);
+    if let Some(cursor) = &query.cursor {
+        // 游标分页: 从上一页最后一条记录的 (排序值, id) 之后开始
+        let (key, id) = query.sort.parse_cursor(query.order, cursor)?;
+        builder.push(format!(" and ({}, id){}(", sort_expr, compare));
+        match key {
+            SortKey::Time(time) => builder.push_bind(time),
+            SortKey::Name(name) => builder.push_bind(name),
+            SortKey::Price(price) => builder.push_bind(price),
+        };
+        builder.push(", ").push_bind(id).push(")");
+    }
+    builder.push(format!(" order by {} {}, id {}", sort_expr, direction, direction));
+    // 多查一条用于判断是否还有下一页
+    builder.push(" limit ").push_bind(limit + 1);
+    if query.cursor.is_none() {
+        builder.push(" offset ").push_bind(offset);
+    }
+
+    let mut items: Vec<Course> = builder
+        .build_query_as()
+        .fetch_all(&mut *conn)
+        .await?;
+
+    let next_cursor = if items.len() as i64 > limit {
+        items.truncate(limit as usize);
+        items.last().map(|c| query.sort.cursor(query.order, c))
+    } else {
+        None
+    };
+
+    Ok(CoursePage {
+        items,
+        total,
+        limit,
+        offset: if query.cursor.is_some() { 0 } else { offset },
+        next_cursor,
+    })
+}
+
+fn push_course_filters(
+    builder: &mut QueryBuilder<'_, Postgres>,
+    teacher_id: i32,
+    query: &CourseQuery,
+) {
+    builder.push(" where teacher_id = ").push_bind(teacher_id);
+    builder.push(" and deleted_at is null");
+    if let Some(level) = &query.level {
+        builder.push(" and level = ").push_bind(level.clone());
+    }
+    if let Some(language) = &query.language {
+        builder.push(" and language = ").push_bind(language.clone());
+    }
+    if let Some(format) = &query.format {
+        builder.push(" and format = ").push_bind(format.clone());
+    }
+    if let Some(currency) = &query.currency {
+        builder.push(" and currency = ").push_bind(currency.clone());
+    }
+    if let Some(min_price) = query.min_price {
+        builder.push(" and price >= ").push_bind(min_price);
+    }
+    if let Some(max_price) = query.max_price {
+        builder.push(" and price <= ").push_bind(max_price);
+    }
+}
+
+#[instrument(skip_all, fields(q = %query.q))]
+pub async fn search_courses_db(
+    conn: &mut PgConnection,
+    query: &CourseSearchQuery,
+) -> Result<CourseSearchPage, AppError> {
+    query.validate()?;
+    let limit = query.limit();
+    let offset = query.offset();
+
+    let mut count_builder = QueryBuilder::new("select count(*) from course");
+    push_search_filters(&mut count_builder, query);
+    let total: i64 = count_builder
+        .build_query_scalar()
+        .fetch_one(&mut *conn)
         .await?;
 
-    Ok(rows)
+    let mut builder = QueryBuilder::new("select *, ts_rank(");
+    builder
+        .push(COURSE_DOCUMENT)
+        .push(", websearch_to_tsquery('english', ")
+        .push_bind(query.q.clone())
+        .push(")) as rank, ts_headline('english', name, websearch_to_tsquery('english', ")
+        .push_bind(query.q.clone())
+        .push(format!("), '{}') as name_highlight", HEADLINE_OPTIONS))
+        .push(", ts_headline('english', coalesce(description, structure, ''), websearch_to_tsquery('english', ")
+        .push_bind(query.q.clone())
+        .push(format!("), '{}') as snippet from course", HEADLINE_OPTIONS));
+    push_search_filters(&mut builder, query);
+    builder.push(" order by rank desc, id asc limit ").push_bind(limit);
+    builder.push(" offset ").push_bind(offset);
+
+    let items: Vec<CourseSearchHit> = builder
+        .build_query_as()
+        .fetch_all(&mut *conn)
+        .await?;
+
+    Ok(CourseSearchPage {
+        items,
+        total,
+        limit,
+        offset,
+    })
+}
+
+fn push_search_filters(builder: &mut QueryBuilder<'_, Postgres>, query: &CourseSearchQuery) {
+    builder
+        .push(" where ")
+        .push(COURSE_DOCUMENT)
+        .push(" @@ websearch_to_tsquery('english', ")
+        .push_bind(query.q.clone())
+        .push(") and deleted_at is null");
+    if let Some(level) = &query.level {
+        builder.push(" and level = ").push_bind(level.clone());
+    }
+    if let Some(language) = &query.language {
+        builder.push(" and language = ").push_bind(language.clone());
+    }
 }
- 
 
+#[instrument(skip_all, fields(teacher_id = teacher_id, course_id = course_id))]
 pub async fn get_course_detail_db(
-    pool: &PgPool, 
+    conn: &mut PgConnection, 
     teacher_id: i32, 
     course_id: i32
 ) -> Result<Course, AppError> {
     let row  = sqlx::query_as!(
-        Course,
-        r#"Select * From course where teacher_id = $1 and id = $2"#,
+        CourseRow,
+        r#"Select * From course where teacher_id = $1 and id = $2 and deleted_at is null"#,
         teacher_id,
         course_id,
     )
-        .fetch_optional(pool)
+        .fetch_optional(&mut *conn)
         .await?;
     if let Some(row) = row {
-        Ok(row)
+        Ok(row.try_into()?)
     }else {
-        Err(AppError::NotFound("Cound't found course".into()))
+        Err(AppError::NotFound("course".into()))
     }
 }
 
+#[instrument(skip_all, fields(teacher_id = new_course.teacher_id))]
 pub async fn post_new_course_db(
-    pool: &PgPool, 
+    conn: &mut PgConnection, 
     new_course: CreateCourse,
 ) -> Result<Course, AppError> {
+    // 外键只能保证老师存在, 已删除的老师也不能再创建课程
+    // 锁住老师直到插入完成, 与 delete_teacher_db 互斥, 避免删除老师时漏掉新建的课程
+    let mut tx = conn.begin().await?;
+    let teacher_active = sqlx::query_scalar!(
+        r#"select id from teacher where id = $1 and deleted_at is null for share"#,
+        new_course.teacher_id,
+    )
+        .fetch_optional(&mut *tx)
+        .await?;
+    if teacher_active.is_none() {
+        return Err(AppError::missing_reference(COURSE_TEACHER_FKEY));
+    }
+
     let row = sqlx::query_as!(
-        Course,
-        r#"Insert into course(teacher_id, name, description, format, structure, duration, price, language, level) 
-        values ($1, $2, $3, $4, $5, $6, $7, $8, $9)
-        Returning id, teacher_id, name, time, description, format, structure, duration, price, language, level
+        CourseRow,
+        r#"Insert into course(teacher_id, name, description, format, structure, duration, price, currency, language, level, capacity) 
+        values ($1, $2, $3, $4, $5, $6, $7, $8, $9, $10, $11)
+        Returning id, teacher_id, name, time, description, format, structure, duration, price, currency, language, level, capacity, deleted_at, version
         "#,
         new_course.teacher_id, 
         new_course.name,
@@ -56,99 +225,125 @@ pub async fn post_new_course_db(
         new_course.format,
         new_course.structure,
         new_course.duration,
-        new_course.price,
+        new_course.price.as_ref().map(|p| p.amount),
+        new_course.price.as_ref().map(|p| p.currency.as_str()),
         new_course.language,
-        new_course.level
+        new_course.level,
+        new_course.capacity,
     )
-        .fetch_one(pool)
+        .fetch_one(&mut *tx)
         .await?;
-    Ok(row)
+    tx.commit().await?;
+    Ok(row.try_into()?)
 }
 
+// 与 post_new_course_db 一样锁住老师, 所有课程在同一个事务中插入
+#[instrument(skip_all, fields(teacher_id = teacher_id, rows = courses.len()))]
+pub async fn import_courses_db(
+    conn: &mut PgConnection,
+    teacher_id: i32,
+    courses: Vec<CreateCourse>,
+) -> Result<u64, AppError> {
+    let mut tx = conn.begin().await?;
+    let teacher_active = sqlx::query_scalar!(
+        r#"select id from teacher where id = $1 and deleted_at is null for share"#,
+        teacher_id,
+    )
+        .fetch_optional(&mut *tx)
+        .await?;
+    if teacher_active.is_none() {
+        return Err(AppError::missing_reference(COURSE_TEACHER_FKEY));
+    }
+
+    if courses.is_empty() {
+        return Ok(0);
+    }
+
+    // 所有课程在一条语句中插入, 每门课程 11 个参数,
+    // MAX_IMPORT_ROWS 门课程的参数个数远小于 Postgres 的上限 65535
+    let mut builder = QueryBuilder::new(
+        "insert into course (teacher_id, name, description, format, structure, duration, price, currency, language, level, capacity) ",
+    );
+    builder.push_values(courses, |mut row, course| {
+        row.push_bind(teacher_id)
+            .push_bind(course.name)
+            .push_bind(course.description)
+            .push_bind(course.format)
+            .push_bind(course.structure)
+            .push_bind(course.duration)
+            .push_bind(course.price.as_ref().map(|p| p.amount))
+            .push_bind(course.price.map(|p| p.currency))
+            .push_bind(course.language)
+            .push_bind(course.level)
+            .push_bind(course.capacity);
+    });
+    let imported = builder
+        .build()
+        .execute(&mut *tx)
+        .await?
+        .rows_affected();
+    tx.commit().await?;
+    Ok(imported)
+}
+
+#[instrument(skip_all, fields(teacher_id = teacher_id, course_id = id))]
 pub async fn delete_course_db(
-    pool: &PgPool, 
+    conn: &mut PgConnection, 
     teacher_id: i32, 
-    id: i32
-) -> Result<String, AppError>  {
-    let course_row = sqlx::query!(
-        r#"DELETE FROM course where teacher_id = $1 and id = $2"#,
+    id: i32,
+    if_match: &IfMatch,
+) -> Result<(), AppError>  {
+    let mut tx = conn.begin().await?;
+    let version = sqlx::query_scalar!(
+        r#"select version from course where teacher_id = $1 and id = $2 and deleted_at is null for update"#,
         teacher_id,
         id,
     )
-        .execute(pool)
-        .await?;
-    Ok(format!("Deleted {:?} record", course_row))
+        .fetch_optional(&mut *tx)
+        .await?
+        .ok_or_else(|| AppError::NotFound("course".into()))?;
+    check_version(if_match, version)?;
 
+    // 只移入回收站, 由 purge_deleted_courses_db 彻底删除
+    sqlx::query!(
+        r#"UPDATE course SET deleted_at = now(), version = version + 1 where teacher_id = $1 and id = $2"#,
+        teacher_id,
+        id,
+    )
+        .execute(&mut *tx)
+        .await?;
+    tx.commit().await?;
+    Ok(())
 }
 
+#[instrument(skip_all, fields(teacher_id = teacher_id, course_id = id))]
 pub async fn update_course_db(
-    pool: &PgPool,
+    conn: &mut PgConnection,
     teacher_id: i32,
     id: i32,
-    update_course: UpdateCourse, 
+    if_match: &IfMatch,
+    patch: PatchCourse,
 ) -> Result<Course, AppError> {
 
-    // 查出原始的记录
-    let current_course_row = sqlx::query_as!(
-        Course,
-        r#"SELECT * FROM course where teacher_id = $1 and id = $2"#,
+    // 查出原始的记录并锁住, 合并与写入在同一个事务中完成, 避免覆盖其他请求的修改
+    let mut tx = conn.begin().await?;
+    let mut course: Course = sqlx::query_as!(
+        CourseRow,
+        r#"SELECT * FROM course where teacher_id = $1 and id = $2 and deleted_at is null FOR UPDATE"#,
         teacher_id, 
         id,
     )
-        .fetch_one(pool)
-        .await
-        .map_err(|_err| { AppError::NotFound("Course Id not found".into())})?;
-
-    let name: String = if let Some(name) = update_course.name {
-        name
-    } else {
-        current_course_row.name
-    };
-
-    let description: String = if let Some(description) = update_course.description {
-        description
-    }else {
-        current_course_row.description.unwrap_or_default()
-    };
-
-    let format = if let Some(format) = update_course.format {
-        format
-    } else {
-        current_course_row.format.unwrap_or_default()
-    };
-
-    let structure = if let Some(structure) = update_course.structure {
-        structure
-    } else {
-        current_course_row.structure.unwrap_or_default()
-    };
+        .fetch_optional(&mut *tx)
+        .await?
+        .ok_or_else(|| AppError::NotFound("course".into()))?
+        .try_into()?;
+    check_version(if_match, course.version)?;
 
-    let duration = if let Some(duration) = update_course.duration {
-        duration
-    } else {
-        current_course_row.duration.unwrap_or_default()
-    };
-
-    let price: i32 = if let Some(price) = update_course.price {
-        price
-    } else {
-        current_course_row.price.unwrap_or_default()
-    };
-
-    let language: String = if let Some(language) = update_course.language {
-        language
-    } else {
-        current_course_row.language.unwrap_or_default()
-    };
-
-    let level: String = if let Some(level) = update_course.level {
-        level 
-    } else {
-        current_course_row.level.unwrap_or_default()
-    };
+    // 没有传入的字段保留原值, 传入 null 的字段保存为 NULL
+    patch.apply(&mut course);
 
     let course_row = sqlx::query_as!(
-        Course,
+        CourseRow,
         r#"UPDATE course SET
             name = $1, 
             description = $2,
@@ -156,27 +351,98 @@ pub async fn update_course_db(
             structure = $4,
             duration = $5,
             price = $6,
-            language = $7,
-            level = $8
-        where teacher_id = $9 and id = $10
-        RETURNING id, teacher_id, name, time, description, format, structure, duration, price, language, level
+            currency = $7,
+            language = $8,
+            level = $9,
+            capacity = $10,
+            version = version + 1
+        where teacher_id = $11 and id = $12 and deleted_at is null
+        RETURNING id, teacher_id, name, time, description, format, structure, duration, price, currency, language, level, capacity, deleted_at, version
         "#,
-        name,
-        description,
-        format,
-        structure,
-        duration,
-        price,
-        language,
-        level,
+        course.name,
+        course.description,
+        course.format,
+        course.structure,
+        course.duration,
+        course.price.as_ref().map(|p| p.amount),
+        course.price.as_ref().map(|p| p.currency.as_str()),
+        course.language,
+        course.level,
+        course.capacity,
         teacher_id, 
         id,
     )
-        .fetch_one(pool)
-        .await;
-    if let Ok(course) = course_row {
-        Ok(course)
-    }else {
-        Err(AppError::NotFound("Course id not found".into()))
+        .fetch_optional(&mut *tx)
+        .await?
+        .ok_or_else(|| AppError::NotFound("course".into()))?;
+    tx.commit().await?;
+    Ok(course_row.try_into()?)
+}
+
+#[instrument(skip_all, fields(teacher_id = teacher_id))]
+pub async fn get_deleted_courses_db(
+    conn: &mut PgConnection,
+    teacher_id: i32,
+) -> Result<Vec<Course>, AppError> {
+    let rows = sqlx::query_as!(
+        CourseRow,
+        r#"select * from course where teacher_id = $1 and deleted_at is not null
+        order by deleted_at desc, id desc"#,
+        teacher_id,
+    )
+        .fetch_all(&mut *conn)
+        .await?;
+    let courses = rows.into_iter().map(Course::try_from).collect::<Result<_, _>>()?;
+    Ok(courses)
+}
+
+#[instrument(skip_all, fields(teacher_id = teacher_id, course_id = id))]
+pub async fn restore_course_db(
+    conn: &mut PgConnection,
+    teacher_id: i32,
+    id: i32,
+) -> Result<Course, AppError> {
+    let course_row = sqlx::query_as!(
+        CourseRow,
+        r#"update course set deleted_at = null, version = version + 1
+        where teacher_id = $1 and id = $2 and deleted_at is not null
+            and exists(select 1 from teacher where id = $1 and deleted_at is null)
+        returning id, teacher_id, name, time, description, format, structure, duration, price, currency, language, level, capacity, deleted_at, version
+        "#,
+        teacher_id,
+        id,
+    )
+        .fetch_optional(&mut *conn)
+        .await?;
+    if let Some(course) = course_row {
+        return Ok(course.try_into()?);
     }
+
+    // 区分课程不在回收站中和老师已被删除
+    let in_trash = sqlx::query_scalar!(
+        r#"select exists(select 1 from course where teacher_id = $1 and id = $2 and deleted_at is not null) as "exists!""#,
+        teacher_id,
+        id,
+    )
+        .fetch_one(&mut *conn)
+        .await?;
+    if in_trash {
+        Err(AppError::missing_reference(COURSE_TEACHER_FKEY))
+    } else {
+        Err(AppError::NotFound("course".into()))
+    }
+}
+
+#[instrument(skip_all)]
+pub async fn purge_deleted_courses_db(
+    conn: &mut PgConnection,
+    retention: Duration,
+) -> Result<u64, AppError> {
+    let result = sqlx::query!(
+        r#"delete from course where deleted_at < now() - make_interval(secs => $1)"#,
+        retention.num_seconds() as f64,
+    )
+        .execute(&mut *conn)
+        .await?;
+    Ok(result.rows_affected())
 }
@@ -0,0 +1,11 @@
+use tracing::instrument;
+
+use crate::db::{with_pool, DbPool};
+use crate::errors::AppError;
+
+// 执行一条最简单的查询, 确认能从连接池拿到可用的连接
+#[instrument(skip_all)]
+pub async fn ping_db(pool: &DbPool) -> Result<(), AppError> {
+    with_pool!(pool, p => sqlx::query("select 1").execute(p).await.map(|_| ())?);
+    Ok(())
+}
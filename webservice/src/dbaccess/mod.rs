@@ -1,2 +1,48 @@
+use sqlx::error::ErrorKind;
+
+use crate::errors::AppError;
+
 pub mod course;
+pub mod section;
+pub mod student;
 pub mod teacher;
+pub mod health;
+#[cfg(feature = "sqlite")]
+pub mod sqlite;
+
+// 数据库中的约束名, 数据库没有返回约束名或由代码检查的约束也使用这些名称, 保证错误信息一致
+pub const COURSE_TEACHER_FKEY: &str = "course_teacher_id_fkey";
+pub const COURSE_PRICE_CHECK: &str = "course_price_check";
+pub const COURSE_CAPACITY_CHECK: &str = "course_capacity_check";
+pub const TEACHER_EMAIL_KEY: &str = "teacher_email_key";
+pub const STUDENT_EMAIL_KEY: &str = "student_email_key";
+
+// 删除可能仍被引用的记录时使用, 外键错误转换为 Conflict, 其他错误与 From<sqlx::Error> 相同
+pub fn still_referenced_error(err: sqlx::Error) -> AppError {
+    match &err {
+        sqlx::Error::Database(db_err) if matches!(db_err.kind(), ErrorKind::ForeignKeyViolation) => {
+            AppError::still_referenced(db_err.constraint().unwrap_or(COURSE_TEACHER_FKEY))
+        }
+        _ => err.into(),
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use actix_web::{http::StatusCode, ResponseError};
+    use sqlx::postgres::PgPool;
+
+    use super::*;
+
+    // 同一个外键错误, 默认按引用的记录不存在处理, 删除时由调用方转换为 409
+    #[sqlx::test(fixtures("../handlers/fixtures/teachers.sql", "../handlers/fixtures/courses.sql"))]
+    async fn foreign_key_errors_depend_on_the_call_site(db_pool: PgPool) {
+        let delete = || sqlx::query("delete from teacher where id = 1").execute(&db_pool);
+        let err = AppError::from(delete().await.unwrap_err());
+        assert_eq!(err.status_code(), StatusCode::UNPROCESSABLE_ENTITY);
+
+        let err = delete().await.map_err(still_referenced_error).unwrap_err();
+        assert_eq!(err.status_code(), StatusCode::CONFLICT);
+        assert!(err.to_string().contains(COURSE_TEACHER_FKEY));
+    }
+}
@@ -0,0 +1,384 @@
+use sqlx::postgres::PgConnection;
+use sqlx::Connection;
+use tracing::instrument;
+
+use crate::errors::AppError;
+use crate::models::section::{
+    insert_position, CreateLesson, CreateSection, Lesson, Reorder, Section, SectionRow,
+    UpdateLesson, UpdateSection,
+};
+
+// 课程必须存在、未删除且属于该老师
+async fn ensure_course(
+    conn: &mut PgConnection,
+    teacher_id: i32,
+    course_id: i32,
+) -> Result<(), AppError> {
+    let exists = sqlx::query_scalar!(
+        r#"select exists(select 1 from course where id = $1 and teacher_id = $2 and deleted_at is null) as "exists!""#,
+        course_id,
+        teacher_id,
+    )
+        .fetch_one(conn)
+        .await?;
+    match exists {
+        true => Ok(()),
+        false => Err(AppError::NotFound("course".into())),
+    }
+}
+
+// 修改章节或课时之前锁住课程, 同一门课程的修改依次执行, 保证 position 连续
+// 同时避免课程在修改期间被删除
+async fn lock_course(
+    conn: &mut PgConnection,
+    teacher_id: i32,
+    course_id: i32,
+) -> Result<(), AppError> {
+    sqlx::query_scalar!(
+        r#"select id from course where id = $1 and teacher_id = $2 and deleted_at is null for no key update"#,
+        course_id,
+        teacher_id,
+    )
+        .fetch_optional(conn)
+        .await?
+        .map(|_| ())
+        .ok_or_else(|| AppError::NotFound("course".into()))
+}
+
+async fn ensure_section(
+    conn: &mut PgConnection,
+    course_id: i32,
+    section_id: i32,
+) -> Result<(), AppError> {
+    sqlx::query_scalar!(
+        r#"select id from section where id = $1 and course_id = $2"#,
+        section_id,
+        course_id,
+    )
+        .fetch_optional(conn)
+        .await?
+        .map(|_| ())
+        .ok_or_else(|| AppError::NotFound("section".into()))
+}
+
+async fn load_sections(conn: &mut PgConnection, course_id: i32) -> Result<Vec<Section>, AppError> {
+    let sections = sqlx::query_as!(
+        SectionRow,
+        r#"select course_id, id, title, position from section where course_id = $1 order by position"#,
+        course_id,
+    )
+        .fetch_all(&mut *conn)
+        .await?;
+    let lessons = sqlx::query_as!(
+        Lesson,
+        r#"select lesson.section_id, lesson.id, lesson.title, lesson.content, lesson.duration_minutes, lesson.position
+        from lesson join section on section.id = lesson.section_id
+        where section.course_id = $1
+        order by lesson.position"#,
+        course_id,
+    )
+        .fetch_all(&mut *conn)
+        .await?;
+    Ok(Section::assemble(sections, lessons))
+}
+
+async fn load_lessons(conn: &mut PgConnection, section_id: i32) -> Result<Vec<Lesson>, AppError> {
+    let lessons = sqlx::query_as!(
+        Lesson,
+        r#"select section_id, id, title, content, duration_minutes, position
+        from lesson where section_id = $1 order by position"#,
+        section_id,
+    )
+        .fetch_all(conn)
+        .await?;
+    Ok(lessons)
+}
+
+#[instrument(skip_all, fields(teacher_id = teacher_id, course_id = course_id))]
+pub async fn get_sections_db(
+    conn: &mut PgConnection,
+    teacher_id: i32,
+    course_id: i32,
+) -> Result<Vec<Section>, AppError> {
+    ensure_course(&mut *conn, teacher_id, course_id).await?;
+    load_sections(&mut *conn, course_id).await
+}
+
+#[instrument(skip_all, fields(teacher_id = teacher_id, course_id = course_id))]
+pub async fn post_new_section_db(
+    conn: &mut PgConnection,
+    teacher_id: i32,
+    course_id: i32,
+    new_section: CreateSection,
+) -> Result<Section, AppError> {
+    let mut tx = conn.begin().await?;
+    lock_course(&mut tx, teacher_id, course_id).await?;
+
+    let count = sqlx::query_scalar!(
+        r#"select count(*) as "count!" from section where course_id = $1"#,
+        course_id,
+    )
+        .fetch_one(&mut *tx)
+        .await?;
+    let position = insert_position(new_section.position, count as i32);
+    sqlx::query!(
+        r#"update section set position = position + 1 where course_id = $1 and position >= $2"#,
+        course_id,
+        position,
+    )
+        .execute(&mut *tx)
+        .await?;
+    let row = sqlx::query_as!(
+        SectionRow,
+        r#"insert into section (course_id, title, position) values ($1, $2, $3)
+        returning course_id, id, title, position"#,
+        course_id,
+        new_section.title,
+        position,
+    )
+        .fetch_one(&mut *tx)
+        .await?;
+    tx.commit().await?;
+    Ok(row.into())
+}
+
+#[instrument(skip_all, fields(teacher_id = teacher_id, course_id = course_id, section_id = section_id))]
+pub async fn update_section_db(
+    conn: &mut PgConnection,
+    teacher_id: i32,
+    course_id: i32,
+    section_id: i32,
+    update: UpdateSection,
+) -> Result<Section, AppError> {
+    let mut tx = conn.begin().await?;
+    lock_course(&mut tx, teacher_id, course_id).await?;
+
+    let row = sqlx::query_as!(
+        SectionRow,
+        r#"update section set title = $1 where id = $2 and course_id = $3
+        returning course_id, id, title, position"#,
+        update.title,
+        section_id,
+        course_id,
+    )
+        .fetch_optional(&mut *tx)
+        .await?
+        .ok_or_else(|| AppError::NotFound("section".into()))?;
+    let mut section = Section::from(row);
+    section.lessons = load_lessons(&mut tx, section_id).await?;
+    tx.commit().await?;
+    Ok(section)
+}
+
+#[instrument(skip_all, fields(teacher_id = teacher_id, course_id = course_id, section_id = section_id))]
+pub async fn delete_section_db(
+    conn: &mut PgConnection,
+    teacher_id: i32,
+    course_id: i32,
+    section_id: i32,
+) -> Result<(), AppError> {
+    let mut tx = conn.begin().await?;
+    lock_course(&mut tx, teacher_id, course_id).await?;
+
+    // 课时由外键的 on delete cascade 一起删除, 之后的章节依次前移
+    let position = sqlx::query_scalar!(
+        r#"delete from section where id = $1 and course_id = $2 returning position"#,
+        section_id,
+        course_id,
+    )
+        .fetch_optional(&mut *tx)
+        .await?
+        .ok_or_else(|| AppError::NotFound("section".into()))?;
+    sqlx::query!(
+        r#"update section set position = position - 1 where course_id = $1 and position > $2"#,
+        course_id,
+        position,
+    )
+        .execute(&mut *tx)
+        .await?;
+    tx.commit().await?;
+    Ok(())
+}
+
+#[instrument(skip_all, fields(teacher_id = teacher_id, course_id = course_id))]
+pub async fn reorder_sections_db(
+    conn: &mut PgConnection,
+    teacher_id: i32,
+    course_id: i32,
+    order: Reorder,
+) -> Result<Vec<Section>, AppError> {
+    let mut tx = conn.begin().await?;
+    lock_course(&mut tx, teacher_id, course_id).await?;
+
+    let mut current = sqlx::query_scalar!(
+        r#"select id from section where course_id = $1"#,
+        course_id,
+    )
+        .fetch_all(&mut *tx)
+        .await?;
+    if !order.matches(&mut current) {
+        return Err(Reorder::mismatch("section"));
+    }
+    // 新的序号为 id 在数组中的位置
+    sqlx::query!(
+        r#"update section set position = o.position::int
+        from unnest($1::int[]) with ordinality as o(id, position)
+        where section.id = o.id"#,
+        &order.ids,
+    )
+        .execute(&mut *tx)
+        .await?;
+    let sections = load_sections(&mut tx, course_id).await?;
+    tx.commit().await?;
+    Ok(sections)
+}
+
+#[instrument(skip_all, fields(teacher_id = teacher_id, course_id = course_id, section_id = section_id))]
+pub async fn post_new_lesson_db(
+    conn: &mut PgConnection,
+    teacher_id: i32,
+    course_id: i32,
+    section_id: i32,
+    new_lesson: CreateLesson,
+) -> Result<Lesson, AppError> {
+    let mut tx = conn.begin().await?;
+    lock_course(&mut tx, teacher_id, course_id).await?;
+    ensure_section(&mut tx, course_id, section_id).await?;
+
+    let count = sqlx::query_scalar!(
+        r#"select count(*) as "count!" from lesson where section_id = $1"#,
+        section_id,
+    )
+        .fetch_one(&mut *tx)
+        .await?;
+    let position = insert_position(new_lesson.position, count as i32);
+    sqlx::query!(
+        r#"update lesson set position = position + 1 where section_id = $1 and position >= $2"#,
+        section_id,
+        position,
+    )
+        .execute(&mut *tx)
+        .await?;
+    let lesson = sqlx::query_as!(
+        Lesson,
+        r#"insert into lesson (section_id, title, content, duration_minutes, position)
+        values ($1, $2, $3, $4, $5)
+        returning section_id, id, title, content, duration_minutes, position"#,
+        section_id,
+        new_lesson.title,
+        new_lesson.content,
+        new_lesson.duration_minutes,
+        position,
+    )
+        .fetch_one(&mut *tx)
+        .await?;
+    tx.commit().await?;
+    Ok(lesson)
+}
+
+#[instrument(skip_all, fields(teacher_id = teacher_id, course_id = course_id, section_id = section_id, lesson_id = lesson_id))]
+pub async fn update_lesson_db(
+    conn: &mut PgConnection,
+    teacher_id: i32,
+    course_id: i32,
+    section_id: i32,
+    lesson_id: i32,
+    update: UpdateLesson,
+) -> Result<Lesson, AppError> {
+    let mut tx = conn.begin().await?;
+    lock_course(&mut tx, teacher_id, course_id).await?;
+    ensure_section(&mut tx, course_id, section_id).await?;
+
+    // 查出原始的记录, 未传入的字段保持不变
+    let mut lesson = sqlx::query_as!(
+        Lesson,
+        r#"select section_id, id, title, content, duration_minutes, position
+        from lesson where id = $1 and section_id = $2"#,
+        lesson_id,
+        section_id,
+    )
+        .fetch_optional(&mut *tx)
+        .await?
+        .ok_or_else(|| AppError::NotFound("lesson".into()))?;
+    update.apply(&mut lesson);
+
+    let lesson = sqlx::query_as!(
+        Lesson,
+        r#"update lesson set title = $1, content = $2, duration_minutes = $3 where id = $4
+        returning section_id, id, title, content, duration_minutes, position"#,
+        lesson.title,
+        lesson.content,
+        lesson.duration_minutes,
+        lesson_id,
+    )
+        .fetch_optional(&mut *tx)
+        .await?
+        .ok_or_else(|| AppError::NotFound("lesson".into()))?;
+    tx.commit().await?;
+    Ok(lesson)
+}
+
+#[instrument(skip_all, fields(teacher_id = teacher_id, course_id = course_id, section_id = section_id, lesson_id = lesson_id))]
+pub async fn delete_lesson_db(
+    conn: &mut PgConnection,
+    teacher_id: i32,
+    course_id: i32,
+    section_id: i32,
+    lesson_id: i32,
+) -> Result<(), AppError> {
+    let mut tx = conn.begin().await?;
+    lock_course(&mut tx, teacher_id, course_id).await?;
+    ensure_section(&mut tx, course_id, section_id).await?;
+
+    let position = sqlx::query_scalar!(
+        r#"delete from lesson where id = $1 and section_id = $2 returning position"#,
+        lesson_id,
+        section_id,
+    )
+        .fetch_optional(&mut *tx)
+        .await?
+        .ok_or_else(|| AppError::NotFound("lesson".into()))?;
+    sqlx::query!(
+        r#"update lesson set position = position - 1 where section_id = $1 and position > $2"#,
+        section_id,
+        position,
+    )
+        .execute(&mut *tx)
+        .await?;
+    tx.commit().await?;
+    Ok(())
+}
+
+#[instrument(skip_all, fields(teacher_id = teacher_id, course_id = course_id, section_id = section_id))]
+pub async fn reorder_lessons_db(
+    conn: &mut PgConnection,
+    teacher_id: i32,
+    course_id: i32,
+    section_id: i32,
+    order: Reorder,
+) -> Result<Vec<Lesson>, AppError> {
+    let mut tx = conn.begin().await?;
+    lock_course(&mut tx, teacher_id, course_id).await?;
+    ensure_section(&mut tx, course_id, section_id).await?;
+
+    let mut current = sqlx::query_scalar!(
+        r#"select id from lesson where section_id = $1"#,
+        section_id,
+    )
+        .fetch_all(&mut *tx)
+        .await?;
+    if !order.matches(&mut current) {
+        return Err(Reorder::mismatch("lesson"));
+    }
+    sqlx::query!(
+        r#"update lesson set position = o.position::int
+        from unnest($1::int[]) with ordinality as o(id, position)
+        where lesson.id = o.id"#,
+        &order.ids,
+    )
+        .execute(&mut *tx)
+        .await?;
+    let lessons = load_lessons(&mut tx, section_id).await?;
+    tx.commit().await?;
+    Ok(lessons)
+}
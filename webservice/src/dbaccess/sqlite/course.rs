@@ -0,0 +1,444 @@
+use chrono::Duration;
+use sqlx::sqlite::{Sqlite, SqliteConnection};
+use sqlx::Connection;
+use sqlx::QueryBuilder;
+use tracing::instrument;
+
+use crate::dbaccess::COURSE_TEACHER_FKEY;
+use crate::errors::AppError;
+use crate::etag::{check_version, IfMatch};
+use crate::models::course::{
+    Course, CoursePage, CourseQuery, CourseSearchHit, CourseSearchPage, CourseSearchQuery,
+    CourseSort, CreateCourse, PatchCourse, SortKey, SortOrder,
+};
+
+use super::foreign_key_error;
+
+// 权重与 Postgres 中 setweight 的 A、B、C 对应, 列的顺序见 migrations_sqlite 中的 course_fts
+const BM25_WEIGHTS: &str = "1.0, 0.4, 0.2";
+
+#[instrument(skip_all, fields(teacher_id = teacher_id))]
+pub async fn get_course_for_teacher_db(
+    conn: &mut SqliteConnection,
+    teacher_id: i32,
+    query: &CourseQuery,
+) -> Result<CoursePage, AppError> {
+    query.validate()?;
+    let limit = query.limit();
+    let offset = query.offset();
+
+    let mut count_builder = QueryBuilder::new("select count(*) from course");
+    push_course_filters(&mut count_builder, teacher_id, query);
+    let total: i64 = count_builder
+        .build_query_scalar()
+        .fetch_one(&mut *conn)
+        .await?;
+
+    // 时间以文本保存, 格式与 sqlx 绑定 NaiveDateTime 时一致, 可以直接比较
+    let sort_expr = match query.sort {
+        CourseSort::Time => "coalesce(time, '1970-01-01 00:00:00')",
+        CourseSort::Name => "name",
+        CourseSort::Price => "coalesce(price, 0)",
+    };
+    let (direction, compare) = match query.order {
+        SortOrder::Asc => ("asc", " > "),
+        SortOrder::Desc => ("desc", " < "),
+    };
+
+    let mut builder = QueryBuilder::new("select * from course");
+    push_course_filters(&mut builder, teacher_id, query);
+    if let Some(cursor) = &query.cursor {
+        // 游标分页: 从上一页最后一条记录的 (排序值, id) 之后开始
+        let (key, id) = query.sort.parse_cursor(query.order, cursor)?;
+        builder.push(format!(" and ({}, id){}(", sort_expr, compare));
+        match key {
+            SortKey::Time(time) => builder.push_bind(time),
+            SortKey::Name(name) => builder.push_bind(name),
+            SortKey::Price(price) => builder.push_bind(price),
+        };
+        builder.push(", ").push_bind(id).push(")");
+    }
+    builder.push(format!(" order by {} {}, id {}", sort_expr, direction, direction));
+    // 多查一条用于判断是否还有下一页
+    builder.push(" limit ").push_bind(limit + 1);
+    if query.cursor.is_none() {
+        builder.push(" offset ").push_bind(offset);
+    }
+
+    let mut items: Vec<Course> = builder
+        .build_query_as()
+        .fetch_all(&mut *conn)
+        .await?;
+
+    let next_cursor = if items.len() as i64 > limit {
+        items.truncate(limit as usize);
+        items.last().map(|c| query.sort.cursor(query.order, c))
+    } else {
+        None
+    };
+
+    Ok(CoursePage {
+        items,
+        total,
+        limit,
+        offset: if query.cursor.is_some() { 0 } else { offset },
+        next_cursor,
+    })
+}
+
+fn push_course_filters(
+    builder: &mut QueryBuilder<'_, Sqlite>,
+    teacher_id: i32,
+    query: &CourseQuery,
+) {
+    builder.push(" where teacher_id = ").push_bind(teacher_id);
+    builder.push(" and deleted_at is null");
+    if let Some(level) = &query.level {
+        builder.push(" and level = ").push_bind(level.clone());
+    }
+    if let Some(language) = &query.language {
+        builder.push(" and language = ").push_bind(language.clone());
+    }
+    if let Some(format) = &query.format {
+        builder.push(" and format = ").push_bind(format.clone());
+    }
+    if let Some(currency) = &query.currency {
+        builder.push(" and currency = ").push_bind(currency.clone());
+    }
+    if let Some(min_price) = query.min_price {
+        builder.push(" and price >= ").push_bind(min_price);
+    }
+    if let Some(max_price) = query.max_price {
+        builder.push(" and price <= ").push_bind(max_price);
+    }
+}
+
+// 把用户输入转换为 FTS5 的查询: 每个词加上引号, 所有词都需要匹配
+// 不支持 websearch_to_tsquery 的 or、-排除 等语法, 但用户输入的特殊字符不会导致语法错误
+fn fts_query(q: &str) -> String {
+    q.split_whitespace()
+        .map(|word| format!("\"{}\"", word.replace('"', "\"\"")))
+        .collect::<Vec<_>>()
+        .join(" ")
+}
+
+#[instrument(skip_all, fields(q = %query.q))]
+pub async fn search_courses_db(
+    conn: &mut SqliteConnection,
+    query: &CourseSearchQuery,
+) -> Result<CourseSearchPage, AppError> {
+    query.validate()?;
+    let limit = query.limit();
+    let offset = query.offset();
+
+    let mut count_builder = QueryBuilder::new("select count(*)");
+    push_search_filters(&mut count_builder, query);
+    let total: i64 = count_builder
+        .build_query_scalar()
+        .fetch_one(&mut *conn)
+        .await?;
+
+    // bm25 越小越相关, 取反后与 Postgres 的 ts_rank 一样越大越相关
+    // 片段优先从描述中截取, 没有描述时使用课程结构
+    let mut builder = QueryBuilder::new(format!(
+        "select course.*, -bm25(course_fts, {}) as rank, \
+         highlight(course_fts, 0, '<mark>', '</mark>') as name_highlight, \
+         case when course.description is not null \
+             then snippet(course_fts, 1, '<mark>', '</mark>', '...', 35) \
+             else coalesce(snippet(course_fts, 2, '<mark>', '</mark>', '...', 35), '') \
+         end as snippet",
+        BM25_WEIGHTS
+    ));
+    push_search_filters(&mut builder, query);
+    builder.push(" order by rank desc, course.id asc limit ").push_bind(limit);
+    builder.push(" offset ").push_bind(offset);
+
+    let items: Vec<CourseSearchHit> = builder
+        .build_query_as()
+        .fetch_all(&mut *conn)
+        .await?;
+
+    Ok(CourseSearchPage {
+        items,
+        total,
+        limit,
+        offset,
+    })
+}
+
+fn push_search_filters(builder: &mut QueryBuilder<'_, Sqlite>, query: &CourseSearchQuery) {
+    builder
+        .push(" from course_fts join course on course.id = course_fts.rowid")
+        .push(" where course_fts match ")
+        .push_bind(fts_query(&query.q))
+        .push(" and course.deleted_at is null");
+    if let Some(level) = &query.level {
+        builder.push(" and course.level = ").push_bind(level.clone());
+    }
+    if let Some(language) = &query.language {
+        builder.push(" and course.language = ").push_bind(language.clone());
+    }
+}
+
+#[instrument(skip_all, fields(teacher_id = teacher_id, course_id = course_id))]
+pub async fn get_course_detail_db(
+    conn: &mut SqliteConnection,
+    teacher_id: i32,
+    course_id: i32,
+) -> Result<Course, AppError> {
+    sqlx::query_as::<_, Course>("select * from course where teacher_id = ? and id = ? and deleted_at is null")
+        .bind(teacher_id)
+        .bind(course_id)
+        .fetch_optional(&mut *conn)
+        .await?
+        .ok_or_else(|| AppError::NotFound("course".into()))
+}
+
+#[instrument(skip_all, fields(teacher_id = new_course.teacher_id))]
+pub async fn post_new_course_db(
+    conn: &mut SqliteConnection,
+    new_course: CreateCourse,
+) -> Result<Course, AppError> {
+    // 外键只能保证老师存在, 已删除的老师也不能再创建课程
+    let teacher_active: bool = sqlx::query_scalar(
+        "select exists(select 1 from teacher where id = ? and deleted_at is null)",
+    )
+        .bind(new_course.teacher_id)
+        .fetch_one(&mut *conn)
+        .await?;
+    if !teacher_active {
+        return Err(AppError::missing_reference(COURSE_TEACHER_FKEY));
+    }
+
+    sqlx::query_as::<_, Course>(
+        r#"insert into course (teacher_id, name, description, format, structure, duration, price, currency, language, level, capacity)
+        values (?, ?, ?, ?, ?, ?, ?, ?, ?, ?, ?)
+        returning id, teacher_id, name, time, description, format, structure, duration, price, currency, language, level, capacity, deleted_at, version
+        "#,
+    )
+        .bind(new_course.teacher_id)
+        .bind(new_course.name)
+        .bind(new_course.description)
+        .bind(new_course.format)
+        .bind(new_course.structure)
+        .bind(new_course.duration)
+        .bind(new_course.price.as_ref().map(|p| p.amount))
+        .bind(new_course.price.map(|p| p.currency))
+        .bind(new_course.language)
+        .bind(new_course.level)
+        .bind(new_course.capacity)
+        .fetch_all(&mut *conn)
+        .await
+        .map_err(|e| foreign_key_error(e, AppError::missing_reference))?
+        .pop()
+        .ok_or_else(|| sqlx::Error::RowNotFound.into())
+}
+
+// 所有课程在同一个事务中插入, 先写入再检查老师, 以便在检查前获得写锁
+#[instrument(skip_all, fields(teacher_id = teacher_id, rows = courses.len()))]
+pub async fn import_courses_db(
+    conn: &mut SqliteConnection,
+    teacher_id: i32,
+    courses: Vec<CreateCourse>,
+) -> Result<u64, AppError> {
+    if courses.is_empty() {
+        return Ok(0);
+    }
+
+    let mut tx = conn.begin().await?;
+    // 所有课程在一条语句中插入, 参数个数与 Postgres 相同, 也小于 SQLite 的上限 32766
+    let mut builder = QueryBuilder::new(
+        "insert into course (teacher_id, name, description, format, structure, duration, price, currency, language, level, capacity) ",
+    );
+    builder.push_values(courses, |mut row, course| {
+        row.push_bind(teacher_id)
+            .push_bind(course.name)
+            .push_bind(course.description)
+            .push_bind(course.format)
+            .push_bind(course.structure)
+            .push_bind(course.duration)
+            .push_bind(course.price.as_ref().map(|p| p.amount))
+            .push_bind(course.price.map(|p| p.currency))
+            .push_bind(course.language)
+            .push_bind(course.level)
+            .push_bind(course.capacity);
+    });
+    let imported = builder
+        .build()
+        .execute(&mut *tx)
+        .await
+        .map_err(|e| foreign_key_error(e, AppError::missing_reference))?
+        .rows_affected();
+
+    // 外键只能保证老师存在, 已删除的老师也不能再导入课程
+    let teacher_active: bool = sqlx::query_scalar(
+        "select exists(select 1 from teacher where id = ? and deleted_at is null)",
+    )
+        .bind(teacher_id)
+        .fetch_one(&mut *tx)
+        .await?;
+    if !teacher_active {
+        return Err(AppError::missing_reference(COURSE_TEACHER_FKEY));
+    }
+    tx.commit().await?;
+    Ok(imported)
+}
+
+#[instrument(skip_all, fields(teacher_id = teacher_id, course_id = id))]
+pub async fn delete_course_db(
+    conn: &mut SqliteConnection,
+    teacher_id: i32,
+    id: i32,
+    if_match: &IfMatch,
+) -> Result<(), AppError> {
+    let current = get_course_detail_db(&mut *conn, teacher_id, id).await?;
+    check_version(if_match, current.version)?;
+
+    // 只移入回收站, 由 purge_deleted_courses_db 彻底删除
+    // 带上读取到的版本号, 期间被其他请求修改或删除时不会更新任何记录
+    let course_row = sqlx::query(
+        "update course set deleted_at = strftime('%Y-%m-%d %H:%M:%f', 'now'), version = version + 1 \
+         where teacher_id = ? and id = ? and deleted_at is null and version = ?",
+    )
+        .bind(teacher_id)
+        .bind(id)
+        .bind(current.version)
+        .execute(&mut *conn)
+        .await?;
+    match course_row.rows_affected() {
+        0 => Err(concurrent_modification()),
+        _ => Ok(()),
+    }
+}
+
+// 读取之后、写入之前记录被其他请求修改
+fn concurrent_modification() -> AppError {
+    AppError::PreconditionFailed("Record was modified concurrently".into())
+}
+
+#[instrument(skip_all, fields(teacher_id = teacher_id, course_id = id))]
+pub async fn update_course_db(
+    conn: &mut SqliteConnection,
+    teacher_id: i32,
+    id: i32,
+    if_match: &IfMatch,
+    patch: PatchCourse,
+) -> Result<Course, AppError> {
+    // 查出原始的记录, 未传入的字段保持不变, 与 Postgres 的实现一致
+    // SQLite 没有 select for update, 写入时检查版本号没有变化
+    let mut course = get_course_detail_db(&mut *conn, teacher_id, id).await?;
+    check_version(if_match, course.version)?;
+    let version = course.version;
+    patch.apply(&mut course);
+
+    let course_row = sqlx::query_as::<_, Course>(
+        r#"update course set
+            name = ?,
+            description = ?,
+            format = ?,
+            structure = ?,
+            duration = ?,
+            price = ?,
+            currency = ?,
+            language = ?,
+            level = ?,
+            capacity = ?,
+            version = version + 1
+        where teacher_id = ? and id = ? and deleted_at is null and version = ?
+        returning id, teacher_id, name, time, description, format, structure, duration, price, currency, language, level, capacity, deleted_at, version
+        "#,
+    )
+        .bind(course.name)
+        .bind(course.description)
+        .bind(course.format)
+        .bind(course.structure)
+        .bind(course.duration)
+        .bind(course.price.as_ref().map(|p| p.amount))
+        .bind(course.price.map(|p| p.currency))
+        .bind(course.language)
+        .bind(course.level)
+        .bind(course.capacity)
+        .bind(teacher_id)
+        .bind(id)
+        .bind(version)
+        .fetch_all(&mut *conn)
+        .await?
+        .pop();
+    // 违反约束的错误直接返回
+    course_row.ok_or_else(concurrent_modification)
+}
+
+#[instrument(skip_all, fields(teacher_id = teacher_id))]
+pub async fn get_deleted_courses_db(
+    conn: &mut SqliteConnection,
+    teacher_id: i32,
+) -> Result<Vec<Course>, AppError> {
+    let rows = sqlx::query_as::<_, Course>(
+        "select * from course where teacher_id = ? and deleted_at is not null \
+         order by deleted_at desc, id desc",
+    )
+        .bind(teacher_id)
+        .fetch_all(&mut *conn)
+        .await?;
+    Ok(rows)
+}
+
+#[instrument(skip_all, fields(teacher_id = teacher_id, course_id = id))]
+pub async fn restore_course_db(
+    conn: &mut SqliteConnection,
+    teacher_id: i32,
+    id: i32,
+) -> Result<Course, AppError> {
+    let course_row = sqlx::query_as::<_, Course>(
+        r#"update course set deleted_at = null, version = version + 1
+        where teacher_id = ?1 and id = ?2 and deleted_at is not null
+            and exists(select 1 from teacher where id = ?1 and deleted_at is null)
+        returning id, teacher_id, name, time, description, format, structure, duration, price, currency, language, level, capacity, deleted_at, version
+        "#,
+    )
+        .bind(teacher_id)
+        .bind(id)
+        .fetch_all(&mut *conn)
+        .await?
+        .pop();
+    if let Some(course) = course_row {
+        return Ok(course);
+    }
+
+    // 区分课程不在回收站中和老师已被删除
+    let in_trash: bool = sqlx::query_scalar(
+        "select exists(select 1 from course where teacher_id = ? and id = ? and deleted_at is not null)",
+    )
+        .bind(teacher_id)
+        .bind(id)
+        .fetch_one(&mut *conn)
+        .await?;
+    if in_trash {
+        Err(AppError::missing_reference(COURSE_TEACHER_FKEY))
+    } else {
+        Err(AppError::NotFound("course".into()))
+    }
+}
+
+#[instrument(skip_all)]
+pub async fn purge_deleted_courses_db(
+    conn: &mut SqliteConnection,
+    retention: Duration,
+) -> Result<u64, AppError> {
+    let result = sqlx::query("delete from course where deleted_at < datetime('now', ?)")
+        .bind(format!("{} seconds", -retention.num_seconds()))
+        .execute(&mut *conn)
+        .await?;
+    Ok(result.rows_affected())
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    #[test]
+    fn fts_query_quotes_every_word() {
+        assert_eq!(fts_query(" rust  web "), r#""rust" "web""#);
+        assert_eq!(fts_query(r#"say "hi" OR -x"#), r#""say" """hi""" "OR" "-x""#);
+    }
+}
@@ -0,0 +1,23 @@
+// 与 course、teacher 模块对应的 SQLite 实现, 返回的数据与错误保持一致
+// query! 宏只能在编译时检查一种数据库, 这里使用运行时检查的 query_as
+// 带 returning 的写入语句要等全部执行完才会提交, fetch_one/fetch_optional 拿到第一行就归还连接,
+// 其他连接可能还读不到这次写入, 因此不在事务中时使用 fetch_all 再取出第一行
+use sqlx::error::ErrorKind;
+
+use crate::dbaccess::COURSE_TEACHER_FKEY;
+use crate::errors::AppError;
+
+pub mod course;
+pub mod section;
+pub mod student;
+pub mod teacher;
+
+// SQLite 的外键错误不包含约束名, 也区分不了是插入还是删除, 由调用方指定转换后的错误
+fn foreign_key_error(err: sqlx::Error, to_error: fn(&str) -> AppError) -> AppError {
+    match &err {
+        sqlx::Error::Database(db_err) if matches!(db_err.kind(), ErrorKind::ForeignKeyViolation) => {
+            to_error(COURSE_TEACHER_FKEY)
+        }
+        _ => err.into(),
+    }
+}
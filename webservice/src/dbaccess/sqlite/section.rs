@@ -0,0 +1,360 @@
+use sqlx::sqlite::SqliteConnection;
+use sqlx::Connection;
+use tracing::instrument;
+
+use crate::errors::AppError;
+use crate::models::section::{
+    insert_position, CreateLesson, CreateSection, Lesson, Reorder, Section, SectionRow,
+    UpdateLesson, UpdateSection,
+};
+
+async fn ensure_course(
+    conn: &mut SqliteConnection,
+    teacher_id: i32,
+    course_id: i32,
+) -> Result<(), AppError> {
+    let exists: bool = sqlx::query_scalar(
+        "select exists(select 1 from course where id = ? and teacher_id = ? and deleted_at is null)",
+    )
+        .bind(course_id)
+        .bind(teacher_id)
+        .fetch_one(conn)
+        .await?;
+    match exists {
+        true => Ok(()),
+        false => Err(AppError::NotFound("course".into())),
+    }
+}
+
+// SQLite 没有 select for update, 事务中的第一条语句为写入才能在读取之前获得写锁,
+// 这里更新课程的版本号为原值, 同一门课程章节与课时的修改依次执行, 同时检查课程存在
+async fn lock_course(
+    conn: &mut SqliteConnection,
+    teacher_id: i32,
+    course_id: i32,
+) -> Result<(), AppError> {
+    let locked = sqlx::query(
+        "update course set version = version where id = ? and teacher_id = ? and deleted_at is null",
+    )
+        .bind(course_id)
+        .bind(teacher_id)
+        .execute(conn)
+        .await?;
+    match locked.rows_affected() {
+        0 => Err(AppError::NotFound("course".into())),
+        _ => Ok(()),
+    }
+}
+
+async fn ensure_section(
+    conn: &mut SqliteConnection,
+    course_id: i32,
+    section_id: i32,
+) -> Result<(), AppError> {
+    let exists: bool = sqlx::query_scalar(
+        "select exists(select 1 from section where id = ? and course_id = ?)",
+    )
+        .bind(section_id)
+        .bind(course_id)
+        .fetch_one(conn)
+        .await?;
+    match exists {
+        true => Ok(()),
+        false => Err(AppError::NotFound("section".into())),
+    }
+}
+
+async fn load_sections(conn: &mut SqliteConnection, course_id: i32) -> Result<Vec<Section>, AppError> {
+    let sections = sqlx::query_as::<_, SectionRow>(
+        "select course_id, id, title, position from section where course_id = ? order by position",
+    )
+        .bind(course_id)
+        .fetch_all(&mut *conn)
+        .await?;
+    let lessons = sqlx::query_as::<_, Lesson>(
+        r#"select lesson.section_id, lesson.id, lesson.title, lesson.content, lesson.duration_minutes, lesson.position
+        from lesson join section on section.id = lesson.section_id
+        where section.course_id = ?
+        order by lesson.position"#,
+    )
+        .bind(course_id)
+        .fetch_all(&mut *conn)
+        .await?;
+    Ok(Section::assemble(sections, lessons))
+}
+
+async fn load_lessons(conn: &mut SqliteConnection, section_id: i32) -> Result<Vec<Lesson>, AppError> {
+    let lessons = sqlx::query_as::<_, Lesson>(
+        r#"select section_id, id, title, content, duration_minutes, position
+        from lesson where section_id = ? order by position"#,
+    )
+        .bind(section_id)
+        .fetch_all(conn)
+        .await?;
+    Ok(lessons)
+}
+
+#[instrument(skip_all, fields(teacher_id = teacher_id, course_id = course_id))]
+pub async fn get_sections_db(
+    conn: &mut SqliteConnection,
+    teacher_id: i32,
+    course_id: i32,
+) -> Result<Vec<Section>, AppError> {
+    ensure_course(&mut *conn, teacher_id, course_id).await?;
+    load_sections(&mut *conn, course_id).await
+}
+
+#[instrument(skip_all, fields(teacher_id = teacher_id, course_id = course_id))]
+pub async fn post_new_section_db(
+    conn: &mut SqliteConnection,
+    teacher_id: i32,
+    course_id: i32,
+    new_section: CreateSection,
+) -> Result<Section, AppError> {
+    let mut tx = conn.begin().await?;
+    lock_course(&mut tx, teacher_id, course_id).await?;
+
+    let count: i32 = sqlx::query_scalar("select count(*) from section where course_id = ?")
+        .bind(course_id)
+        .fetch_one(&mut *tx)
+        .await?;
+    let position = insert_position(new_section.position, count);
+    sqlx::query("update section set position = position + 1 where course_id = ? and position >= ?")
+        .bind(course_id)
+        .bind(position)
+        .execute(&mut *tx)
+        .await?;
+    let row = sqlx::query_as::<_, SectionRow>(
+        r#"insert into section (course_id, title, position) values (?, ?, ?)
+        returning course_id, id, title, position"#,
+    )
+        .bind(course_id)
+        .bind(new_section.title)
+        .bind(position)
+        .fetch_one(&mut *tx)
+        .await?;
+    tx.commit().await?;
+    Ok(row.into())
+}
+
+#[instrument(skip_all, fields(teacher_id = teacher_id, course_id = course_id, section_id = section_id))]
+pub async fn update_section_db(
+    conn: &mut SqliteConnection,
+    teacher_id: i32,
+    course_id: i32,
+    section_id: i32,
+    update: UpdateSection,
+) -> Result<Section, AppError> {
+    let mut tx = conn.begin().await?;
+    lock_course(&mut tx, teacher_id, course_id).await?;
+
+    let row = sqlx::query_as::<_, SectionRow>(
+        r#"update section set title = ? where id = ? and course_id = ?
+        returning course_id, id, title, position"#,
+    )
+        .bind(update.title)
+        .bind(section_id)
+        .bind(course_id)
+        .fetch_optional(&mut *tx)
+        .await?
+        .ok_or_else(|| AppError::NotFound("section".into()))?;
+    let mut section = Section::from(row);
+    section.lessons = load_lessons(&mut tx, section_id).await?;
+    tx.commit().await?;
+    Ok(section)
+}
+
+#[instrument(skip_all, fields(teacher_id = teacher_id, course_id = course_id, section_id = section_id))]
+pub async fn delete_section_db(
+    conn: &mut SqliteConnection,
+    teacher_id: i32,
+    course_id: i32,
+    section_id: i32,
+) -> Result<(), AppError> {
+    let mut tx = conn.begin().await?;
+    lock_course(&mut tx, teacher_id, course_id).await?;
+
+    // 课时由外键的 on delete cascade 一起删除, 之后的章节依次前移
+    let position: i32 = sqlx::query_scalar(
+        "delete from section where id = ? and course_id = ? returning position",
+    )
+        .bind(section_id)
+        .bind(course_id)
+        .fetch_optional(&mut *tx)
+        .await?
+        .ok_or_else(|| AppError::NotFound("section".into()))?;
+    sqlx::query("update section set position = position - 1 where course_id = ? and position > ?")
+        .bind(course_id)
+        .bind(position)
+        .execute(&mut *tx)
+        .await?;
+    tx.commit().await?;
+    Ok(())
+}
+
+#[instrument(skip_all, fields(teacher_id = teacher_id, course_id = course_id))]
+pub async fn reorder_sections_db(
+    conn: &mut SqliteConnection,
+    teacher_id: i32,
+    course_id: i32,
+    order: Reorder,
+) -> Result<Vec<Section>, AppError> {
+    let mut tx = conn.begin().await?;
+    lock_course(&mut tx, teacher_id, course_id).await?;
+
+    let mut current: Vec<i32> = sqlx::query_scalar("select id from section where course_id = ?")
+        .bind(course_id)
+        .fetch_all(&mut *tx)
+        .await?;
+    if !order.matches(&mut current) {
+        return Err(Reorder::mismatch("section"));
+    }
+    for (index, id) in order.ids.iter().enumerate() {
+        sqlx::query("update section set position = ? where id = ?")
+            .bind(index as i32 + 1)
+            .bind(id)
+            .execute(&mut *tx)
+            .await?;
+    }
+    let sections = load_sections(&mut tx, course_id).await?;
+    tx.commit().await?;
+    Ok(sections)
+}
+
+#[instrument(skip_all, fields(teacher_id = teacher_id, course_id = course_id, section_id = section_id))]
+pub async fn post_new_lesson_db(
+    conn: &mut SqliteConnection,
+    teacher_id: i32,
+    course_id: i32,
+    section_id: i32,
+    new_lesson: CreateLesson,
+) -> Result<Lesson, AppError> {
+    let mut tx = conn.begin().await?;
+    lock_course(&mut tx, teacher_id, course_id).await?;
+    ensure_section(&mut tx, course_id, section_id).await?;
+
+    let count: i32 = sqlx::query_scalar("select count(*) from lesson where section_id = ?")
+        .bind(section_id)
+        .fetch_one(&mut *tx)
+        .await?;
+    let position = insert_position(new_lesson.position, count);
+    sqlx::query("update lesson set position = position + 1 where section_id = ? and position >= ?")
+        .bind(section_id)
+        .bind(position)
+        .execute(&mut *tx)
+        .await?;
+    let lesson = sqlx::query_as::<_, Lesson>(
+        r#"insert into lesson (section_id, title, content, duration_minutes, position)
+        values (?, ?, ?, ?, ?)
+        returning section_id, id, title, content, duration_minutes, position"#,
+    )
+        .bind(section_id)
+        .bind(new_lesson.title)
+        .bind(new_lesson.content)
+        .bind(new_lesson.duration_minutes)
+        .bind(position)
+        .fetch_one(&mut *tx)
+        .await?;
+    tx.commit().await?;
+    Ok(lesson)
+}
+
+#[instrument(skip_all, fields(teacher_id = teacher_id, course_id = course_id, section_id = section_id, lesson_id = lesson_id))]
+pub async fn update_lesson_db(
+    conn: &mut SqliteConnection,
+    teacher_id: i32,
+    course_id: i32,
+    section_id: i32,
+    lesson_id: i32,
+    update: UpdateLesson,
+) -> Result<Lesson, AppError> {
+    let mut tx = conn.begin().await?;
+    lock_course(&mut tx, teacher_id, course_id).await?;
+    ensure_section(&mut tx, course_id, section_id).await?;
+
+    // 查出原始的记录, 未传入的字段保持不变
+    let mut lesson = sqlx::query_as::<_, Lesson>(
+        r#"select section_id, id, title, content, duration_minutes, position
+        from lesson where id = ? and section_id = ?"#,
+    )
+        .bind(lesson_id)
+        .bind(section_id)
+        .fetch_optional(&mut *tx)
+        .await?
+        .ok_or_else(|| AppError::NotFound("lesson".into()))?;
+    update.apply(&mut lesson);
+
+    let lesson = sqlx::query_as::<_, Lesson>(
+        r#"update lesson set title = ?, content = ?, duration_minutes = ? where id = ?
+        returning section_id, id, title, content, duration_minutes, position"#,
+    )
+        .bind(lesson.title)
+        .bind(lesson.content)
+        .bind(lesson.duration_minutes)
+        .bind(lesson_id)
+        .fetch_optional(&mut *tx)
+        .await?
+        .ok_or_else(|| AppError::NotFound("lesson".into()))?;
+    tx.commit().await?;
+    Ok(lesson)
+}
+
+#[instrument(skip_all, fields(teacher_id = teacher_id, course_id = course_id, section_id = section_id, lesson_id = lesson_id))]
+pub async fn delete_lesson_db(
+    conn: &mut SqliteConnection,
+    teacher_id: i32,
+    course_id: i32,
+    section_id: i32,
+    lesson_id: i32,
+) -> Result<(), AppError> {
+    let mut tx = conn.begin().await?;
+    lock_course(&mut tx, teacher_id, course_id).await?;
+    ensure_section(&mut tx, course_id, section_id).await?;
+
+    let position: i32 = sqlx::query_scalar(
+        "delete from lesson where id = ? and section_id = ? returning position",
+    )
+        .bind(lesson_id)
+        .bind(section_id)
+        .fetch_optional(&mut *tx)
+        .await?
+        .ok_or_else(|| AppError::NotFound("lesson".into()))?;
+    sqlx::query("update lesson set position = position - 1 where section_id = ? and position > ?")
+        .bind(section_id)
+        .bind(position)
+        .execute(&mut *tx)
+        .await?;
+    tx.commit().await?;
+    Ok(())
+}
+
+#[instrument(skip_all, fields(teacher_id = teacher_id, course_id = course_id, section_id = section_id))]
+pub async fn reorder_lessons_db(
+    conn: &mut SqliteConnection,
+    teacher_id: i32,
+    course_id: i32,
+    section_id: i32,
+    order: Reorder,
+) -> Result<Vec<Lesson>, AppError> {
+    let mut tx = conn.begin().await?;
+    lock_course(&mut tx, teacher_id, course_id).await?;
+    ensure_section(&mut tx, course_id, section_id).await?;
+
+    let mut current: Vec<i32> = sqlx::query_scalar("select id from lesson where section_id = ?")
+        .bind(section_id)
+        .fetch_all(&mut *tx)
+        .await?;
+    if !order.matches(&mut current) {
+        return Err(Reorder::mismatch("lesson"));
+    }
+    for (index, id) in order.ids.iter().enumerate() {
+        sqlx::query("update lesson set position = ? where id = ?")
+            .bind(index as i32 + 1)
+            .bind(id)
+            .execute(&mut *tx)
+            .await?;
+    }
+    let lessons = load_lessons(&mut tx, section_id).await?;
+    tx.commit().await?;
+    Ok(lessons)
+}
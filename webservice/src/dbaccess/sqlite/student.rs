@@ -0,0 +1,200 @@
+use sqlx::sqlite::SqliteConnection;
+use sqlx::Connection;
+use tracing::instrument;
+
+use crate::errors::AppError;
+use crate::models::student::{
+    already_enrolled, course_full, CourseEnrollments, EnrolledCourse, EnrolledStudent, Enrollment,
+    RegisterStudent, Student, StudentCredential,
+};
+
+// 课程必须存在、未删除且属于该老师, 返回课程的名额
+async fn course_capacity(
+    conn: &mut SqliteConnection,
+    teacher_id: i32,
+    course_id: i32,
+) -> Result<Option<i32>, AppError> {
+    sqlx::query_scalar::<_, Option<i32>>(
+        "select capacity from course where id = ? and teacher_id = ? and deleted_at is null",
+    )
+        .bind(course_id)
+        .bind(teacher_id)
+        .fetch_optional(conn)
+        .await?
+        .ok_or_else(|| AppError::NotFound("course".into()))
+}
+
+// 与 section 模块相同, 事务中先更新课程的版本号为原值以获得写锁, 同一门课程的选课依次执行
+async fn lock_course(
+    conn: &mut SqliteConnection,
+    teacher_id: i32,
+    course_id: i32,
+) -> Result<Option<i32>, AppError> {
+    sqlx::query_scalar::<_, Option<i32>>(
+        "update course set version = version where id = ? and teacher_id = ? and deleted_at is null returning capacity",
+    )
+        .bind(course_id)
+        .bind(teacher_id)
+        .fetch_optional(conn)
+        .await?
+        .ok_or_else(|| AppError::NotFound("course".into()))
+}
+
+async fn ensure_student(conn: &mut SqliteConnection, student_id: i32) -> Result<(), AppError> {
+    let exists: bool = sqlx::query_scalar("select exists(select 1 from student where id = ?)")
+        .bind(student_id)
+        .fetch_one(conn)
+        .await?;
+    match exists {
+        true => Ok(()),
+        false => Err(AppError::NotFound("student".into())),
+    }
+}
+
+#[instrument(skip_all, fields(student_id = student_id))]
+pub async fn get_student_db(conn: &mut SqliteConnection, student_id: i32) -> Result<Student, AppError> {
+    sqlx::query_as::<_, Student>("select id, name, email, time from student where id = ?")
+        .bind(student_id)
+        .fetch_optional(&mut *conn)
+        .await?
+        .ok_or_else(|| AppError::NotFound("student".into()))
+}
+
+#[instrument(skip_all)]
+pub async fn get_student_credential_by_email_db(
+    conn: &mut SqliteConnection,
+    email: &str,
+) -> Result<Option<StudentCredential>, AppError> {
+    let credential: Option<(i32, String)> = sqlx::query_as(
+        "select id, password_hash from student where lower(email) = lower(?)",
+    )
+        .bind(email)
+        .fetch_optional(&mut *conn)
+        .await?;
+    Ok(credential.map(|(id, password_hash)| StudentCredential { id, password_hash }))
+}
+
+#[instrument(skip_all)]
+pub async fn post_new_student_db(
+    conn: &mut SqliteConnection,
+    new_student: RegisterStudent,
+    password_hash: String,
+) -> Result<Student, AppError> {
+    sqlx::query_as::<_, Student>(
+        r#"insert into student (name, email, password_hash) values (?, ?, ?)
+        returning id, name, email, time"#,
+    )
+        .bind(new_student.name)
+        .bind(new_student.email)
+        .bind(password_hash)
+        .fetch_all(&mut *conn)
+        .await?
+        .pop()
+        .ok_or_else(|| sqlx::Error::RowNotFound.into())
+}
+
+#[instrument(skip_all, fields(teacher_id = teacher_id, course_id = course_id, student_id = student_id))]
+pub async fn enroll_db(
+    conn: &mut SqliteConnection,
+    teacher_id: i32,
+    course_id: i32,
+    student_id: i32,
+) -> Result<Enrollment, AppError> {
+    let mut tx = conn.begin().await?;
+    let capacity = lock_course(&mut tx, teacher_id, course_id).await?;
+    ensure_student(&mut tx, student_id).await?;
+
+    let enrolled: bool = sqlx::query_scalar(
+        "select exists(select 1 from enrollment where course_id = ? and student_id = ?)",
+    )
+        .bind(course_id)
+        .bind(student_id)
+        .fetch_one(&mut *tx)
+        .await?;
+    if enrolled {
+        return Err(already_enrolled());
+    }
+    if let Some(capacity) = capacity {
+        let count: i64 = sqlx::query_scalar("select count(*) from enrollment where course_id = ?")
+            .bind(course_id)
+            .fetch_one(&mut *tx)
+            .await?;
+        if count >= capacity as i64 {
+            return Err(course_full());
+        }
+    }
+
+    let enrollment = sqlx::query_as::<_, Enrollment>(
+        r#"insert into enrollment (course_id, student_id) values (?, ?)
+        returning course_id, student_id, enrolled_at"#,
+    )
+        .bind(course_id)
+        .bind(student_id)
+        .fetch_one(&mut *tx)
+        .await?;
+    tx.commit().await?;
+    Ok(enrollment)
+}
+
+#[instrument(skip_all, fields(teacher_id = teacher_id, course_id = course_id, student_id = student_id))]
+pub async fn unenroll_db(
+    conn: &mut SqliteConnection,
+    teacher_id: i32,
+    course_id: i32,
+    student_id: i32,
+) -> Result<(), AppError> {
+    let mut tx = conn.begin().await?;
+    lock_course(&mut tx, teacher_id, course_id).await?;
+    let deleted = sqlx::query("delete from enrollment where course_id = ? and student_id = ?")
+        .bind(course_id)
+        .bind(student_id)
+        .execute(&mut *tx)
+        .await?;
+    if deleted.rows_affected() == 0 {
+        return Err(AppError::NotFound("enrollment".into()));
+    }
+    tx.commit().await?;
+    Ok(())
+}
+
+#[instrument(skip_all, fields(teacher_id = teacher_id, course_id = course_id))]
+pub async fn get_course_enrollments_db(
+    conn: &mut SqliteConnection,
+    teacher_id: i32,
+    course_id: i32,
+) -> Result<CourseEnrollments, AppError> {
+    let capacity = course_capacity(&mut *conn, teacher_id, course_id).await?;
+    let students = sqlx::query_as::<_, EnrolledStudent>(
+        r#"select student.id as student_id, student.name, student.email, enrollment.enrolled_at
+        from enrollment join student on student.id = enrollment.student_id
+        where enrollment.course_id = ?
+        order by enrollment.enrolled_at, student.id"#,
+    )
+        .bind(course_id)
+        .fetch_all(&mut *conn)
+        .await?;
+    Ok(CourseEnrollments {
+        course_id,
+        capacity,
+        total: students.len() as i64,
+        students,
+    })
+}
+
+#[instrument(skip_all, fields(student_id = student_id))]
+pub async fn get_student_courses_db(
+    conn: &mut SqliteConnection,
+    student_id: i32,
+) -> Result<Vec<EnrolledCourse>, AppError> {
+    ensure_student(&mut *conn, student_id).await?;
+    let courses = sqlx::query_as::<_, EnrolledCourse>(
+        r#"select course.*, enrollment.enrolled_at
+        from enrollment join course on course.id = enrollment.course_id
+        where enrollment.student_id = ? and course.deleted_at is null
+        order by enrollment.enrolled_at desc, course.id desc"#,
+    )
+        .bind(student_id)
+        .fetch_all(&mut *conn)
+        .await?;
+    Ok(courses)
+}
@@ -0,0 +1,305 @@
+use chrono::{Duration, NaiveDateTime};
+use sqlx::sqlite::SqliteConnection;
+use sqlx::Connection;
+use tracing::instrument;
+
+use crate::{
+    errors::AppError,
+    etag::{check_version, IfMatch},
+    models::{
+        auth::{Role, TeacherCredential},
+        teacher::{CreateTeacher, DeletedTeacher, PatchTeacher, Teacher},
+    },
+};
+
+use super::foreign_key_error;
+
+// 老师表中的资料字段可以为空, 与 Postgres 的实现一样作为空字符串返回
+type TeacherRow = (i32, Option<String>, Option<String>, Option<String>, Option<NaiveDateTime>, i32);
+type CredentialRow = (i32, Option<String>, String);
+
+fn teacher_from_row((id, name, picture_url, profile, deleted_at, version): TeacherRow) -> Teacher {
+    Teacher {
+        id,
+        name: name.unwrap_or_default(),
+        picture_url: picture_url.unwrap_or_default(),
+        profile: profile.unwrap_or_default(),
+        version,
+        deleted_at,
+    }
+}
+
+fn credential_from_row((id, password_hash, role): CredentialRow) -> TeacherCredential {
+    TeacherCredential {
+        id,
+        password_hash,
+        role: Role::from_db(&role),
+    }
+}
+
+#[instrument(skip_all)]
+pub async fn get_all_teacher_db(conn: &mut SqliteConnection) -> Result<Vec<Teacher>, AppError> {
+    let teachers: Vec<Teacher> = sqlx::query_as::<_, TeacherRow>(
+        "select id, name, picture_url, profile, deleted_at, version from teacher where deleted_at is null",
+    )
+        .fetch_all(&mut *conn)
+        .await?
+        .into_iter()
+        .map(teacher_from_row)
+        .collect();
+
+    match teachers.len() {
+        0 => Err(AppError::NotFound("teacher".into())),
+        _ => Ok(teachers),
+    }
+}
+
+#[instrument(skip_all, fields(teacher_id = teacher_id))]
+pub async fn get_teacher_detail_db(
+    conn: &mut SqliteConnection,
+    teacher_id: i32,
+) -> Result<Teacher, AppError> {
+    sqlx::query_as::<_, TeacherRow>(
+        "select id, name, picture_url, profile, deleted_at, version from teacher where id = ? and deleted_at is null",
+    )
+        .bind(teacher_id)
+        .fetch_optional(&mut *conn)
+        .await?
+        .map(teacher_from_row)
+        .ok_or_else(|| AppError::NotFound("teacher".into()))
+}
+
+#[instrument(skip_all, fields(teacher_id = teacher_id))]
+pub async fn get_teacher_credential_db(
+    conn: &mut SqliteConnection,
+    teacher_id: i32,
+) -> Result<Option<TeacherCredential>, AppError> {
+    let row = sqlx::query_as::<_, CredentialRow>(
+        "select id, password_hash, role from teacher where id = ? and deleted_at is null",
+    )
+        .bind(teacher_id)
+        .fetch_optional(&mut *conn)
+        .await?;
+    Ok(row.map(credential_from_row))
+}
+
+#[instrument(skip_all)]
+pub async fn get_teacher_credential_by_email_db(
+    conn: &mut SqliteConnection,
+    email: &str,
+) -> Result<Option<TeacherCredential>, AppError> {
+    let row = sqlx::query_as::<_, CredentialRow>(
+        "select id, password_hash, role from teacher where lower(email) = lower(?) and deleted_at is null",
+    )
+        .bind(email)
+        .fetch_optional(&mut *conn)
+        .await?;
+    Ok(row.map(credential_from_row))
+}
+
+#[instrument(skip_all)]
+pub async fn teacher_email_exists_db(conn: &mut SqliteConnection, email: &str) -> Result<bool, AppError> {
+    let exists: bool = sqlx::query_scalar(
+        "select exists(select 1 from teacher where lower(email) = lower(?))",
+    )
+        .bind(email)
+        .fetch_one(&mut *conn)
+        .await?;
+    Ok(exists)
+}
+
+#[instrument(skip_all, fields(teacher_id = teacher_id))]
+pub async fn update_teacher_password_db(
+    conn: &mut SqliteConnection,
+    teacher_id: i32,
+    password_hash: String,
+) -> Result<(), AppError> {
+    let result = sqlx::query("update teacher set password_hash = ? where id = ? and deleted_at is null")
+        .bind(password_hash)
+        .bind(teacher_id)
+        .execute(&mut *conn)
+        .await?;
+    match result.rows_affected() {
+        0 => Err(AppError::NotFound("teacher".into())),
+        _ => Ok(()),
+    }
+}
+
+// password_hash 为已经哈希过的密码, 不会出现在返回的 Teacher 中
+#[instrument(skip_all)]
+pub async fn post_new_teacher_db(
+    conn: &mut SqliteConnection,
+    new_teacher: CreateTeacher,
+    password_hash: Option<String>,
+) -> Result<Teacher, AppError> {
+    let row = sqlx::query_as::<_, TeacherRow>(
+        r#"
+        insert into teacher (name, picture_url, profile, email, password_hash)
+        values (?, ?, ?, ?, ?)
+        returning id, name, picture_url, profile, deleted_at, version
+        "#,
+    )
+        .bind(new_teacher.name)
+        .bind(new_teacher.picture_url)
+        .bind(new_teacher.profile)
+        .bind(new_teacher.email)
+        .bind(password_hash)
+        .fetch_all(&mut *conn)
+        .await?
+        .pop()
+        .ok_or(sqlx::Error::RowNotFound)?;
+    Ok(teacher_from_row(row))
+}
+
+#[instrument(skip_all, fields(teacher_id = teacher_id))]
+pub async fn update_teacher_details_db(
+    conn: &mut SqliteConnection,
+    teacher_id: i32,
+    if_match: &IfMatch,
+    patch: PatchTeacher,
+) -> Result<Teacher, AppError> {
+    // SQLite 没有 select for update, 写入时检查版本号没有变化
+    // 读取原始的记录, 传入 null 的字段清空为空字符串
+    let (_, name, picture_url, profile, _, version) = sqlx::query_as::<_, TeacherRow>(
+        "select id, name, picture_url, profile, deleted_at, version from teacher where id = ? and deleted_at is null",
+    )
+        .bind(teacher_id)
+        .fetch_optional(&mut *conn)
+        .await?
+        .ok_or_else(|| AppError::NotFound("teacher".into()))?;
+    check_version(if_match, version)?;
+
+    sqlx::query_as::<_, TeacherRow>(
+        r#"
+        update teacher set
+            name = ?,
+            picture_url = ?,
+            profile = ?,
+            version = version + 1
+        where id = ? and deleted_at is null and version = ?
+        returning id, name, picture_url, profile, deleted_at, version
+        "#,
+    )
+        .bind(patch.name.flatten().or(name))
+        .bind(patch.picture_url.map(Option::unwrap_or_default).or(picture_url))
+        .bind(patch.profile.map(Option::unwrap_or_default).or(profile))
+        .bind(teacher_id)
+        .bind(version)
+        .fetch_all(&mut *conn)
+        .await?
+        .pop()
+        .map(teacher_from_row)
+        .ok_or_else(|| AppError::PreconditionFailed("Record was modified concurrently".into()))
+}
+
+// 只移入回收站, 老师还有未删除的课程时返回 409 及课程数
+// cascade 为 true 时在同一个事务中把老师和他的课程一起移入回收站
+#[instrument(skip_all, fields(teacher_id = teacher_id, cascade = cascade))]
+pub async fn delete_teacher_db(
+    conn: &mut SqliteConnection,
+    teacher_id: i32,
+    if_match: &IfMatch,
+    cascade: bool,
+) -> Result<DeletedTeacher, AppError> {
+    let mut tx = conn.begin().await?;
+    // SQLite 的写事务是串行的, 先执行写入以获得写锁, 再检查版本号和课程, 检查失败时回滚
+    // current_timestamp 只精确到秒, 删除时间精确到毫秒, 恢复时才能区分同一秒内单独删除的课程
+    let version: i32 = sqlx::query_scalar(
+        "update teacher set deleted_at = strftime('%Y-%m-%d %H:%M:%f', 'now'), version = version + 1 \
+         where id = ? and deleted_at is null returning version - 1",
+    )
+        .bind(teacher_id)
+        .fetch_optional(&mut *tx)
+        .await?
+        .ok_or_else(|| AppError::NotFound("teacher".into()))?;
+    check_version(if_match, version)?;
+
+    // 课程的删除时间与老师相同, 恢复老师时据此找到一起删除的课程
+    let deleted_courses = if cascade {
+        sqlx::query(
+            "update course set deleted_at = (select deleted_at from teacher where id = ?), \
+             version = version + 1 where teacher_id = ? and deleted_at is null",
+        )
+            .bind(teacher_id)
+            .bind(teacher_id)
+            .execute(&mut *tx)
+            .await?
+            .rows_affected()
+    } else {
+        let courses: i64 = sqlx::query_scalar(
+            "select count(*) from course where teacher_id = ? and deleted_at is null",
+        )
+            .bind(teacher_id)
+            .fetch_one(&mut *tx)
+            .await?;
+        if courses > 0 {
+            return Err(AppError::HasDependents("course".into(), courses));
+        }
+        0
+    };
+    tx.commit().await?;
+
+    Ok(DeletedTeacher {
+        teacher_id,
+        deleted_courses,
+    })
+}
+
+#[instrument(skip_all)]
+pub async fn get_deleted_teachers_db(conn: &mut SqliteConnection) -> Result<Vec<Teacher>, AppError> {
+    let teachers = sqlx::query_as::<_, TeacherRow>(
+        "select id, name, picture_url, profile, deleted_at, version from teacher \
+         where deleted_at is not null order by deleted_at desc, id desc",
+    )
+        .fetch_all(&mut *conn)
+        .await?
+        .into_iter()
+        .map(teacher_from_row)
+        .collect();
+    Ok(teachers)
+}
+
+#[instrument(skip_all, fields(teacher_id = teacher_id))]
+pub async fn restore_teacher_db(conn: &mut SqliteConnection, teacher_id: i32) -> Result<Teacher, AppError> {
+    let mut tx = conn.begin().await?;
+    // 与 Postgres 的实现一致, 删除时间与老师相同的课程是与老师一起删除的, 一起恢复
+    sqlx::query(
+        r#"update course set deleted_at = null, version = version + 1
+        where teacher_id = ?
+            and deleted_at = (select deleted_at from teacher where id = ? and deleted_at is not null)"#,
+    )
+        .bind(teacher_id)
+        .bind(teacher_id)
+        .execute(&mut *tx)
+        .await?;
+    let teacher = sqlx::query_as::<_, TeacherRow>(
+        r#"update teacher set deleted_at = null, version = version + 1
+        where id = ? and deleted_at is not null
+        returning id, name, picture_url, profile, deleted_at, version"#,
+    )
+        .bind(teacher_id)
+        .fetch_optional(&mut *tx)
+        .await?
+        .map(teacher_from_row)
+        .ok_or_else(|| AppError::NotFound("teacher".into()))?;
+    tx.commit().await?;
+    Ok(teacher)
+}
+
+// 老师的课程(包括回收站中的)全部被彻底删除后才能删除老师
+#[instrument(skip_all)]
+pub async fn purge_deleted_teachers_db(
+    conn: &mut SqliteConnection,
+    retention: Duration,
+) -> Result<u64, AppError> {
+    let result = sqlx::query(
+        r#"delete from teacher
+        where deleted_at < datetime('now', ?)
+            and not exists(select 1 from course where course.teacher_id = teacher.id)"#,
+    )
+        .bind(format!("{} seconds", -retention.num_seconds()))
+        .execute(&mut *conn)
+        .await
+        .map_err(|e| foreign_key_error(e, AppError::still_referenced))?;
+    Ok(result.rows_affected())
+}
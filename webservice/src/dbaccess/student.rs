@@ -0,0 +1,209 @@
+use sqlx::postgres::PgConnection;
+use sqlx::Connection;
+use tracing::instrument;
+
+use crate::errors::AppError;
+use crate::models::student::{
+    already_enrolled, course_full, CourseEnrollments, EnrolledCourse, EnrolledStudent, Enrollment,
+    RegisterStudent, Student, StudentCredential,
+};
+
+// 课程必须存在、未删除且属于该老师, 返回课程的名额
+// lock 为 true 时锁住课程, 同一门课程的选课依次执行, 保证人数不会超出名额
+async fn course_capacity(
+    conn: &mut PgConnection,
+    teacher_id: i32,
+    course_id: i32,
+    lock: bool,
+) -> Result<Option<i32>, AppError> {
+    let capacity = match lock {
+        true => sqlx::query_scalar!(
+            r#"select capacity from course where id = $1 and teacher_id = $2 and deleted_at is null for no key update"#,
+            course_id,
+            teacher_id,
+        )
+            .fetch_optional(conn)
+            .await?,
+        false => sqlx::query_scalar!(
+            r#"select capacity from course where id = $1 and teacher_id = $2 and deleted_at is null"#,
+            course_id,
+            teacher_id,
+        )
+            .fetch_optional(conn)
+            .await?,
+    };
+    capacity.ok_or_else(|| AppError::NotFound("course".into()))
+}
+
+async fn ensure_student(conn: &mut PgConnection, student_id: i32) -> Result<(), AppError> {
+    let exists = sqlx::query_scalar!(
+        r#"select exists(select 1 from student where id = $1) as "exists!""#,
+        student_id,
+    )
+        .fetch_one(conn)
+        .await?;
+    match exists {
+        true => Ok(()),
+        false => Err(AppError::NotFound("student".into())),
+    }
+}
+
+#[instrument(skip_all, fields(student_id = student_id))]
+pub async fn get_student_db(conn: &mut PgConnection, student_id: i32) -> Result<Student, AppError> {
+    sqlx::query_as!(
+        Student,
+        r#"select id, name, email, time from student where id = $1"#,
+        student_id,
+    )
+        .fetch_optional(&mut *conn)
+        .await?
+        .ok_or_else(|| AppError::NotFound("student".into()))
+}
+
+#[instrument(skip_all)]
+pub async fn get_student_credential_by_email_db(
+    conn: &mut PgConnection,
+    email: &str,
+) -> Result<Option<StudentCredential>, AppError> {
+    let credential = sqlx::query_as!(
+        StudentCredential,
+        r#"select id, password_hash from student where lower(email) = lower($1)"#,
+        email,
+    )
+        .fetch_optional(&mut *conn)
+        .await?;
+    Ok(credential)
+}
+
+// 邮箱重复时由唯一索引 student_email_key 返回 Conflict
+#[instrument(skip_all)]
+pub async fn post_new_student_db(
+    conn: &mut PgConnection,
+    new_student: RegisterStudent,
+    password_hash: String,
+) -> Result<Student, AppError> {
+    let student = sqlx::query_as!(
+        Student,
+        r#"insert into student (name, email, password_hash) values ($1, $2, $3)
+        returning id, name, email, time"#,
+        new_student.name,
+        new_student.email,
+        password_hash,
+    )
+        .fetch_one(&mut *conn)
+        .await?;
+    Ok(student)
+}
+
+#[instrument(skip_all, fields(teacher_id = teacher_id, course_id = course_id, student_id = student_id))]
+pub async fn enroll_db(
+    conn: &mut PgConnection,
+    teacher_id: i32,
+    course_id: i32,
+    student_id: i32,
+) -> Result<Enrollment, AppError> {
+    let mut tx = conn.begin().await?;
+    let capacity = course_capacity(&mut tx, teacher_id, course_id, true).await?;
+    ensure_student(&mut tx, student_id).await?;
+
+    let enrolled = sqlx::query_scalar!(
+        r#"select exists(select 1 from enrollment where course_id = $1 and student_id = $2) as "exists!""#,
+        course_id,
+        student_id,
+    )
+        .fetch_one(&mut *tx)
+        .await?;
+    if enrolled {
+        return Err(already_enrolled());
+    }
+    if let Some(capacity) = capacity {
+        let count = sqlx::query_scalar!(
+            r#"select count(*) as "count!" from enrollment where course_id = $1"#,
+            course_id,
+        )
+            .fetch_one(&mut *tx)
+            .await?;
+        if count >= capacity as i64 {
+            return Err(course_full());
+        }
+    }
+
+    let enrollment = sqlx::query_as!(
+        Enrollment,
+        r#"insert into enrollment (course_id, student_id) values ($1, $2)
+        returning course_id, student_id, enrolled_at"#,
+        course_id,
+        student_id,
+    )
+        .fetch_one(&mut *tx)
+        .await?;
+    tx.commit().await?;
+    Ok(enrollment)
+}
+
+#[instrument(skip_all, fields(teacher_id = teacher_id, course_id = course_id, student_id = student_id))]
+pub async fn unenroll_db(
+    conn: &mut PgConnection,
+    teacher_id: i32,
+    course_id: i32,
+    student_id: i32,
+) -> Result<(), AppError> {
+    let mut tx = conn.begin().await?;
+    course_capacity(&mut tx, teacher_id, course_id, true).await?;
+    let deleted = sqlx::query!(
+        r#"delete from enrollment where course_id = $1 and student_id = $2"#,
+        course_id,
+        student_id,
+    )
+        .execute(&mut *tx)
+        .await?;
+    if deleted.rows_affected() == 0 {
+        return Err(AppError::NotFound("enrollment".into()));
+    }
+    tx.commit().await?;
+    Ok(())
+}
+
+#[instrument(skip_all, fields(teacher_id = teacher_id, course_id = course_id))]
+pub async fn get_course_enrollments_db(
+    conn: &mut PgConnection,
+    teacher_id: i32,
+    course_id: i32,
+) -> Result<CourseEnrollments, AppError> {
+    let capacity = course_capacity(&mut *conn, teacher_id, course_id, false).await?;
+    let students = sqlx::query_as!(
+        EnrolledStudent,
+        r#"select student.id as student_id, student.name, student.email, enrollment.enrolled_at
+        from enrollment join student on student.id = enrollment.student_id
+        where enrollment.course_id = $1
+        order by enrollment.enrolled_at, student.id"#,
+        course_id,
+    )
+        .fetch_all(&mut *conn)
+        .await?;
+    Ok(CourseEnrollments {
+        course_id,
+        capacity,
+        total: students.len() as i64,
+        students,
+    })
+}
+
+// 课程的列较多, 与搜索一样使用运行时的查询, 通过 CourseRow 转换为 Course
+#[instrument(skip_all, fields(student_id = student_id))]
+pub async fn get_student_courses_db(
+    conn: &mut PgConnection,
+    student_id: i32,
+) -> Result<Vec<EnrolledCourse>, AppError> {
+    ensure_student(&mut *conn, student_id).await?;
+    let courses = sqlx::query_as::<_, EnrolledCourse>(
+        r#"select course.*, enrollment.enrolled_at
+        from enrollment join course on course.id = enrollment.course_id
+        where enrollment.student_id = $1 and course.deleted_at is null
+        order by enrollment.enrolled_at desc, course.id desc"#,
+    )
+        .bind(student_id)
+        .fetch_all(&mut *conn)
+        .await?;
+    Ok(courses)
+}
@@ -1,147 +1,350 @@
-use sqlx::PgPool;
+use chrono::Duration;
+use sqlx::{Connection, PgConnection};
+use tracing::instrument;
 
-use crate::{errors::AppError, models::teacher::{CreateTeacher, Teacher, UpdateTeacher}};
+use crate::{
+    dbaccess::still_referenced_error,
+    errors::AppError,
+    etag::{check_version, IfMatch},
+    models::{
+        auth::{Role, TeacherCredential},
+        teacher::{CreateTeacher, DeletedTeacher, PatchTeacher, Teacher},
+    },
+};
 
 
+#[instrument(skip_all)]
 pub async fn get_all_teacher_db(
-    pool: &PgPool
+    conn: &mut PgConnection
 ) -> Result<Vec<Teacher>, AppError> {
     let rows = sqlx::query!(
-        r#"select id, name, picture_url, profile from teacher "#
+        r#"select id, name, picture_url, profile, deleted_at, version from teacher where deleted_at is null"#
     )
-        .fetch_all(pool)
+        .fetch_all(&mut *conn)
         .await?;
     let teachers: Vec<Teacher> = rows
         .iter()
         .map(|r| Teacher{
             id: r.id,     
-            name: r.name.clone().unwrap(),
-            picture_url: r.picture_url.clone().unwrap(),
-            profile: r.profile.clone().unwrap(),
+            name: r.name.clone().unwrap_or_default(),
+            picture_url: r.picture_url.clone().unwrap_or_default(),
+            profile: r.profile.clone().unwrap_or_default(),
+            deleted_at: r.deleted_at,
+            version: r.version,
         }).collect();
 
     match teachers.len() {
-        0 => Err(AppError::NotFound("No teachers found".into())),
+        0 => Err(AppError::NotFound("teacher".into())),
         _ => Ok(teachers),
     }
 }
 
+#[instrument(skip_all, fields(teacher_id = teacher_id))]
 pub async fn get_teacher_detail_db(
-    pool: &PgPool,
+    conn: &mut PgConnection,
     teacher_id: i32,
 ) -> Result<Teacher, AppError> {
     let row = sqlx::query!(
-        r#"select id, name, picture_url, profile from teacher where id = $1"#,
+        r#"select id, name, picture_url, profile, deleted_at, version from teacher where id = $1 and deleted_at is null"#,
         teacher_id,
     )
-        .fetch_one(pool)
-        .await
+        .fetch_optional(&mut *conn)
+        .await?
         .map(|r| Teacher {
             id: r.id, 
-            name: r.name.clone().unwrap(),
-            picture_url: r.picture_url.clone().unwrap(),
-            profile: r.profile.clone().unwrap(),
+            name: r.name.clone().unwrap_or_default(),
+            picture_url: r.picture_url.clone().unwrap_or_default(),
+            profile: r.profile.clone().unwrap_or_default(),
+            deleted_at: r.deleted_at,
+            version: r.version,
         })
-        .map_err(|_err| AppError::NotFound("Teacher Id not found".into()))?;
+        .ok_or_else(|| AppError::NotFound("teacher".into()))?;
     Ok(row)
 }
 
+#[instrument(skip_all, fields(teacher_id = teacher_id))]
+pub async fn get_teacher_credential_db(
+    conn: &mut PgConnection,
+    teacher_id: i32,
+) -> Result<Option<TeacherCredential>, AppError> {
+    let row = sqlx::query!(
+        r#"select id, password_hash, role from teacher where id = $1 and deleted_at is null"#,
+        teacher_id,
+    )
+        .fetch_optional(&mut *conn)
+        .await?;
+    Ok(row.map(|r| TeacherCredential {
+        id: r.id,
+        password_hash: r.password_hash,
+        role: Role::from_db(&r.role),
+    }))
+}
+
+#[instrument(skip_all)]
+pub async fn get_teacher_credential_by_email_db(
+    conn: &mut PgConnection,
+    email: &str,
+) -> Result<Option<TeacherCredential>, AppError> {
+    let row = sqlx::query!(
+        r#"select id, password_hash, role from teacher where lower(email) = lower($1) and deleted_at is null"#,
+        email,
+    )
+        .fetch_optional(&mut *conn)
+        .await?;
+    Ok(row.map(|r| TeacherCredential {
+        id: r.id,
+        password_hash: r.password_hash,
+        role: Role::from_db(&r.role),
+    }))
+}
+
+#[instrument(skip_all)]
+pub async fn teacher_email_exists_db(
+    conn: &mut PgConnection,
+    email: &str,
+) -> Result<bool, AppError> {
+    let row = sqlx::query!(
+        r#"select exists(select 1 from teacher where lower(email) = lower($1)) as "exists!""#,
+        email,
+    )
+        .fetch_one(&mut *conn)
+        .await?;
+    Ok(row.exists)
+}
+
+#[instrument(skip_all, fields(teacher_id = teacher_id))]
+pub async fn update_teacher_password_db(
+    conn: &mut PgConnection,
+    teacher_id: i32,
+    password_hash: String,
+) -> Result<(), AppError> {
+    let result = sqlx::query!(
+        r#"update teacher set password_hash = $1 where id = $2 and deleted_at is null"#,
+        password_hash,
+        teacher_id,
+    )
+        .execute(&mut *conn)
+        .await?;
+    match result.rows_affected() {
+        0 => Err(AppError::NotFound("teacher".into())),
+        _ => Ok(()),
+    }
+}
+
+// password_hash 为已经哈希过的密码, 不会出现在返回的 Teacher 中
+#[instrument(skip_all)]
 pub async fn post_new_teacher_db(
-    pool: &PgPool,
+    conn: &mut PgConnection,
     new_teacher: CreateTeacher,
+    password_hash: Option<String>,
 ) -> Result<Teacher, AppError> {
     
     let row = sqlx::query!(
         r#"
-        insert into teacher (name, picture_url, profile) values ($1, $2, $3)
-        returning id, name, picture_url, profile
+        insert into teacher (name, picture_url, profile, email, password_hash)
+        values ($1, $2, $3, $4, $5)
+        returning id, name, picture_url, profile, deleted_at, version
         "#,
         new_teacher.name, 
         new_teacher.picture_url, 
         new_teacher.profile,
+        new_teacher.email,
+        password_hash,
     )
-        .fetch_one(pool)
+        .fetch_one(&mut *conn)
         .await?;
     Ok(Teacher {
         id: row.id, 
-        name: row.name.clone().unwrap(),
-        picture_url: row.picture_url.clone().unwrap(),
-        profile: row.profile.clone().unwrap(),
+        name: row.name.clone().unwrap_or_default(),
+        picture_url: row.picture_url.clone().unwrap_or_default(),
+        profile: row.profile.clone().unwrap_or_default(),
+        deleted_at: row.deleted_at,
+        version: row.version,
     })
 }
 
 
+#[instrument(skip_all, fields(teacher_id = teacher_id))]
 pub async fn update_teacher_details_db(
-    pool: &PgPool, 
+    conn: &mut PgConnection, 
     teacher_id: i32,
-    new_teacher: UpdateTeacher,
+    if_match: &IfMatch,
+    patch: PatchTeacher,
 ) -> Result<Teacher, AppError> {
+    // 合并与写入在同一个事务中完成, 避免覆盖其他请求的修改
+    let mut tx = conn.begin().await?;
     let row = sqlx::query!(
-        r#"select * from teacher where id = $1"#,
+        r#"select * from teacher where id = $1 and deleted_at is null for update"#,
         teacher_id,
     )
-        .fetch_one(pool)
-        .await
-        .map_err(|_e| AppError::NotFound("Teacher Id not found".into()))?;
+        .fetch_optional(&mut *tx)
+        .await?
+        .ok_or_else(|| AppError::NotFound("teacher".into()))?;
+    check_version(if_match, row.version)?;
 
-    let temp = Teacher {
-        id: row.id, 
-        name: if let Some(name) = new_teacher.name {
-            name
-        } else {
-            row.name.clone().unwrap()
-        },
-        picture_url: if let Some(picture_url) = new_teacher.picture_url {
-            picture_url
-        } else {
-            row.picture_url.clone().unwrap()
-        },
-        profile: if let Some(profile) = new_teacher.profile {
-            profile
-        } else {
-            row.profile.clone().unwrap()
-        }
-    };
+    // 没有传入的字段保留原值, 传入 null 的字段清空为空字符串, 与内存实现一致
+    let name = patch.name.flatten().or(row.name);
+    let picture_url = patch.picture_url.map(Option::unwrap_or_default).or(row.picture_url);
+    let profile = patch.profile.map(Option::unwrap_or_default).or(row.profile);
 
     let update_row = sqlx::query!(
         r#"
         update teacher set 
             name = $1,
             picture_url = $2,
-            profile = $3
-        where id = $4
-        returning id, name, picture_url, profile
+            profile = $3,
+            version = version + 1
+        where id = $4 and deleted_at is null
+        returning id, name, picture_url, profile, deleted_at, version
         "#,
-        temp.name, 
-        temp.picture_url,
-        temp.profile,
-        temp.id
+        name, 
+        picture_url,
+        profile,
+        teacher_id
     )
-        .fetch_one(pool)
-        .await
+        .fetch_optional(&mut *tx)
+        .await?
     .map(|row| Teacher {
         id: row.id,
-        name: row.name.clone().unwrap(),
-        picture_url: row.picture_url.clone().unwrap(),
-        profile: row.profile.clone().unwrap(),
+        name: row.name.unwrap_or_default(),
+        picture_url: row.picture_url.unwrap_or_default(),
+        profile: row.profile.unwrap_or_default(),
+        deleted_at: row.deleted_at,
+        version: row.version,
     })
-    .map_err(|_e| AppError::NotFound("Teacher Id not found".into()))?;
-            
+    .ok_or_else(|| AppError::NotFound("teacher".into()))?;
+    tx.commit().await?;
+
     Ok(update_row)
 }
 
 
+// 只移入回收站, 老师还有未删除的课程时返回 409 及课程数
+// cascade 为 true 时在同一个事务中把老师和他的课程一起移入回收站
+#[instrument(skip_all, fields(teacher_id = teacher_id, cascade = cascade))]
 pub async fn delete_teacher_db(
-    pool: &PgPool,
+    conn: &mut PgConnection,
     teacher_id: i32,
-) -> Result<String, AppError> {
-    let row = sqlx::query!(
-        r#"delete from teacher where id = $1"#, 
-        teacher_id
+    if_match: &IfMatch,
+    cascade: bool,
+) -> Result<DeletedTeacher, AppError> {
+    let mut tx = conn.begin().await?;
+    // 锁住老师, 删除完成前不能为其创建课程, 见 post_new_course_db
+    let version = sqlx::query_scalar!(
+        r#"select version from teacher where id = $1 and deleted_at is null for update"#,
+        teacher_id,
+    )
+        .fetch_optional(&mut *tx)
+        .await?
+        .ok_or_else(|| AppError::NotFound("teacher".into()))?;
+    check_version(if_match, version)?;
+
+    let deleted_courses = if cascade {
+        sqlx::query!(
+            r#"update course set deleted_at = now(), version = version + 1
+            where teacher_id = $1 and deleted_at is null"#,
+            teacher_id,
+        )
+            .execute(&mut *tx)
+            .await?
+            .rows_affected()
+    } else {
+        let courses = sqlx::query_scalar!(
+            r#"select count(*) as "count!" from course where teacher_id = $1 and deleted_at is null"#,
+            teacher_id,
+        )
+            .fetch_one(&mut *tx)
+            .await?;
+        if courses > 0 {
+            return Err(AppError::HasDependents("course".into(), courses));
+        }
+        0
+    };
+
+    sqlx::query!(
+        r#"update teacher set deleted_at = now(), version = version + 1 where id = $1"#,
+        teacher_id,
+    )
+        .execute(&mut *tx)
+        .await?;
+    tx.commit().await?;
+
+    Ok(DeletedTeacher {
+        teacher_id,
+        deleted_courses,
+    })
+}
+
+#[instrument(skip_all)]
+pub async fn get_deleted_teachers_db(conn: &mut PgConnection) -> Result<Vec<Teacher>, AppError> {
+    let rows = sqlx::query!(
+        r#"select id, name, picture_url, profile, deleted_at, version from teacher
+        where deleted_at is not null
+        order by deleted_at desc, id desc"#
     )
-        .execute(pool)
+        .fetch_all(&mut *conn)
+        .await?;
+    Ok(rows
+        .into_iter()
+        .map(|r| Teacher {
+            id: r.id,
+            name: r.name.unwrap_or_default(),
+            picture_url: r.picture_url.unwrap_or_default(),
+            profile: r.profile.unwrap_or_default(),
+            deleted_at: r.deleted_at,
+            version: r.version,
+        })
+        .collect())
+}
+
+#[instrument(skip_all, fields(teacher_id = teacher_id))]
+pub async fn restore_teacher_db(conn: &mut PgConnection, teacher_id: i32) -> Result<Teacher, AppError> {
+    let mut tx = conn.begin().await?;
+    // 级联删除时课程与老师在同一个事务中删除, now() 相同, 删除时间与老师相同的课程一起恢复
+    // 之前单独删除的课程仍留在回收站中
+    sqlx::query!(
+        r#"update course set deleted_at = null, version = version + 1
+        where teacher_id = $1
+            and deleted_at = (select deleted_at from teacher where id = $1 and deleted_at is not null for update)"#,
+        teacher_id,
+    )
+        .execute(&mut *tx)
+        .await?;
+    let teacher = sqlx::query!(
+        r#"update teacher set deleted_at = null, version = version + 1
+        where id = $1 and deleted_at is not null
+        returning id, name, picture_url, profile, deleted_at, version"#,
+        teacher_id,
+    )
+        .fetch_optional(&mut *tx)
+        .await?
+        .map(|r| Teacher {
+            id: r.id,
+            name: r.name.unwrap_or_default(),
+            picture_url: r.picture_url.unwrap_or_default(),
+            profile: r.profile.unwrap_or_default(),
+            deleted_at: r.deleted_at,
+            version: r.version,
+        })
+        .ok_or_else(|| AppError::NotFound("teacher".into()))?;
+    tx.commit().await?;
+    Ok(teacher)
+}
+
+// 老师的课程(包括回收站中的)全部被彻底删除后才能删除老师
+#[instrument(skip_all)]
+pub async fn purge_deleted_teachers_db(
+    conn: &mut PgConnection,
+    retention: Duration,
+) -> Result<u64, AppError> {
+    let result = sqlx::query!(
+        r#"delete from teacher
+        where deleted_at < now() - make_interval(secs => $1)
+            and not exists(select 1 from course where course.teacher_id = teacher.id)"#,
+        retention.num_seconds() as f64,
+    )
+        .execute(&mut *conn)
         .await
-    .map_err(|_e| AppError::DBError("Unable to delete teacher".into()))?;
-    
-    Ok(format!("Deleted teacher {:?} records", row))
+        .map_err(still_referenced_error)?;
+    Ok(result.rows_affected())
 }
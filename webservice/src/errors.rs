@@ -1,39 +1,173 @@
 use actix_web::{error, http::StatusCode, HttpResponse, body::BoxBody};
 use serde::Serialize;
-use sqlx::error::Error as SQLxError;
+use sqlx::error::{Error as SQLxError, ErrorKind};
+use sqlx::postgres::PgDatabaseError;
+use sqlx::migrate::MigrateError;
 use std::fmt::{self, Display};
+use validator::ValidationErrors;
+
+use crate::request_id::current_request_id;
 
 #[derive(Debug, Serialize)]
 pub enum AppError {
     DBError(String),
     ActixError(String),
+    // 找不到的资源名称, 如 course, teacher, 错误码为 <资源>_not_found
     NotFound(String),
     InvalidaValue(String),
+    Unauthorized(String),
+    Forbidden(String),
+    Conflict(String),
+    // 写入的数据违反了数据库约束(外键、check 等), 提示信息中包含约束名
+    ConstraintViolation(String),
+    // 删除的记录仍被其他记录引用, 包含引用它的资源名称与数量, 如 ("course", 3)
+    HasDependents(String, i64),
+    // If-Match 中的版本与当前版本不一致
+    PreconditionFailed(String),
+    // 修改或删除时没有携带 If-Match
+    PreconditionRequired(String),
+    // 请求参数校验失败, 包含每个字段的错误
+    ValidationFailed(Vec<FieldError>),
+}
+
+// 字段级别的错误信息
+#[derive(Debug, Serialize, Clone, PartialEq)]
+pub struct FieldError {
+    pub field: String,
+    pub message: String,
 }
 
+impl FieldError {
+    pub fn new(field: impl Into<String>, message: impl Into<String>) -> Self {
+        FieldError {
+            field: field.into(),
+            message: message.into(),
+        }
+    }
+}
+
+// 返回给客户端的错误, 客户端应该根据 code 判断错误类型, error_message 只用于展示
 #[derive(Debug, Serialize)]
 pub struct ErrorResponse {
+    code: String,
     error_message: String,
+    // 与响应头 X-Request-Id 一致, 方便根据日志排查问题
+    request_id: Option<String>,
+    #[serde(skip_serializing_if = "Option::is_none")]
+    details: Option<Vec<FieldError>>,
+    #[serde(skip_serializing_if = "Option::is_none")]
+    dependents: Option<Dependents>,
+}
+
+// 仍然引用被删除记录的资源及其数量
+#[derive(Debug, Serialize)]
+pub struct Dependents {
+    resource: String,
+    count: i64,
 }
 
 impl AppError {
+    // 单个字段校验失败
+    pub fn invalid_field(field: impl Into<String>, message: impl Into<String>) -> Self {
+        AppError::ValidationFailed(vec![FieldError::new(field, message)])
+    }
+
+    // 违反数据库约束时的错误, 不依赖数据库的存储实现也使用这些函数, 保证提示信息一致
+    pub fn unique_violation(constraint: &str) -> Self {
+        AppError::Conflict(format!(
+            "Duplicate value violates unique constraint {}",
+            constraint
+        ))
+    }
+
+    pub fn still_referenced(constraint: &str) -> Self {
+        AppError::Conflict(format!(
+            "Record is still referenced by foreign key {}",
+            constraint
+        ))
+    }
+
+    pub fn missing_reference(constraint: &str) -> Self {
+        AppError::ConstraintViolation(format!(
+            "Referenced record does not exist, foreign key {}",
+            constraint
+        ))
+    }
+
+    pub fn check_violation(constraint: &str) -> Self {
+        AppError::ConstraintViolation(format!(
+            "Value violates check constraint {}",
+            constraint
+        ))
+    }
+
+    // 稳定的错误码, 不会随提示信息变化
+    pub fn code(&self) -> String {
+        match self {
+            AppError::DBError(_) => "database_error".into(),
+            AppError::ActixError(_) => "internal_error".into(),
+            AppError::NotFound(resource) => format!("{}_not_found", resource),
+            AppError::InvalidaValue(_) => "invalid_request".into(),
+            AppError::Unauthorized(_) => "unauthorized".into(),
+            AppError::Forbidden(_) => "forbidden".into(),
+            AppError::Conflict(_) => "conflict".into(),
+            AppError::ConstraintViolation(_) => "constraint_violation".into(),
+            AppError::HasDependents(_, _) => "has_dependents".into(),
+            AppError::PreconditionFailed(_) => "precondition_failed".into(),
+            AppError::PreconditionRequired(_) => "precondition_required".into(),
+            AppError::ValidationFailed(_) => "validation_failed".into(),
+        }
+    }
+
     fn error_response(&self) -> String {
         match self {
             AppError::DBError(e) => {
-                println!("Database error occurred: {:?}", e);
+                tracing::error!(error = %e, "Database error occurred");
                 "Database error".into()
             },
             AppError::ActixError(e) => {
-                println!("Server error occurred: {:?}", e);
+                tracing::error!(error = %e, "Server error occurred");
                 "Internel server error".into()
             }
-            AppError::NotFound(e) => {
-                println!("Not Found error occurred: {:?}", e);
-                e.into()
+            AppError::NotFound(resource) => {
+                tracing::info!(resource = %resource, "Not found");
+                format!("{} not found", resource)
             }
             AppError::InvalidaValue(e) => {
-                println!("Invalide request param {:?}", e);
-                "Invalide request params".into()
+                tracing::info!(error = %e, "Invalid request param");
+                e.into()
+            }
+            AppError::Unauthorized(e) => {
+                tracing::warn!(error = %e, "Unauthorized request");
+                e.into()
+            }
+            AppError::Forbidden(e) => {
+                tracing::warn!(error = %e, "Forbidden request");
+                e.into()
+            }
+            AppError::Conflict(e) => {
+                tracing::info!(error = %e, "Conflict occurred");
+                e.into()
+            }
+            AppError::ConstraintViolation(e) => {
+                tracing::info!(error = %e, "Constraint violation");
+                e.into()
+            }
+            AppError::ValidationFailed(details) => {
+                tracing::info!(details = ?details, "Validation failed");
+                "Request validation failed".into()
+            }
+            AppError::HasDependents(resource, count) => {
+                tracing::info!(resource = %resource, count, "Record still has dependents");
+                format!("Record is still referenced by {} {} records", count, resource)
+            }
+            AppError::PreconditionFailed(e) => {
+                tracing::info!(error = %e, "Precondition failed");
+                e.into()
+            }
+            AppError::PreconditionRequired(e) => {
+                tracing::info!(error = %e, "Precondition required");
+                e.into()
             }
 
         }
@@ -47,14 +181,37 @@ impl error::ResponseError for AppError {
             AppError::DBError(_) => StatusCode::INTERNAL_SERVER_ERROR,
             AppError::ActixError(_) => StatusCode::INTERNAL_SERVER_ERROR,
             AppError::NotFound(_) => StatusCode::NOT_FOUND,
-            AppError::InvalidaValue(_) => StatusCode::BAD_REQUEST
+            AppError::InvalidaValue(_) => StatusCode::BAD_REQUEST,
+            AppError::Unauthorized(_) => StatusCode::UNAUTHORIZED,
+            AppError::Forbidden(_) => StatusCode::FORBIDDEN,
+            AppError::Conflict(_) => StatusCode::CONFLICT,
+            AppError::ConstraintViolation(_) => StatusCode::UNPROCESSABLE_ENTITY,
+            AppError::ValidationFailed(_) => StatusCode::UNPROCESSABLE_ENTITY,
+            AppError::HasDependents(_, _) => StatusCode::CONFLICT,
+            AppError::PreconditionFailed(_) => StatusCode::PRECONDITION_FAILED,
+            AppError::PreconditionRequired(_) => StatusCode::PRECONDITION_REQUIRED,
         }
     }
 
     // 返回的响应提
     fn error_response(&self) -> HttpResponse<BoxBody> {
+        let details = match self {
+            AppError::ValidationFailed(details) => Some(details.clone()),
+            _ => None,
+        };
+        let dependents = match self {
+            AppError::HasDependents(resource, count) => Some(Dependents {
+                resource: resource.clone(),
+                count: *count,
+            }),
+            _ => None,
+        };
         HttpResponse::build(self.status_code()).json(ErrorResponse {
+            code: self.code(),
             error_message: self.error_response(),
+            request_id: current_request_id(),
+            details,
+            dependents,
         })
     }
 }
@@ -71,9 +228,144 @@ impl From<error::Error> for AppError {
     }
 }
 
+// 根据数据库返回的错误类型转换为对应的状态码, 其他错误仍然作为 500 处理
 impl From<SQLxError> for AppError {
     fn from(value: SQLxError) -> Self {
+        let db_err = match &value {
+            SQLxError::Database(db_err) => db_err,
+            // 记录不存在时由调用方返回具体资源的 NotFound, 这里出现 RowNotFound 说明查询不符合预期
+            _ => return AppError::DBError(value.to_string()),
+        };
+        let constraint = db_err
+            .constraint()
+            .or_else(|| message_constraint(db_err.message()))
+            .unwrap_or("unknown");
+        match db_err.kind() {
+            ErrorKind::UniqueViolation => AppError::unique_violation(constraint),
+            // 从错误中区分不了是写入了不存在的引用还是删除了仍被引用的记录, 默认按前者处理,
+            // 删除可能仍被引用的记录时由调用方通过 dbaccess::still_referenced_error 转换
+            ErrorKind::ForeignKeyViolation => AppError::missing_reference(constraint),
+            ErrorKind::CheckViolation => AppError::check_violation(constraint),
+            ErrorKind::NotNullViolation => {
+                let column = db_err
+                    .try_downcast_ref::<PgDatabaseError>()
+                    .and_then(|e| e.column())
+                    .or_else(|| message_column(db_err.message()))
+                    .unwrap_or("unknown");
+                AppError::invalid_field(column, "must not be null")
+            }
+            _ => AppError::DBError(value.to_string()),
+        }
+    }
+}
+
+// SQLite 不单独返回约束名, 从错误信息中解析, 如:
+// UNIQUE constraint failed: index 'teacher_email_key'
+// CHECK constraint failed: course_price_check
+// 外键错误的信息中没有约束名, 由 dbaccess::sqlite 转换
+fn message_constraint(message: &str) -> Option<&str> {
+    let (_, detail) = message.split_once("constraint failed: ")?;
+    match detail.strip_prefix("index '") {
+        Some(index) => index.strip_suffix('\''),
+        None if !detail.contains([' ', '.']) => Some(detail),
+        None => None,
+    }
+}
+
+// 如: NOT NULL constraint failed: course.name
+fn message_column(message: &str) -> Option<&str> {
+    let (_, column) = message.split_once("NOT NULL constraint failed: ")?;
+    column.rsplit('.').next()
+}
+
+// 声明式校验的结果, 每个字段的每条规则对应一条 FieldError
+impl From<ValidationErrors> for AppError {
+    fn from(value: ValidationErrors) -> Self {
+        let mut details: Vec<FieldError> = value
+            .field_errors()
+            .into_iter()
+            .flat_map(|(field, errors)| {
+                errors.iter().map(move |e| {
+                    let message = e
+                        .message
+                        .as_ref()
+                        .map(|m| m.to_string())
+                        .unwrap_or_else(|| e.code.to_string());
+                    FieldError::new(field.to_string(), message)
+                })
+            })
+            .collect();
+        details.sort_by(|a, b| a.field.cmp(&b.field));
+        AppError::ValidationFailed(details)
+    }
+}
+
+impl From<MigrateError> for AppError {
+    fn from(value: MigrateError) -> Self {
         AppError::DBError(value.to_string())
     }
 }
 
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use actix_web::{body::to_bytes, ResponseError};
+
+    #[actix_rt::test]
+    async fn not_found_uses_resource_code() {
+        let err = AppError::NotFound("course".into());
+        assert_eq!(err.code(), "course_not_found");
+        assert_eq!(err.status_code(), StatusCode::NOT_FOUND);
+
+        let body = to_bytes(ResponseError::error_response(&err).into_body()).await.unwrap();
+        let body = String::from_utf8(body.to_vec()).unwrap();
+        assert!(body.contains(r#""code":"course_not_found""#));
+        assert!(!body.contains("details"));
+    }
+
+    #[test]
+    fn constraint_from_sqlite_message() {
+        assert_eq!(
+            message_constraint("UNIQUE constraint failed: index 'teacher_email_key'"),
+            Some("teacher_email_key")
+        );
+        assert_eq!(
+            message_constraint("CHECK constraint failed: course_price_check"),
+            Some("course_price_check")
+        );
+        assert_eq!(message_constraint("UNIQUE constraint failed: teacher.email"), None);
+        assert_eq!(message_constraint("FOREIGN KEY constraint failed"), None);
+        assert_eq!(message_column("NOT NULL constraint failed: course.name"), Some("name"));
+    }
+
+    #[test]
+    fn row_not_found_is_not_a_client_error() {
+        let err = AppError::from(SQLxError::RowNotFound);
+        assert_eq!(err.status_code(), StatusCode::INTERNAL_SERVER_ERROR);
+    }
+
+    #[actix_rt::test]
+    async fn validation_failed_lists_fields() {
+        let err = AppError::ValidationFailed(vec![
+            FieldError::new("name", "must not be empty"),
+            FieldError::new("price", "must not be negative"),
+        ]);
+        assert_eq!(err.status_code(), StatusCode::UNPROCESSABLE_ENTITY);
+
+        let body = to_bytes(ResponseError::error_response(&err).into_body()).await.unwrap();
+        let body = String::from_utf8(body.to_vec()).unwrap();
+        assert!(body.contains(r#""code":"validation_failed""#));
+        assert!(body.contains(r#"{"field":"price","message":"must not be negative"}"#));
+    }
+
+    #[actix_rt::test]
+    async fn has_dependents_reports_count() {
+        let err = AppError::HasDependents("course".into(), 3);
+        assert_eq!(err.status_code(), StatusCode::CONFLICT);
+
+        let body = to_bytes(ResponseError::error_response(&err).into_body()).await.unwrap();
+        let body = String::from_utf8(body.to_vec()).unwrap();
+        assert!(body.contains(r#""code":"has_dependents""#));
+        assert!(body.contains(r#""dependents":{"resource":"course","count":3}"#));
+    }
+}
@@ -0,0 +1,120 @@
+use std::collections::BTreeSet;
+use std::future::{ready, Ready};
+
+use actix_web::http::header::{self, EntityTag, Header};
+use actix_web::{dev::Payload, FromRequest, HttpRequest};
+
+use crate::errors::AppError;
+
+// 根据版本号生成强 ETag, 如 "3"
+pub fn etag(version: i32) -> header::ETag {
+    header::ETag(EntityTag::new_strong(version.to_string()))
+}
+
+// 修改或删除前检查版本号, If-Match 中任意一个版本号与当前版本一致即可(RFC 9110 13.1.1)
+// If-Match: * 时不检查
+pub fn check_version(if_match: &IfMatch, current: i32) -> Result<(), AppError> {
+    match &if_match.versions {
+        Some(versions) if !versions.contains(&current) => Err(AppError::PreconditionFailed(format!(
+            "If-Match does not contain current version {}",
+            current
+        ))),
+        _ => Ok(()),
+    }
+}
+
+// 请求头 If-Match 中的版本号, 在 handler 参数中声明即要求请求携带 If-Match, 缺少时返回 428
+// If-Match: * 匹配任意版本, 此时 versions 为 None
+#[derive(Debug, Clone, PartialEq)]
+pub struct IfMatch {
+    pub versions: Option<BTreeSet<i32>>,
+}
+
+impl IfMatch {
+    pub const ANY: IfMatch = IfMatch { versions: None };
+
+    pub fn version(version: i32) -> Self {
+        IfMatch {
+            versions: Some(BTreeSet::from([version])),
+        }
+    }
+
+    fn from_request_head(req: &HttpRequest) -> Result<Self, AppError> {
+        if !req.headers().contains_key(header::IF_MATCH) {
+            return Err(AppError::PreconditionRequired(
+                "If-Match header with the current ETag is required".into(),
+            ));
+        }
+        let tags = match header::IfMatch::parse(req) {
+            Ok(header::IfMatch::Any) => return Ok(IfMatch::ANY),
+            Ok(header::IfMatch::Items(tags)) => tags,
+            Err(_) => return Err(AppError::InvalidaValue("Invalid If-Match header".into())),
+        };
+        // If-Match 使用强比较, 弱 ETag 和不是版本号的 ETag 不会与任何版本匹配
+        let versions: BTreeSet<i32> = tags
+            .iter()
+            .filter(|tag| !tag.weak)
+            .filter_map(|tag| tag.tag().parse().ok())
+            .collect();
+        if versions.is_empty() {
+            return Err(AppError::PreconditionFailed(
+                "If-Match does not contain the current version".into(),
+            ));
+        }
+        Ok(IfMatch {
+            versions: Some(versions),
+        })
+    }
+}
+
+impl FromRequest for IfMatch {
+    type Error = AppError;
+    type Future = Ready<Result<Self, Self::Error>>;
+
+    fn from_request(req: &HttpRequest, _payload: &mut Payload) -> Self::Future {
+        ready(IfMatch::from_request_head(req))
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use actix_web::{http::StatusCode, test::TestRequest, ResponseError};
+
+    use super::*;
+
+    fn if_match(value: Option<&str>) -> Result<IfMatch, AppError> {
+        let mut req = TestRequest::default();
+        if let Some(value) = value {
+            req = req.insert_header((header::IF_MATCH, value));
+        }
+        IfMatch::from_request_head(&req.to_http_request())
+    }
+
+    #[test]
+    fn parse_if_match() {
+        assert_eq!(if_match(Some(r#""3""#)).unwrap(), IfMatch::version(3));
+        assert_eq!(if_match(Some("*")).unwrap(), IfMatch::ANY);
+
+        let err = if_match(None).unwrap_err();
+        assert_eq!(err.status_code(), StatusCode::PRECONDITION_REQUIRED);
+        let err = if_match(Some(r#"W/"3""#)).unwrap_err();
+        assert_eq!(err.status_code(), StatusCode::PRECONDITION_FAILED);
+        let err = if_match(Some(r#""abc""#)).unwrap_err();
+        assert_eq!(err.status_code(), StatusCode::PRECONDITION_FAILED);
+        let err = if_match(Some(r#"W/"1", "abc""#)).unwrap_err();
+        assert_eq!(err.status_code(), StatusCode::PRECONDITION_FAILED);
+
+        // 多个 ETag 时忽略弱 ETag 和不是版本号的 ETag, 其余的都可以匹配
+        let tags = if_match(Some(r#""1", W/"2", "abc", "3""#)).unwrap();
+        assert_eq!(tags.versions, Some(BTreeSet::from([1, 3])));
+        assert!(check_version(&tags, 1).is_ok());
+        assert!(check_version(&tags, 3).is_ok());
+        let err = check_version(&tags, 2).unwrap_err();
+        assert_eq!(err.code(), "precondition_failed");
+
+        assert!(check_version(&IfMatch::version(2), 2).is_ok());
+        assert!(check_version(&IfMatch::ANY, 2).is_ok());
+        let err = check_version(&IfMatch::version(1), 2).unwrap_err();
+        assert_eq!(err.code(), "precondition_failed");
+    }
+}
@@ -0,0 +1,106 @@
+use actix_web::{web, HttpResponse};
+
+use crate::{
+    auth::{verify_password, JwtConfig},
+    errors::AppError,
+    handlers::teacher::create_teacher,
+    models::auth::{LoginRequest, RegisterTeacher, Role, TokenResponse},
+    state::AppState,
+};
+
+
+// 老师注册, 邮箱不能重复
+pub async fn register(
+    app_state: web::Data<AppState>,
+    new_teacher: web::Json<RegisterTeacher>,
+) -> Result<HttpResponse, AppError> {
+    let new_teacher: RegisterTeacher = new_teacher.try_into()?;
+    create_teacher(&app_state, new_teacher.into())
+        .await
+        .map(|teacher| HttpResponse::Created().json(teacher))
+}
+
+// 老师使用邮箱登录, 校验密码后签发 token
+pub async fn login(
+    app_state: web::Data<AppState>,
+    jwt_config: web::Data<JwtConfig>,
+    login: web::Json<LoginRequest>,
+) -> Result<HttpResponse, AppError> {
+    let credential = app_state.teachers.get_teacher_credential_by_email(login.email.trim()).await?;
+    // 老师不存在和密码错误返回同样的错误, 避免暴露邮箱是否已注册
+    let credential = credential
+        .filter(|c| {
+            c.password_hash
+                .as_deref()
+                .is_some_and(|hash| verify_password(&login.password, hash))
+        })
+        .ok_or_else(|| AppError::Unauthorized("Invalid email or password".into()))?;
+
+    token_response(&jwt_config, credential.id, credential.role)
+}
+
+// 登录成功后签发 token, 老师与学生的登录共用
+pub(crate) fn token_response(jwt_config: &JwtConfig, id: i32, role: Role) -> Result<HttpResponse, AppError> {
+    let access_token = jwt_config.issue_token(id, role)?;
+    Ok(HttpResponse::Ok().json(TokenResponse {
+        access_token,
+        token_type: "Bearer".into(),
+        expires_in: jwt_config.ttl,
+    }))
+}
+
+#[cfg(test)]
+mod tests {
+    use actix_web::{http::StatusCode, web, ResponseError};
+
+    use crate::{
+        models::auth::{LoginRequest, RegisterTeacher},
+        test_support::{db_test, test_jwt_config},
+    };
+
+    use super::{login, register};
+
+    db_test!(register_then_login(app_state) {
+        let app_state = web::Data::new(app_state);
+        let jwt_config = web::Data::new(test_jwt_config());
+
+        let email = "teacher@unicorn.pro".to_string();
+        let new_teacher = RegisterTeacher {
+            name: "Registered teacher".into(),
+            picture_url: "http://unicorn.pro".into(),
+            profile: "A registered teacher".into(),
+            email: email.clone(),
+            password: "a-secret-password".into(),
+        };
+        let resp = register(app_state.clone(), web::Json(new_teacher.clone()))
+            .await
+            .unwrap();
+        assert_eq!(resp.status(), StatusCode::CREATED);
+
+        // 同一个邮箱(忽略大小写)不能重复注册
+        let duplicate = RegisterTeacher {
+            email: email.to_uppercase(),
+            ..new_teacher
+        };
+        let err = register(app_state.clone(), web::Json(duplicate))
+            .await
+            .unwrap_err();
+        assert_eq!(err.status_code(), StatusCode::CONFLICT);
+
+        let resp = login(app_state.clone(), jwt_config.clone(), web::Json(LoginRequest {
+            email: email.clone(),
+            password: "a-secret-password".into(),
+        }))
+            .await
+            .unwrap();
+        assert_eq!(resp.status(), StatusCode::OK);
+
+        let err = login(app_state, jwt_config, web::Json(LoginRequest {
+            email,
+            password: "wrong-password".into(),
+        }))
+            .await
+            .unwrap_err();
+        assert_eq!(err.status_code(), StatusCode::UNAUTHORIZED);
+    });
+}
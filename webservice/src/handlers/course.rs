@@ -1,25 +1,31 @@
 use crate::{
-    dbaccess::course::{
-        delete_course_db, get_course_detail_db, get_course_for_teacher_db, post_new_course_db, update_course_db
-    }, 
+    auth::AuthUser,
+    course_file::{encode_courses, parse_courses},
     errors::AppError, 
-    models::course::{CreateCourse, UpdateCourse}
+    etag::{etag, IfMatch},
+    models::course::{
+        CourseDetail, CourseDetailQuery, CourseFileFormat, CourseQuery, CourseSearchQuery,
+        CreateCourse, ExportCoursesQuery, ImportCoursesQuery, ImportReport, PatchCourse,
+        UpdateCourse,
+    },
 };
 
 use crate::state::AppState;
-use actix_web::{web, HttpResponse };
+use actix_web::{http::header, web, HttpRequest, HttpResponse };
+use futures_util::{stream, TryStreamExt};
 
 // 新增课程的 Post 
 pub async fn post_new_course(
     new_course: web::Json<CreateCourse>,
     app_state: web::Data<AppState>,
+    user: AuthUser,
 ) -> Result<HttpResponse, AppError> {
-    println!("Received new course");
-    post_new_course_db(
-        &app_state.db, 
+    tracing::debug!(teacher_id = new_course.teacher_id, "Received new course");
+    user.ensure_can_manage(new_course.teacher_id)?;
+    app_state.courses.post_new_course(
         new_course.try_into()?,
     ).await
-    .map(|course| HttpResponse::Ok().json(course))
+    .map(|course| HttpResponse::Ok().insert_header(etag(course.version)).json(course))
 }
 
 // 获取某位老师的所有课程 Get 请求
@@ -28,57 +34,205 @@ pub async fn get_courses_for_teacher (
     // 获取路径中的参数, 元组类型, 可以根据顺序获取多个
     // params: web::Path<(i32,)>,
     params: web::Path<i32>,
+    // 分页、排序与过滤参数
+    query: web::Query<CourseQuery>,
 ) -> Result<HttpResponse, AppError> { 
     let teacher_id = params.into_inner();
     // let teacher_id: i32 = i32::try_from(params.0).unwrap();
-    get_course_for_teacher_db(
-        &app_state.db, 
+    app_state.courses.get_course_for_teacher(
         teacher_id,
+        &query,
     ).await
     .map(|courses| HttpResponse::Ok().json(courses))
 }    
 
-// 获取具体某个老师的某个课程
+// 跨老师全文检索课程, 按相关度排序
+pub async fn search_courses(
+    app_state: web::Data<AppState>,
+    query: web::Query<CourseSearchQuery>,
+) -> Result<HttpResponse, AppError> {
+    app_state.courses.search_courses(&query)
+        .await
+        .map(|page| HttpResponse::Ok().json(page))
+}
+
+// 获取具体某个老师的某个课程, ?include=sections 时同时返回章节与课时
 pub async fn get_course_detail(
     app_state: web::Data<AppState> ,
     // params: web::Path<(usize, usize)>,
     params: web::Path<(i32, i32)>,
+    query: web::Query<CourseDetailQuery>,
 ) -> Result<HttpResponse, AppError> {
     // let teacher_id = i32::try_from(params.0).unwrap();
     // let course_id = i32::try_from(params.1).unwrap();
     let (teacher_id, course_id) = params.into_inner();
-    get_course_detail_db(
-        &app_state.db, 
+    let include_sections = query.include_sections()?;
+    let course = app_state.courses.get_course_detail(
         teacher_id, 
         course_id
-    ).await
-    .map(|course|  HttpResponse::Ok().json(course))
+    ).await?;
+    let sections = match include_sections {
+        true => Some(app_state.sections.get_sections(teacher_id, course_id).await?),
+        false => None,
+    };
+    Ok(HttpResponse::Ok()
+        .insert_header(etag(course.version))
+        .json(CourseDetail { course, sections }))
 }
 
+// 删除成功返回 204, 课程不存在时返回 404, If-Match 与当前版本不一致时返回 412
 pub async fn delete_course(
     app_state: web::Data<AppState>,
     params: web::Path<(i32, i32)>,
+    if_match: IfMatch,
+    user: AuthUser,
 ) -> Result<HttpResponse, AppError> {
     let (teacher_id, course_id) = params.into_inner();
-    delete_course_db(&app_state.db, teacher_id, course_id)
+    user.ensure_can_manage(teacher_id)?;
+    app_state.courses.delete_course(teacher_id, course_id, &if_match)
     .await
-    .map(|resp| HttpResponse::Ok().json(resp))
+    .map(|()| HttpResponse::NoContent().finish())
+}
+
+// 批量导入课程, 请求体为 CSV(text/csv) 或 NDJSON(application/x-ndjson)
+// 任何一行有错误时都不保存, 返回 422 及每一行的错误; ?dry_run=true 时只校验, 返回 200 及错误
+pub async fn import_courses(
+    app_state: web::Data<AppState>,
+    params: web::Path<i32>,
+    query: web::Query<ImportCoursesQuery>,
+    req: HttpRequest,
+    body: web::Bytes,
+    user: AuthUser,
+) -> Result<HttpResponse, AppError> {
+    let teacher_id = params.into_inner();
+    user.ensure_can_manage(teacher_id)?;
+    let format = req
+        .headers()
+        .get(header::CONTENT_TYPE)
+        .and_then(|value| value.to_str().ok())
+        .and_then(CourseFileFormat::from_content_type)
+        .ok_or_else(|| {
+            AppError::InvalidaValue("Content-Type must be text/csv or application/x-ndjson".into())
+        })?;
+    app_state.teachers.get_teacher_detail(teacher_id).await?;
+
+    let (courses, errors) = parse_courses(format, teacher_id, &body);
+    let valid = courses.len();
+    if query.dry_run {
+        return Ok(HttpResponse::Ok().json(ImportReport { dry_run: true, valid, imported: 0, errors }));
+    }
+    if !errors.is_empty() {
+        return Err(AppError::ValidationFailed(errors));
+    }
+    app_state.courses.import_courses(teacher_id, courses)
+        .await
+        .map(|imported| {
+            HttpResponse::Ok().json(ImportReport { dry_run: false, valid, imported, errors: Vec::new() })
+        })
+}
+
+// 导出老师的所有课程, 每次从存储中读取一页并写入响应, 不会一次加载全部课程
+// 导出的文件可以直接用于导入
+pub async fn export_courses(
+    app_state: web::Data<AppState>,
+    params: web::Path<i32>,
+    query: web::Query<ExportCoursesQuery>,
+) -> Result<HttpResponse, AppError> {
+    let teacher_id = params.into_inner();
+    let format = query.format;
+    app_state.teachers.get_teacher_detail(teacher_id).await?;
+
+    // 状态为下一页的游标, 第一页没有游标, 最后一页之后为 None
+    let pages = stream::try_unfold(Some(None), move |cursor: Option<Option<String>>| {
+        let app_state = app_state.clone();
+        async move {
+            let Some(cursor) = cursor else {
+                return Ok(None);
+            };
+            let first = cursor.is_none();
+            let query = CourseQuery {
+                limit: Some(CourseQuery::MAX_LIMIT),
+                cursor,
+                ..Default::default()
+            };
+            let page = app_state.courses.get_course_for_teacher(teacher_id, &query).await?;
+            let chunk = encode_courses(format, &page.items, first)?;
+            Ok::<_, AppError>(Some((chunk, page.next_cursor.map(Some))))
+        }
+    });
+
+    Ok(HttpResponse::Ok()
+        .content_type(format.content_type())
+        .insert_header((
+            header::CONTENT_DISPOSITION,
+            format!("attachment; filename=\"courses-{}.{}\"", teacher_id, format.extension()),
+        ))
+        .streaming(pages.map_err(actix_web::Error::from)))
+}
+
+// 回收站中的课程, 最近删除的在前
+pub async fn get_deleted_courses(
+    app_state: web::Data<AppState>,
+    params: web::Path<i32>,
+    user: AuthUser,
+) -> Result<HttpResponse, AppError> {
+    let teacher_id = params.into_inner();
+    user.ensure_can_manage(teacher_id)?;
+    app_state.courses.get_deleted_courses(teacher_id)
+        .await
+        .map(|courses| HttpResponse::Ok().json(courses))
 }
 
+pub async fn restore_course(
+    app_state: web::Data<AppState>,
+    params: web::Path<(i32, i32)>,
+    user: AuthUser,
+) -> Result<HttpResponse, AppError> {
+    let (teacher_id, course_id) = params.into_inner();
+    user.ensure_can_manage(teacher_id)?;
+    app_state.courses.restore_course(teacher_id, course_id)
+        .await
+        .map(|course| HttpResponse::Ok().insert_header(etag(course.version)).json(course))
+}
+
+// 需要携带 If-Match, 与当前版本不一致时返回 412
 pub async fn update_course_details(
     app_state: web::Data<AppState>,
     upate_course: web::Json<UpdateCourse>,
     params: web::Path<(i32, i32)>,
+    if_match: IfMatch,
+    user: AuthUser,
 ) -> Result<HttpResponse, AppError> {
     let (teacher_id, course_id) = params.into_inner();
-    update_course_db(
-        &app_state.db, 
+    user.ensure_can_manage(teacher_id)?;
+    app_state.courses.update_course(
         teacher_id, 
         course_id, 
-        upate_course.into()
+        &if_match,
+        UpdateCourse::try_from(upate_course)?.into()
+    )
+        .await
+        .map(|course| HttpResponse::Ok().insert_header(etag(course.version)).json(course))
+}
+
+// 部分修改, 不传入的字段保持不变, 传入 null 时清空, 也接受 application/merge-patch+json
+pub async fn patch_course_details(
+    app_state: web::Data<AppState>,
+    patch: web::Json<PatchCourse>,
+    params: web::Path<(i32, i32)>,
+    if_match: IfMatch,
+    user: AuthUser,
+) -> Result<HttpResponse, AppError> {
+    let (teacher_id, course_id) = params.into_inner();
+    user.ensure_can_manage(teacher_id)?;
+    app_state.courses.update_course(
+        teacher_id,
+        course_id,
+        &if_match,
+        patch.try_into()?
     )
         .await
-        .map(|course| HttpResponse::Ok().json(course))
+        .map(|course| HttpResponse::Ok().insert_header(etag(course.version)).json(course))
 }
 
 
@@ -86,117 +240,254 @@ pub async fn update_course_details(
 mod test {
 
     use super::*;
-    use actix_web::{http::StatusCode, ResponseError};
-    use dotenv::dotenv;
-    use std::env;
-    use sqlx::postgres::PgPoolOptions;
-    use std::sync::Mutex;
-
-    // 由于是异步函数，测试时候需要加上 #[actix_rt::test]
-    #[ignore]
+    use crate::models::{auth::Role, course::{CourseSort, Money, SortOrder}};
+    use crate::test_support::{bearer, db_test, test_app};
+    use actix_web::{http::StatusCode, test, ResponseError};
+    use chrono::Duration;
+
+    fn admin_user() -> AuthUser {
+        AuthUser { id: 0, role: Role::Admin }
+    }
+
+    fn teacher_user(teacher_id: i32) -> AuthUser {
+        AuthUser { id: teacher_id, role: Role::Teacher }
+    }
+
+    // 每个测试使用独立的临时数据库, fixtures 中老师 1 有课程 1、2、3
+    db_test!(delete_other_teacher_course_forbidden(app_state) fixtures("teachers", "courses") {
+        let app_state = web::Data::new(app_state);
+
+        let params: web::Path<(i32, i32)> = web::Path::from((1, 1));
+        let resp = delete_course(app_state, params, IfMatch::ANY, teacher_user(2)).await;
+        match resp {
+            Ok(_) => panic!("teacher 2 should not delete courses of teacher 1"),
+            Err(err) => assert_eq!(err.status_code(), StatusCode::FORBIDDEN),
+        }
+    });
+
+    // 通过完整的 App 发送请求, 经过路由、鉴权和中间件
+    db_test!(test_course_create(app_state) fixtures("teachers") {
+        let app = test::init_service(test_app(app_state)).await;
+        let req = test::TestRequest::post()
+            .uri("/courses/")
+            .insert_header(bearer(1, Role::Teacher))
+            .set_json(serde_json::json!({
+                "teacher_id": 1,
+                "name": "Test Course",
+                "description": "This is a course",
+                "language": "English",
+                "level": "Beginner",
+            }))
+            .to_request();
+        let resp = test::call_service(&app, req).await;
+        assert_eq!(resp.status(), StatusCode::OK);
+        let course: serde_json::Value = test::read_body_json(resp).await;
+        assert_eq!(course["teacher_id"], 1);
+        assert_eq!(course["name"], "Test Course");
+
+        // 未登录时不能创建课程
+        let req = test::TestRequest::post()
+            .uri("/courses/")
+            .set_json(serde_json::json!({ "teacher_id": 1, "name": "Anonymous" }))
+            .to_request();
+        let resp = test::call_service(&app, req).await;
+        assert_eq!(resp.status(), StatusCode::UNAUTHORIZED);
+    });
+
+    // 使用内存存储, 不需要数据库
     #[actix_rt::test]
-    async fn test_course_create() {
-        dotenv().ok();
-        let db_url = env::var("DATABASE_URL").expect("DATABASE_URL is not set");
-        let db_pool = PgPoolOptions::new().connect(&db_url).await.unwrap();
+    async fn course_crud_in_memory() {
+        let app = test::init_service(test_app(AppState::in_memory())).await;
+        let req = test::TestRequest::post()
+            .uri("/teachers/")
+            .set_json(serde_json::json!({
+                "name": "Memory teacher",
+                "picture_url": "http://unicorn.pro",
+                "profile": "A teacher without a database",
+            }))
+            .to_request();
+        let teacher: serde_json::Value = test::call_and_read_body_json(&app, req).await;
+        let teacher_id = teacher["id"].as_i64().unwrap() as i32;
+
+        let req = test::TestRequest::post()
+            .uri("/courses/")
+            .insert_header(bearer(teacher_id, Role::Teacher))
+            .set_json(serde_json::json!({ "teacher_id": teacher_id, "name": "Memory course" }))
+            .to_request();
+        let resp = test::call_service(&app, req).await;
+        assert_eq!(resp.status(), StatusCode::OK);
+
+        let req = test::TestRequest::get()
+            .uri(&format!("/courses/{}", teacher_id))
+            .to_request();
+        let page: serde_json::Value = test::call_and_read_body_json(&app, req).await;
+        assert_eq!(page["total"], 1);
+        assert_eq!(page["items"][0]["name"], "Memory course");
+
+        let req = test::TestRequest::get().uri("/health/ready").to_request();
+        let report: serde_json::Value = test::call_and_read_body_json(&app, req).await;
+        assert_eq!(report["status"], "ok");
+        assert_eq!(report["storage"], "memory");
+    }
+
+    db_test!(post_course_for_missing_teacher(app_state) fixtures("teachers") {
+        let app_state = web::Data::new(app_state);
         let course = web::Json(CreateCourse {
-            teacher_id: 1,
-            name: "Test Course".into(),
-            description: Some("This is a course".into()),
+            teacher_id: 100_000,
+            name: "Orphan course".into(),
+            description: None,
             format: None,
             structure: None,
             duration: None,
             price: None,
-            language: Some("English".into()),
-            level: Some("Beginner".into()),
-        });
-        
-        let app_state: web::Data::<AppState> = web::Data::new(AppState{
-            health_check_response: "".to_string(),
-            visit_count: Mutex::new(0),
-            db: db_pool,
+            language: None,
+            level: None,
+            capacity: None,
         });
 
-        let response = post_new_course(
-            course, app_state
-        )
+        let err = post_new_course(course, app_state, admin_user())
             .await
-            .unwrap();
-        assert_eq!(response.status(), StatusCode::OK);
-    }
+            .unwrap_err();
+        assert_eq!(err.status_code(), StatusCode::UNPROCESSABLE_ENTITY);
+        assert_eq!(err.code(), "constraint_violation");
+        assert!(err.to_string().contains("course_teacher_id_fkey"));
+    });
 
-    #[actix_rt::test]
-    async fn test_all_course_success() {
-        dotenv().ok();
-        let db_url = env::var("DATABASE_URL")
-            .expect("DATABASE_URL is not set in .env file");
-        let db_pool = PgPoolOptions::new().connect(&db_url).await.unwrap();
-        let app_state: web::Data<AppState> = web::Data::new(AppState {
-            health_check_response: "".to_string(),
-            visit_count: Mutex::new(0),
-            db: db_pool,
+    db_test!(post_invalid_course_lists_fields(app_state) {
+        let app_state = web::Data::new(app_state);
+        let course = web::Json(CreateCourse {
+            teacher_id: 1,
+            name: "  ".into(),
+            description: None,
+            format: Some("Podcast".into()),
+            structure: None,
+            duration: None,
+            price: Some(Money::new(-1, "CNY")),
+            language: None,
+            level: Some("Expert".into()),
+            capacity: None,
         });
+
+        let err = post_new_course(course, app_state, admin_user())
+            .await
+            .unwrap_err();
+        assert_eq!(err.status_code(), StatusCode::UNPROCESSABLE_ENTITY);
+        let AppError::ValidationFailed(details) = err else {
+            panic!("expected validation error, got {:?}", err);
+        };
+        let fields: Vec<&str> = details.iter().map(|d| d.field.as_str()).collect();
+        assert_eq!(fields, ["format", "level", "name", "price"]);
+    });
+
+    db_test!(test_all_course_success(app_state) fixtures("teachers", "courses") {
+        let app_state: web::Data<AppState> = web::Data::new(app_state);
         let teacher_id: web::Path<i32> = web::Path::from(1);
         let response = get_courses_for_teacher(
             app_state,
             teacher_id,
+            web::Query(CourseQuery::default()),
         ).await.unwrap();
         assert_eq!(response.status(), StatusCode::OK)
-    }
+    });
 
-    #[actix_rt::test]
-    async fn test_get_course_detail() {
-        dotenv().ok();
-        let db_url = env::var("DATABASE_URL")
-            .expect("DATABASE_RUL is not set in .env file");
-        let db_pool = PgPoolOptions::new().connect(&db_url).await.unwrap();
-        let app_state: web::Data<AppState> = web::Data::new(AppState {
-            health_check_response: "".to_string(),
-            visit_count: Mutex::new(0),
-            db: db_pool,
+    db_test!(get_courses_by_cursor(app_state) fixtures("teachers", "courses") {
+        let query = CourseQuery {
+            limit: Some(1),
+            sort: CourseSort::Price,
+            currency: Some("CNY".into()),
+            ..Default::default()
+        };
+        let first = app_state.courses.get_course_for_teacher(1, &query).await.unwrap();
+        assert_eq!(first.items.len(), 1);
+        assert_eq!(first.total, 3);
+
+        let query = CourseQuery {
+            cursor: first.next_cursor.clone(),
+            ..query
+        };
+        let second = app_state.courses.get_course_for_teacher(1, &query).await.unwrap();
+        assert_eq!(second.items.len(), 1);
+        assert_ne!(second.items[0].id, first.items[0].id);
+
+        // 游标不能换用其它排序字段或方向
+        let mismatched = [
+            CourseQuery { sort: CourseSort::Name, ..query.clone() },
+            CourseQuery { order: SortOrder::Desc, ..query.clone() },
+        ];
+        for query in mismatched {
+            match app_state.courses.get_course_for_teacher(1, &query).await {
+                Ok(_) => panic!("cursor from another sort should be rejected"),
+                Err(err) => assert_eq!(err.status_code(), StatusCode::UNPROCESSABLE_ENTITY),
+            }
+        }
+    });
+
+    db_test!(get_courses_invalid_limit(app_state) {
+        let query = CourseQuery {
+            limit: Some(0),
+            ..Default::default()
+        };
+        let resp = app_state.courses.get_course_for_teacher(1, &query).await;
+        match resp {
+            Ok(_) => panic!("limit 0 should be rejected"),
+            Err(err) => assert_eq!(err.status_code(), StatusCode::UNPROCESSABLE_ENTITY),
+        }
+    });
+
+    db_test!(search_courses_success(app_state) fixtures("teachers", "courses") {
+        let course = app_state.courses.post_new_course(CreateCourse {
+            teacher_id: 2,
+            name: "Searchable compilers".into(),
+            description: Some("Writing a tokenizer and parser from scratch".into()),
+            format: None,
+            structure: None,
+            duration: None,
+            price: None,
+            language: Some("English".into()),
+            level: Some("Beginner".into()),
+            capacity: None,
+        })
+            .await
+            .unwrap();
+        let app_state = web::Data::new(app_state);
+
+        let query = web::Query(CourseSearchQuery {
+            q: "tokenizer".into(),
+            level: Some("Beginner".into()),
+            ..Default::default()
         });
+        let page = app_state.courses.search_courses(&query).await.unwrap();
+        assert_eq!(page.items.len(), 1);
+        let hit = &page.items[0];
+        assert_eq!(hit.course.id, course.id);
+        assert!(hit.snippet.contains("<mark>tokenizer</mark>"));
+
+        let resp = search_courses(app_state, query).await.unwrap();
+        assert_eq!(resp.status(), StatusCode::OK);
+    });
+
+    db_test!(test_get_course_detail(app_state) fixtures("teachers", "courses") {
+        let app_state: web::Data<AppState> = web::Data::new(app_state);
         let teacher_id: web::Path<(i32,i32)> = web::Path::from((1,1));
         let response = get_course_detail(
-            app_state, teacher_id
+            app_state, teacher_id, web::Query(CourseDetailQuery::default())
         ).await.unwrap();
         assert_eq!(response.status(), StatusCode::OK)
-    }
+    });
 
-    #[actix_rt::test]
-    async fn get_one_course_failure() {
-        dotenv().ok();
-        let db_url = env::var("DATABASE_URL")
-            .expect("DATABASE_URL is not set in .env file");
-        let db_pool = PgPoolOptions::new().connect(&db_url)
-            .await
-            .unwrap();
-        let app_state = web::Data::new(AppState {
-            health_check_response: "".to_string(),
-            visit_count: Mutex::new(0),
-            db: db_pool,
-        });
+    db_test!(get_one_course_failure(app_state) fixtures("teachers", "courses") {
+        let app_state = web::Data::new(app_state);
 
         let params: web::Path<(i32, i32)> = web::Path::from((1,100));
-        let resp = get_course_detail(app_state, params).await;
+        let resp = get_course_detail(app_state, params, web::Query(CourseDetailQuery::default())).await;
         match resp {
-            Ok(_) => println!("Something wrong..."),
+            Ok(_) => panic!("course 100 should not exist"),
             Err(err) => assert_eq!(err.status_code(), StatusCode::NOT_FOUND),
         }
-    }
+    });
 
-    #[actix_rt::test]
-    async fn update_course_success() {
-        dotenv().ok();
-        let db_url = env::var("DATABASE_URL")
-            .expect("DATABASE_URL not set in .env file");
-        let db_pool = PgPoolOptions::new().connect(&db_url)
-            .await.unwrap();
-
-        let app_state = web::Data::new(AppState{ 
-            health_check_response: "".to_string(),
-            visit_count: Mutex::new(0),
-            db: db_pool,
-        });
+    db_test!(update_course_success(app_state) fixtures("teachers", "courses") {
+        let app_state = web::Data::new(app_state);
 
         let update_course = UpdateCourse {
             name: Some("Course name changed".into()),
@@ -207,6 +498,7 @@ mod test {
             duration: None,
             language: Some("Chinese".into()),
             structure: None,
+            capacity: None,
         };
 
         let params: web::Path<(i32, i32)> = web::Path::from((1,2));
@@ -215,54 +507,341 @@ mod test {
             app_state,
             update_params,
             params,
+            IfMatch::version(1),
+            teacher_user(1),
         )
             .await
             .unwrap();
         assert_eq!(resp.status(), StatusCode::OK);
-    }
+    });
 
 
-    #[ignore]
-    #[actix_rt::test]
-    async fn delete_course_success() {
-        dotenv().ok();
-        let db_url = env::var("DATABASE_URL")
-            .expect("DATABASE_URL is not set in .env file");
-        let db_pool = PgPoolOptions::new().connect(&db_url)
-            .await.unwrap();
-        let app_state = web::Data::new(AppState {
-            health_check_response: "".to_string(),
-            visit_count: Mutex::new(0),
-            db: db_pool,
-        });
+    db_test!(delete_course_success(app_state) fixtures("teachers", "courses") {
+        let app = test::init_service(test_app(app_state)).await;
+        let req = test::TestRequest::delete()
+            .uri("/courses/1/3")
+            .insert_header(bearer(1, Role::Teacher))
+            .insert_header(("If-Match", "\"1\""))
+            .to_request();
+        let resp = test::call_service(&app, req).await;
+        assert_eq!(resp.status(), StatusCode::NO_CONTENT);
+        assert!(test::read_body(resp).await.is_empty());
 
-        let params: web::Path<(i32, i32)> = web::Path::from((1, 3));
-        let resp = delete_course(app_state, params)
-            .await
-            .unwrap();
+        let req = test::TestRequest::get().uri("/courses/1/3").to_request();
+        let resp = test::call_service(&app, req).await;
+        assert_eq!(resp.status(), StatusCode::NOT_FOUND);
+
+        // 已经删除的课程再次删除时返回 404
+        let req = test::TestRequest::delete()
+            .uri("/courses/1/3")
+            .insert_header(bearer(1, Role::Teacher))
+            .insert_header(("If-Match", "\"1\""))
+            .to_request();
+        let resp = test::call_service(&app, req).await;
+        assert_eq!(resp.status(), StatusCode::NOT_FOUND);
+        let body: serde_json::Value = test::read_body_json(resp).await;
+        assert_eq!(body["code"], "course_not_found");
+    });
+
+    // 有错误的行时不保存任何课程, dry_run 只返回每一行的错误
+    db_test!(import_courses_validates_every_row(app_state) fixtures("teachers") {
+        let app = test::init_service(test_app(app_state)).await;
+        let csv = "name,description,price,currency,level\n\
+                   Rust basics,Ownership and borrowing,9900,CNY,Beginner\n\
+                   Broken course,,-5,CNY,Expert\n\
+                   Async Rust,,,,Advanced\n";
+        let import = |uri: &str, body: &'static str| {
+            test::TestRequest::post()
+                .uri(uri)
+                .insert_header(bearer(2, Role::Teacher))
+                .insert_header(("Content-Type", "text/csv"))
+                .set_payload(body)
+                .to_request()
+        };
+
+        let report: serde_json::Value =
+            test::call_and_read_body_json(&app, import("/courses/2/import?dry_run=true", csv)).await;
+        assert_eq!(report["valid"], 2);
+        assert_eq!(report["imported"], 0);
+        let fields: Vec<&str> = report["errors"]
+            .as_array()
+            .unwrap()
+            .iter()
+            .map(|e| e["field"].as_str().unwrap())
+            .collect();
+        assert_eq!(fields, ["rows[2].level", "rows[2].price"]);
+
+        let resp = test::call_service(&app, import("/courses/2/import", csv)).await;
+        assert_eq!(resp.status(), StatusCode::UNPROCESSABLE_ENTITY);
+        let req = test::TestRequest::get().uri("/courses/2").to_request();
+        let page: serde_json::Value = test::call_and_read_body_json(&app, req).await;
+        assert_eq!(page["total"], 0);
+
+        let valid = "name,description,price,currency,level\nRust basics,,9900,CNY,Beginner\nAsync Rust,,,,Advanced\n";
+        let report: serde_json::Value =
+            test::call_and_read_body_json(&app, import("/courses/2/import", valid)).await;
+        assert_eq!(report["imported"], 2);
+        let req = test::TestRequest::get().uri("/courses/2").to_request();
+        let page: serde_json::Value = test::call_and_read_body_json(&app, req).await;
+        assert_eq!(page["total"], 2);
+
+        let req = test::TestRequest::post()
+            .uri("/courses/1/import")
+            .insert_header(bearer(2, Role::Teacher))
+            .insert_header(("Content-Type", "text/csv"))
+            .set_payload(valid)
+            .to_request();
+        assert_eq!(test::call_service(&app, req).await.status(), StatusCode::FORBIDDEN);
+    });
+
+    // 行数上限内的文件在一条语句中全部导入
+    db_test!(import_courses_up_to_the_row_limit(app_state) fixtures("teachers") {
+        let app = test::init_service(test_app(app_state)).await;
+        let mut csv = String::from("name,description,price,currency,level\n");
+        for i in 0..crate::course_file::MAX_IMPORT_ROWS {
+            csv.push_str(&format!("Course {},,9900,CNY,Beginner\n", i));
+        }
+        let req = test::TestRequest::post()
+            .uri("/courses/2/import")
+            .insert_header(bearer(2, Role::Teacher))
+            .insert_header(("Content-Type", "text/csv"))
+            .set_payload(csv)
+            .to_request();
+        let report: serde_json::Value = test::call_and_read_body_json(&app, req).await;
+        assert_eq!(report["imported"], crate::course_file::MAX_IMPORT_ROWS);
+        let req = test::TestRequest::get().uri("/courses/2").to_request();
+        let page: serde_json::Value = test::call_and_read_body_json(&app, req).await;
+        assert_eq!(page["total"], crate::course_file::MAX_IMPORT_ROWS);
+    });
+
+    // 导出的文件可以原样导入到另一个老师名下
+    db_test!(export_courses_round_trip(app_state) fixtures("teachers", "courses") {
+        let app = test::init_service(test_app(app_state)).await;
+        let req = test::TestRequest::get().uri("/courses/1/export?format=ndjson").to_request();
+        let resp = test::call_service(&app, req).await;
         assert_eq!(resp.status(), StatusCode::OK);
-    }
+        assert_eq!(resp.headers().get("content-type").unwrap(), "application/x-ndjson");
+        let ndjson = test::read_body(resp).await;
+        let names: Vec<String> = ndjson
+            .split(|b| *b == b'\n')
+            .filter(|line| !line.is_empty())
+            .map(|line| serde_json::from_slice::<serde_json::Value>(line).unwrap()["name"].as_str().unwrap().to_string())
+            .collect();
+        assert_eq!(names, ["First course", "Second course", "Third course"]);
 
+        let req = test::TestRequest::get().uri("/courses/1/export").to_request();
+        let csv = test::call_and_read_body(&app, req).await;
+        assert!(csv.starts_with(b"teacher_id,id,name,time,description"));
+        assert_eq!(csv.split(|b| *b == b'\n').filter(|line| !line.is_empty()).count(), 4);
 
-    #[actix_rt::test]
-    async fn test_course_failure() {
-        dotenv().ok();
-        let db_url = env::var("DATABASE_URL")
-            .expect("DATABASE_URL is not set in .env file");
-        let db_pool = PgPoolOptions::new().connect(&db_url)
-            .await
-            .unwrap();
-        let app_state = web::Data::new(AppState {
-            health_check_response: "".to_string(),
-            visit_count: Mutex::new(0),
-            db: db_pool,
-        });
+        let req = test::TestRequest::post()
+            .uri("/courses/2/import")
+            .insert_header(bearer(2, Role::Teacher))
+            .insert_header(("Content-Type", "text/csv; charset=utf-8"))
+            .set_payload(csv)
+            .to_request();
+        let report: serde_json::Value = test::call_and_read_body_json(&app, req).await;
+        assert_eq!(report["imported"], 3);
+
+        let req = test::TestRequest::get().uri("/courses/100/export").to_request();
+        assert_eq!(test::call_service(&app, req).await.status(), StatusCode::NOT_FOUND);
+    });
+
+    // PATCH 区分不传入、null 和值, PUT 不会把 NULL 改为空字符串或 0
+    db_test!(patch_course_clears_fields(app_state) fixtures("teachers", "courses") {
+        let app = test::init_service(test_app(app_state)).await;
+        let req = test::TestRequest::patch()
+            .uri("/courses/1/1")
+            .insert_header(bearer(1, Role::Teacher))
+            .insert_header(("If-Match", "*"))
+            .insert_header(("Content-Type", "application/merge-patch+json"))
+            .set_payload(r#"{ "price": null, "structure": null, "level": "Advanced" }"#)
+            .to_request();
+        let course: serde_json::Value = test::call_and_read_body_json(&app, req).await;
+        assert_eq!(course["name"], "First course");
+        assert_eq!(course["description"], "An introduction to Rust");
+        assert_eq!(course["price"], serde_json::Value::Null);
+        assert_eq!(course["structure"], serde_json::Value::Null);
+        assert_eq!(course["level"], "Advanced");
+
+        let req = test::TestRequest::put()
+            .uri("/courses/1/1")
+            .insert_header(bearer(1, Role::Teacher))
+            .insert_header(("If-Match", "*"))
+            .set_json(serde_json::json!({ "name": "Renamed course" }))
+            .to_request();
+        let course: serde_json::Value = test::call_and_read_body_json(&app, req).await;
+        assert_eq!(course["name"], "Renamed course");
+        assert_eq!(course["price"], serde_json::Value::Null);
+        assert_eq!(course["structure"], serde_json::Value::Null);
+
+        let req = test::TestRequest::patch()
+            .uri("/courses/1/1")
+            .insert_header(bearer(1, Role::Teacher))
+            .insert_header(("If-Match", "*"))
+            .set_json(serde_json::json!({ "name": null }))
+            .to_request();
+        let resp = test::call_service(&app, req).await;
+        assert_eq!(resp.status(), StatusCode::UNPROCESSABLE_ENTITY);
+        let body: serde_json::Value = test::read_body_json(resp).await;
+        assert_eq!(body["details"][0]["field"], "name");
+    });
+
+    // 价格以最小货币单位与货币代码保存, 不支持的货币返回 422
+    db_test!(course_price_keeps_currency(app_state) fixtures("teachers", "courses") {
+        let app = test::init_service(test_app(app_state)).await;
+        let req = test::TestRequest::get().uri("/courses/1/1").to_request();
+        let course: serde_json::Value = test::call_and_read_body_json(&app, req).await;
+        assert_eq!(course["price"], serde_json::json!({ "amount": 100, "currency": "CNY" }));
+
+        let patch = |price: serde_json::Value| {
+            test::TestRequest::patch()
+                .uri("/courses/1/1")
+                .insert_header(bearer(1, Role::Teacher))
+                .insert_header(("If-Match", "*"))
+                .set_json(serde_json::json!({ "price": price }))
+                .to_request()
+        };
+        let course: serde_json::Value =
+            test::call_and_read_body_json(&app, patch(serde_json::json!({ "amount": 1500, "currency": "JPY" }))).await;
+        assert_eq!(course["price"], serde_json::json!({ "amount": 1500, "currency": "JPY" }));
+
+        let resp = test::call_service(&app, patch(serde_json::json!({ "amount": 1500, "currency": "BTC" }))).await;
+        assert_eq!(resp.status(), StatusCode::UNPROCESSABLE_ENTITY);
+        let body: serde_json::Value = test::read_body_json(resp).await;
+        assert_eq!(body["details"][0]["field"], "price");
+
+        // 按价格排序时只比较同一货币的金额, 1500 日元不会排在 300 元之前
+        let req = test::TestRequest::get().uri("/courses/1?sort=price&order=desc&currency=CNY").to_request();
+        let page: serde_json::Value = test::call_and_read_body_json(&app, req).await;
+        assert_eq!(page["total"], 2);
+        assert_eq!(page["items"][0]["price"], serde_json::json!({ "amount": 300, "currency": "CNY" }));
+        let req = test::TestRequest::get().uri("/courses/1?min_price=1000&currency=JPY").to_request();
+        let page: serde_json::Value = test::call_and_read_body_json(&app, req).await;
+        assert_eq!(page["total"], 1);
+
+        for uri in ["/courses/1?sort=price", "/courses/1?max_price=100", "/courses/1?sort=price&currency=BTC"] {
+            let resp = test::call_service(&app, test::TestRequest::get().uri(uri).to_request()).await;
+            assert!(resp.status().is_client_error(), "{}", uri);
+            let body: serde_json::Value = test::read_body_json(resp).await;
+            assert_eq!(body["details"][0]["field"], "currency", "{}", uri);
+        }
+    });
+
+    // 修改时必须携带 If-Match, 版本号过期时返回 412, 成功后返回新的 ETag
+    db_test!(update_course_requires_current_etag(app_state) fixtures("teachers", "courses") {
+        let app = test::init_service(test_app(app_state)).await;
+        let req = test::TestRequest::get().uri("/courses/1/1").to_request();
+        let resp = test::call_service(&app, req).await;
+        assert_eq!(resp.headers().get("etag").unwrap(), "\"1\"");
+
+        let put = |if_match: Option<&str>| {
+            let mut req = test::TestRequest::put()
+                .uri("/courses/1/1")
+                .insert_header(bearer(1, Role::Teacher))
+                .set_json(serde_json::json!({ "name": "Renamed course" }));
+            if let Some(if_match) = if_match {
+                req = req.insert_header(("If-Match", if_match));
+            }
+            req.to_request()
+        };
+
+        let resp = test::call_service(&app, put(None)).await;
+        assert_eq!(resp.status(), StatusCode::PRECONDITION_REQUIRED);
+
+        let resp = test::call_service(&app, put(Some("\"1\""))).await;
+        assert_eq!(resp.status(), StatusCode::OK);
+        assert_eq!(resp.headers().get("etag").unwrap(), "\"2\"");
+
+        // 另一个客户端仍然持有旧的 ETag
+        let resp = test::call_service(&app, put(Some("\"1\""))).await;
+        assert_eq!(resp.status(), StatusCode::PRECONDITION_FAILED);
+        let body: serde_json::Value = test::read_body_json(resp).await;
+        assert_eq!(body["code"], "precondition_failed");
+
+        // 多个 ETag 中任意一个是当前版本即可
+        let resp = test::call_service(&app, put(Some("\"1\", \"2\""))).await;
+        assert_eq!(resp.status(), StatusCode::OK);
+        assert_eq!(resp.headers().get("etag").unwrap(), "\"3\"");
+
+        let req = test::TestRequest::delete()
+            .uri("/courses/1/1")
+            .insert_header(bearer(1, Role::Teacher))
+            .insert_header(("If-Match", "W/\"3\""))
+            .to_request();
+        assert_eq!(test::call_service(&app, req).await.status(), StatusCode::PRECONDITION_FAILED);
+    });
+
+
+    // 删除的课程移入回收站, 恢复后重新出现在列表中
+    db_test!(deleted_course_restored_from_trash(app_state) fixtures("teachers", "courses") {
+        let app = test::init_service(test_app(app_state)).await;
+        let req = test::TestRequest::delete()
+            .uri("/courses/1/2")
+            .insert_header(bearer(1, Role::Teacher))
+            .insert_header(("If-Match", "*"))
+            .to_request();
+        assert_eq!(test::call_service(&app, req).await.status(), StatusCode::NO_CONTENT);
+
+        let req = test::TestRequest::get().uri("/courses/1").to_request();
+        let page: serde_json::Value = test::call_and_read_body_json(&app, req).await;
+        assert_eq!(page["total"], 2);
+
+        // 回收站只有老师本人和管理员可以查看
+        let req = test::TestRequest::get()
+            .uri("/courses/1/trash")
+            .insert_header(bearer(2, Role::Teacher))
+            .to_request();
+        assert_eq!(test::call_service(&app, req).await.status(), StatusCode::FORBIDDEN);
+        let req = test::TestRequest::get()
+            .uri("/courses/1/trash")
+            .insert_header(bearer(1, Role::Teacher))
+            .to_request();
+        let trash: serde_json::Value = test::call_and_read_body_json(&app, req).await;
+        assert_eq!(trash.as_array().unwrap().len(), 1);
+        assert_eq!(trash[0]["id"], 2);
+        assert!(trash[0]["deleted_at"].is_string());
+
+        let req = test::TestRequest::post()
+            .uri("/courses/1/2/restore")
+            .insert_header(bearer(1, Role::Teacher))
+            .to_request();
+        let course: serde_json::Value = test::call_and_read_body_json(&app, req).await;
+        assert_eq!(course["id"], 2);
+        assert!(course.get("deleted_at").is_none());
+
+        // 不在回收站中的课程不能恢复
+        let req = test::TestRequest::post()
+            .uri("/courses/1/2/restore")
+            .insert_header(bearer(1, Role::Teacher))
+            .to_request();
+        assert_eq!(test::call_service(&app, req).await.status(), StatusCode::NOT_FOUND);
+
+        let req = test::TestRequest::get().uri("/courses/1/2").to_request();
+        assert_eq!(test::call_service(&app, req).await.status(), StatusCode::OK);
+    });
+
+    db_test!(purge_deleted_courses_after_retention(app_state) fixtures("teachers", "courses") {
+        app_state.courses.delete_course(1, 1, &IfMatch::ANY).await.unwrap();
+        let purged = app_state.courses.purge_deleted_courses(Duration::days(30)).await.unwrap();
+        assert_eq!(purged, 0);
+
+        // 负的保留时间使刚删除的课程也已过期
+        let purged = app_state.courses.purge_deleted_courses(Duration::seconds(-1)).await.unwrap();
+        assert_eq!(purged, 1);
+        assert!(app_state.courses.get_deleted_courses(1).await.unwrap().is_empty());
+        let err = app_state.courses.restore_course(1, 1).await.unwrap_err();
+        assert_eq!(err.status_code(), StatusCode::NOT_FOUND);
+    });
+
+    db_test!(test_course_failure(app_state) fixtures("teachers", "courses") {
+        let app_state = web::Data::new(app_state);
 
         let params: web::Path<(i32, i32)> = web::Path::from((1,1001));
-        let resp = delete_course(app_state, params).await;
+        let resp = delete_course(app_state, params, IfMatch::ANY, teacher_user(1)).await;
         match resp {
-            Ok(_) => println!("Something wrong...."),
+            Ok(_) => panic!("deleting a missing course should fail"),
             Err(err) => assert_eq!(err.status_code(), StatusCode::NOT_FOUND),
         }
-    }
+    });
 }
@@ -0,0 +1,5 @@
+-- 依赖 teachers.sql, 课程 id 依次为 1、2、3
+insert into course (teacher_id, name, description, format, price, currency, language, level)
+values (1, 'First course', 'An introduction to Rust', 'Video', 100, 'CNY', 'English', 'Beginner'),
+       (1, 'Second course', 'Web services with actix-web', 'Live', 200, 'CNY', 'English', 'Intermediate'),
+       (1, 'Third course', 'Async Rust in depth', 'Text', 300, 'CNY', 'Chinese', 'Advanced');
@@ -0,0 +1,9 @@
+-- 依赖 courses.sql, 课程 1 的章节 id 依次为 1、2, 课时 id 依次为 1、2、3
+insert into section (course_id, title, position)
+values (1, 'Getting started', 1),
+       (1, 'Ownership', 2);
+
+insert into lesson (section_id, title, content, duration_minutes, position)
+values (1, 'Installing Rust', 'rustup and cargo', 10, 1),
+       (2, 'Moves', null, 20, 1),
+       (2, 'Borrowing', null, 25, 2);
@@ -0,0 +1,5 @@
+-- 学生 id 依次为 1、2、3, 密码哈希为占位值, 登录的测试通过注册接口创建学生
+insert into student (name, email, password_hash)
+values ('Alice', 'alice@example.com', 'not-a-hash'),
+       ('Bob', 'bob@example.com', 'not-a-hash'),
+       ('Carol', 'carol@example.com', 'not-a-hash');
@@ -0,0 +1,5 @@
+-- 老师 1 名下有课程, 老师 2 没有课程
+-- 不指定 id, 在空数据库中依次生成 1、2, Postgres 与 SQLite 都可以执行
+insert into teacher (name, picture_url, profile, email)
+values ('Dave', 'http://unicorn.pro/dave.png', 'Dave is an experienced Rust teacher', 'dave@unicorn.pro'),
+       ('Tom', 'http://unicorn.pro/tom.png', 'Tom teaches machine learning', 'tom@unicorn.pro');
@@ -1,3 +1,6 @@
+pub mod auth;
 pub mod general;
 pub mod course;
+pub mod section;
+pub mod student;
 pub mod teacher;
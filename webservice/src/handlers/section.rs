@@ -0,0 +1,298 @@
+use actix_web::{web, HttpResponse};
+
+use crate::{
+    auth::AuthUser,
+    errors::AppError,
+    models::section::{CreateLesson, CreateSection, Reorder, UpdateLesson, UpdateSection},
+    state::AppState,
+};
+
+// 按顺序获取课程的所有章节及其课时
+pub async fn get_sections(
+    app_state: web::Data<AppState>,
+    params: web::Path<(i32, i32)>,
+) -> Result<HttpResponse, AppError> {
+    let (teacher_id, course_id) = params.into_inner();
+    app_state.sections.get_sections(teacher_id, course_id)
+        .await
+        .map(|sections| HttpResponse::Ok().json(sections))
+}
+
+// 新增章节, 指定 position 时插入到该位置, 否则添加到最后
+pub async fn post_new_section(
+    app_state: web::Data<AppState>,
+    new_section: web::Json<CreateSection>,
+    params: web::Path<(i32, i32)>,
+    user: AuthUser,
+) -> Result<HttpResponse, AppError> {
+    let (teacher_id, course_id) = params.into_inner();
+    user.ensure_can_manage(teacher_id)?;
+    app_state.sections.post_new_section(teacher_id, course_id, new_section.try_into()?)
+        .await
+        .map(|section| HttpResponse::Ok().json(section))
+}
+
+pub async fn update_section(
+    app_state: web::Data<AppState>,
+    update: web::Json<UpdateSection>,
+    params: web::Path<(i32, i32, i32)>,
+    user: AuthUser,
+) -> Result<HttpResponse, AppError> {
+    let (teacher_id, course_id, section_id) = params.into_inner();
+    user.ensure_can_manage(teacher_id)?;
+    app_state.sections.update_section(teacher_id, course_id, section_id, update.try_into()?)
+        .await
+        .map(|section| HttpResponse::Ok().json(section))
+}
+
+// 同时删除章节中的课时, 成功返回 204
+pub async fn delete_section(
+    app_state: web::Data<AppState>,
+    params: web::Path<(i32, i32, i32)>,
+    user: AuthUser,
+) -> Result<HttpResponse, AppError> {
+    let (teacher_id, course_id, section_id) = params.into_inner();
+    user.ensure_can_manage(teacher_id)?;
+    app_state.sections.delete_section(teacher_id, course_id, section_id)
+        .await
+        .map(|()| HttpResponse::NoContent().finish())
+}
+
+// 请求体为 { "ids": [3, 1, 2] }, 必须包含课程的所有章节, 返回调整后的章节
+pub async fn reorder_sections(
+    app_state: web::Data<AppState>,
+    order: web::Json<Reorder>,
+    params: web::Path<(i32, i32)>,
+    user: AuthUser,
+) -> Result<HttpResponse, AppError> {
+    let (teacher_id, course_id) = params.into_inner();
+    user.ensure_can_manage(teacher_id)?;
+    app_state.sections.reorder_sections(teacher_id, course_id, order.try_into()?)
+        .await
+        .map(|sections| HttpResponse::Ok().json(sections))
+}
+
+pub async fn post_new_lesson(
+    app_state: web::Data<AppState>,
+    new_lesson: web::Json<CreateLesson>,
+    params: web::Path<(i32, i32, i32)>,
+    user: AuthUser,
+) -> Result<HttpResponse, AppError> {
+    let (teacher_id, course_id, section_id) = params.into_inner();
+    user.ensure_can_manage(teacher_id)?;
+    app_state.sections.post_new_lesson(teacher_id, course_id, section_id, new_lesson.try_into()?)
+        .await
+        .map(|lesson| HttpResponse::Ok().json(lesson))
+}
+
+// 不传入的字段保持不变
+pub async fn update_lesson(
+    app_state: web::Data<AppState>,
+    update: web::Json<UpdateLesson>,
+    params: web::Path<(i32, i32, i32, i32)>,
+    user: AuthUser,
+) -> Result<HttpResponse, AppError> {
+    let (teacher_id, course_id, section_id, lesson_id) = params.into_inner();
+    user.ensure_can_manage(teacher_id)?;
+    app_state.sections.update_lesson(teacher_id, course_id, section_id, lesson_id, update.try_into()?)
+        .await
+        .map(|lesson| HttpResponse::Ok().json(lesson))
+}
+
+pub async fn delete_lesson(
+    app_state: web::Data<AppState>,
+    params: web::Path<(i32, i32, i32, i32)>,
+    user: AuthUser,
+) -> Result<HttpResponse, AppError> {
+    let (teacher_id, course_id, section_id, lesson_id) = params.into_inner();
+    user.ensure_can_manage(teacher_id)?;
+    app_state.sections.delete_lesson(teacher_id, course_id, section_id, lesson_id)
+        .await
+        .map(|()| HttpResponse::NoContent().finish())
+}
+
+// 与 reorder_sections 相同, 必须包含章节的所有课时
+pub async fn reorder_lessons(
+    app_state: web::Data<AppState>,
+    order: web::Json<Reorder>,
+    params: web::Path<(i32, i32, i32)>,
+    user: AuthUser,
+) -> Result<HttpResponse, AppError> {
+    let (teacher_id, course_id, section_id) = params.into_inner();
+    user.ensure_can_manage(teacher_id)?;
+    app_state.sections.reorder_lessons(teacher_id, course_id, section_id, order.try_into()?)
+        .await
+        .map(|lessons| HttpResponse::Ok().json(lessons))
+}
+
+#[cfg(test)]
+mod tests {
+    use crate::models::auth::Role;
+    use crate::test_support::{bearer, db_test, test_app};
+    use actix_web::{http::StatusCode, test};
+    use serde_json::{json, Value};
+
+    fn ids(items: &Value) -> Vec<i64> {
+        items.as_array().unwrap().iter().map(|i| i["id"].as_i64().unwrap()).collect()
+    }
+
+    fn positions(items: &Value) -> Vec<i64> {
+        items.as_array().unwrap().iter().map(|i| i["position"].as_i64().unwrap()).collect()
+    }
+
+    // 新增、删除与调整顺序后 position 保持从 1 开始连续
+    db_test!(sections_keep_positions(app_state) fixtures("teachers", "courses") {
+        let app = test::init_service(test_app(app_state)).await;
+        let post = |body: Value| {
+            test::TestRequest::post()
+                .uri("/courses/1/1/sections")
+                .insert_header(bearer(1, Role::Teacher))
+                .set_json(body)
+                .to_request()
+        };
+        let a: Value = test::call_and_read_body_json(&app, post(json!({ "title": "Basics" }))).await;
+        let b: Value = test::call_and_read_body_json(&app, post(json!({ "title": "Traits" }))).await;
+        let c: Value = test::call_and_read_body_json(&app, post(json!({ "title": "Intro", "position": 1 }))).await;
+        assert_eq!(c["position"], 1);
+        let (a, b, c) = (a["id"].as_i64().unwrap(), b["id"].as_i64().unwrap(), c["id"].as_i64().unwrap());
+
+        let req = test::TestRequest::get().uri("/courses/1/1/sections").to_request();
+        let sections: Value = test::call_and_read_body_json(&app, req).await;
+        assert_eq!(ids(&sections), [c, a, b]);
+        assert_eq!(positions(&sections), [1, 2, 3]);
+
+        let reorder = |order: Value| {
+            test::TestRequest::put()
+                .uri("/courses/1/1/sections/order")
+                .insert_header(bearer(1, Role::Teacher))
+                .set_json(json!({ "ids": order }))
+                .to_request()
+        };
+        let sections: Value = test::call_and_read_body_json(&app, reorder(json!([b, c, a]))).await;
+        assert_eq!(ids(&sections), [b, c, a]);
+        assert_eq!(positions(&sections), [1, 2, 3]);
+        for order in [json!([b, c]), json!([b, c, a, a]), json!([b, c, 999])] {
+            let resp = test::call_service(&app, reorder(order)).await;
+            assert_eq!(resp.status(), StatusCode::UNPROCESSABLE_ENTITY);
+            let body: Value = test::read_body_json(resp).await;
+            assert_eq!(body["details"][0]["field"], "ids");
+        }
+
+        let req = test::TestRequest::delete()
+            .uri(&format!("/courses/1/1/sections/{}", b))
+            .insert_header(bearer(1, Role::Teacher))
+            .to_request();
+        assert_eq!(test::call_service(&app, req).await.status(), StatusCode::NO_CONTENT);
+        let req = test::TestRequest::get().uri("/courses/1/1/sections").to_request();
+        let sections: Value = test::call_and_read_body_json(&app, req).await;
+        assert_eq!(ids(&sections), [c, a]);
+        assert_eq!(positions(&sections), [1, 2]);
+
+        let req = test::TestRequest::put()
+            .uri(&format!("/courses/1/1/sections/{}", a))
+            .insert_header(bearer(1, Role::Teacher))
+            .set_json(json!({ "title": "Ownership basics" }))
+            .to_request();
+        let section: Value = test::call_and_read_body_json(&app, req).await;
+        assert_eq!(section["title"], "Ownership basics");
+        assert_eq!(section["position"], 2);
+    });
+
+    db_test!(section_errors(app_state) fixtures("teachers", "courses", "sections") {
+        let app = test::init_service(test_app(app_state)).await;
+        let put = |uri: &str, teacher_id: i32| {
+            test::TestRequest::put()
+                .uri(uri)
+                .insert_header(bearer(teacher_id, Role::Teacher))
+                .set_json(json!({ "title": "Renamed" }))
+                .to_request()
+        };
+        let cases = [
+            ("/courses/1/1/sections/1", 2, StatusCode::FORBIDDEN, "forbidden"),
+            ("/courses/2/1/sections/1", 2, StatusCode::NOT_FOUND, "course_not_found"),
+            ("/courses/1/100/sections/1", 1, StatusCode::NOT_FOUND, "course_not_found"),
+            ("/courses/1/2/sections/1", 1, StatusCode::NOT_FOUND, "section_not_found"),
+            ("/courses/1/1/sections/1/lessons/2", 1, StatusCode::NOT_FOUND, "lesson_not_found"),
+        ];
+        for (uri, teacher_id, status, code) in cases {
+            let resp = test::call_service(&app, put(uri, teacher_id)).await;
+            assert_eq!(resp.status(), status, "{}", uri);
+            let body: Value = test::read_body_json(resp).await;
+            assert_eq!(body["code"], code, "{}", uri);
+        }
+
+        let req = test::TestRequest::post()
+            .uri("/courses/1/1/sections")
+            .insert_header(bearer(1, Role::Teacher))
+            .set_json(json!({ "title": " ", "position": 0 }))
+            .to_request();
+        let resp = test::call_service(&app, req).await;
+        assert_eq!(resp.status(), StatusCode::UNPROCESSABLE_ENTITY);
+        let body: Value = test::read_body_json(resp).await;
+        assert_eq!(body["details"].as_array().unwrap().len(), 2);
+    });
+
+    // 课程详情中嵌入章节与课时, 课时的新增、修改、删除与调整顺序
+    db_test!(lessons_embedded_in_course_detail(app_state) fixtures("teachers", "courses", "sections") {
+        let app = test::init_service(test_app(app_state)).await;
+        let req = test::TestRequest::get().uri("/courses/1/1").to_request();
+        let course: Value = test::call_and_read_body_json(&app, req).await;
+        assert!(course.get("sections").is_none());
+
+        let req = test::TestRequest::get().uri("/courses/1/1?include=sections").to_request();
+        let course: Value = test::call_and_read_body_json(&app, req).await;
+        assert_eq!(course["name"], "First course");
+        assert_eq!(ids(&course["sections"]), [1, 2]);
+        assert_eq!(ids(&course["sections"][1]["lessons"]), [2, 3]);
+        assert_eq!(course["sections"][0]["lessons"][0]["duration_minutes"], 10);
+
+        let req = test::TestRequest::get().uri("/courses/1/1?include=students").to_request();
+        assert_eq!(test::call_service(&app, req).await.status(), StatusCode::UNPROCESSABLE_ENTITY);
+
+        let req = test::TestRequest::post()
+            .uri("/courses/1/1/sections/2/lessons")
+            .insert_header(bearer(1, Role::Teacher))
+            .set_json(json!({ "title": "Ownership rules", "position": 1 }))
+            .to_request();
+        let lesson: Value = test::call_and_read_body_json(&app, req).await;
+        assert_eq!(lesson["position"], 1);
+        let new_id = lesson["id"].as_i64().unwrap();
+
+        let req = test::TestRequest::put()
+            .uri(&format!("/courses/1/1/sections/2/lessons/{}", new_id))
+            .insert_header(bearer(1, Role::Teacher))
+            .set_json(json!({ "duration_minutes": 5 }))
+            .to_request();
+        let lesson: Value = test::call_and_read_body_json(&app, req).await;
+        assert_eq!(lesson["title"], "Ownership rules");
+        assert_eq!(lesson["duration_minutes"], 5);
+
+        let req = test::TestRequest::put()
+            .uri("/courses/1/1/sections/2/lessons/order")
+            .insert_header(bearer(1, Role::Teacher))
+            .set_json(json!({ "ids": [3, new_id, 2] }))
+            .to_request();
+        let lessons: Value = test::call_and_read_body_json(&app, req).await;
+        assert_eq!(ids(&lessons), [3, new_id, 2]);
+
+        let req = test::TestRequest::delete()
+            .uri(&format!("/courses/1/1/sections/2/lessons/{}", new_id))
+            .insert_header(bearer(1, Role::Teacher))
+            .to_request();
+        assert_eq!(test::call_service(&app, req).await.status(), StatusCode::NO_CONTENT);
+
+        // 删除章节时一起删除其课时
+        let req = test::TestRequest::delete()
+            .uri("/courses/1/1/sections/1")
+            .insert_header(bearer(1, Role::Teacher))
+            .to_request();
+        assert_eq!(test::call_service(&app, req).await.status(), StatusCode::NO_CONTENT);
+
+        let req = test::TestRequest::get().uri("/courses/1/1/sections").to_request();
+        let sections: Value = test::call_and_read_body_json(&app, req).await;
+        assert_eq!(ids(&sections), [2]);
+        assert_eq!(sections[0]["position"], 1);
+        assert_eq!(ids(&sections[0]["lessons"]), [3, 2]);
+        assert_eq!(positions(&sections[0]["lessons"]), [1, 2]);
+    });
+}
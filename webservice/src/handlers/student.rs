@@ -0,0 +1,285 @@
+use actix_web::{web, HttpResponse};
+
+use crate::{
+    auth::{hash_password, verify_password, AuthUser, JwtConfig},
+    errors::AppError,
+    handlers::auth::token_response,
+    models::{
+        auth::{LoginRequest, Role},
+        student::{EnrollStudent, RegisterStudent},
+    },
+    state::AppState,
+};
+
+
+// 学生注册, 邮箱不能重复
+pub async fn register_student(
+    app_state: web::Data<AppState>,
+    new_student: web::Json<RegisterStudent>,
+) -> Result<HttpResponse, AppError> {
+    let new_student: RegisterStudent = new_student.try_into()?;
+    let password_hash = hash_password(&new_student.password)?;
+    app_state.students.post_new_student(new_student, password_hash)
+        .await
+        .map(|student| HttpResponse::Created().json(student))
+}
+
+// 学生使用邮箱登录, 签发的 token 角色为 student
+pub async fn student_login(
+    app_state: web::Data<AppState>,
+    jwt_config: web::Data<JwtConfig>,
+    login: web::Json<LoginRequest>,
+) -> Result<HttpResponse, AppError> {
+    let credential = app_state.students.get_student_credential_by_email(login.email.trim()).await?;
+    // 与老师登录一样, 学生不存在和密码错误返回同样的错误
+    let credential = credential
+        .filter(|c| verify_password(&login.password, &c.password_hash))
+        .ok_or_else(|| AppError::Unauthorized("Invalid email or password".into()))?;
+    token_response(&jwt_config, credential.id, Role::Student)
+}
+
+pub async fn get_student_details(
+    app_state: web::Data<AppState>,
+    params: web::Path<i32>,
+    user: AuthUser,
+) -> Result<HttpResponse, AppError> {
+    let student_id = params.into_inner();
+    user.ensure_student(student_id)?;
+    app_state.students.get_student(student_id)
+        .await
+        .map(|student| HttpResponse::Ok().json(student))
+}
+
+// 学生已选的课程, 最近选的在前
+pub async fn get_student_courses(
+    app_state: web::Data<AppState>,
+    params: web::Path<i32>,
+    user: AuthUser,
+) -> Result<HttpResponse, AppError> {
+    let student_id = params.into_inner();
+    user.ensure_student(student_id)?;
+    app_state.students.get_student_courses(student_id)
+        .await
+        .map(|courses| HttpResponse::Ok().json(courses))
+}
+
+// 选课, 学生只能为自己选课, 课程已满或已经选过时返回 409
+pub async fn enroll(
+    app_state: web::Data<AppState>,
+    params: web::Path<(i32, i32)>,
+    enrollment: web::Json<EnrollStudent>,
+    user: AuthUser,
+) -> Result<HttpResponse, AppError> {
+    let (teacher_id, course_id) = params.into_inner();
+    user.ensure_student(enrollment.student_id)?;
+    app_state.students.enroll(teacher_id, course_id, enrollment.student_id)
+        .await
+        .map(|enrollment| HttpResponse::Created().json(enrollment))
+}
+
+// 退课, 学生本人或者课程的老师都可以操作, 成功返回 204
+pub async fn unenroll(
+    app_state: web::Data<AppState>,
+    params: web::Path<(i32, i32, i32)>,
+    user: AuthUser,
+) -> Result<HttpResponse, AppError> {
+    let (teacher_id, course_id, student_id) = params.into_inner();
+    user.ensure_student(student_id)
+        .or_else(|_| user.ensure_can_manage(teacher_id))?;
+    app_state.students.unenroll(teacher_id, course_id, student_id)
+        .await
+        .map(|()| HttpResponse::NoContent().finish())
+}
+
+// 课程的选课学生, 只有课程的老师或者管理员可以查看
+pub async fn get_course_enrollments(
+    app_state: web::Data<AppState>,
+    params: web::Path<(i32, i32)>,
+    user: AuthUser,
+) -> Result<HttpResponse, AppError> {
+    let (teacher_id, course_id) = params.into_inner();
+    user.ensure_can_manage(teacher_id)?;
+    app_state.students.get_course_enrollments(teacher_id, course_id)
+        .await
+        .map(|enrollments| HttpResponse::Ok().json(enrollments))
+}
+
+#[cfg(test)]
+mod tests {
+    use crate::models::auth::Role;
+    use crate::test_support::{bearer, db_test, test_app};
+    use actix_web::{http::StatusCode, test};
+    use serde_json::{json, Value};
+
+    // 名额已满或重复选课时返回 409, 退课后空出的名额可以再选
+    db_test!(enrollment_respects_capacity(app_state) fixtures("teachers", "courses", "students") {
+        let app = test::init_service(test_app(app_state)).await;
+        let req = test::TestRequest::patch()
+            .uri("/courses/1/1")
+            .insert_header(bearer(1, Role::Teacher))
+            .insert_header(("If-Match", "\"1\""))
+            .set_json(json!({ "capacity": 2 }))
+            .to_request();
+        let course: Value = test::call_and_read_body_json(&app, req).await;
+        assert_eq!(course["capacity"], 2);
+
+        let enroll = |student_id: i32| {
+            test::TestRequest::post()
+                .uri("/courses/1/1/enrollments")
+                .insert_header(bearer(student_id, Role::Student))
+                .set_json(json!({ "student_id": student_id }))
+                .to_request()
+        };
+        for student_id in [1, 2] {
+            let resp = test::call_service(&app, enroll(student_id)).await;
+            assert_eq!(resp.status(), StatusCode::CREATED);
+        }
+        for student_id in [1, 3] {
+            let resp = test::call_service(&app, enroll(student_id)).await;
+            assert_eq!(resp.status(), StatusCode::CONFLICT);
+        }
+
+        let req = test::TestRequest::get()
+            .uri("/courses/1/1/enrollments")
+            .insert_header(bearer(1, Role::Teacher))
+            .to_request();
+        let enrollments: Value = test::call_and_read_body_json(&app, req).await;
+        assert_eq!(enrollments["capacity"], 2);
+        assert_eq!(enrollments["total"], 2);
+        assert_eq!(enrollments["students"][0]["name"], "Alice");
+
+        let req = test::TestRequest::delete()
+            .uri("/courses/1/1/enrollments/2")
+            .insert_header(bearer(2, Role::Student))
+            .to_request();
+        assert_eq!(test::call_service(&app, req).await.status(), StatusCode::NO_CONTENT);
+        let resp = test::call_service(&app, enroll(3)).await;
+        assert_eq!(resp.status(), StatusCode::CREATED);
+
+        // 已删除的课程不出现在学生的课程列表中
+        let req = test::TestRequest::post()
+            .uri("/courses/1/2/enrollments")
+            .insert_header(bearer(3, Role::Student))
+            .set_json(json!({ "student_id": 3 }))
+            .to_request();
+        assert_eq!(test::call_service(&app, req).await.status(), StatusCode::CREATED);
+        let req = test::TestRequest::delete()
+            .uri("/courses/1/2")
+            .insert_header(bearer(1, Role::Teacher))
+            .insert_header(("If-Match", "*"))
+            .to_request();
+        assert_eq!(test::call_service(&app, req).await.status(), StatusCode::NO_CONTENT);
+        let req = test::TestRequest::get()
+            .uri("/students/3/courses")
+            .insert_header(bearer(3, Role::Student))
+            .to_request();
+        let courses: Value = test::call_and_read_body_json(&app, req).await;
+        assert_eq!(courses.as_array().unwrap().len(), 1);
+        assert_eq!(courses[0]["id"], 1);
+        assert_eq!(courses[0]["name"], "First course");
+        assert!(courses[0]["enrolled_at"].is_string());
+    });
+
+    db_test!(enrollment_permissions(app_state) fixtures("teachers", "courses", "students") {
+        let app = test::init_service(test_app(app_state)).await;
+        let enroll = |uri: &str, student_id: i32| {
+            test::TestRequest::post()
+                .uri(uri)
+                .insert_header(bearer(1, Role::Student))
+                .set_json(json!({ "student_id": student_id }))
+                .to_request()
+        };
+        let cases = [
+            ("/courses/1/1/enrollments", 2, StatusCode::FORBIDDEN, "forbidden"),
+            ("/courses/2/1/enrollments", 1, StatusCode::NOT_FOUND, "course_not_found"),
+            ("/courses/1/100/enrollments", 1, StatusCode::NOT_FOUND, "course_not_found"),
+        ];
+        for (uri, student_id, status, code) in cases {
+            let resp = test::call_service(&app, enroll(uri, student_id)).await;
+            assert_eq!(resp.status(), status, "{}", uri);
+            let body: Value = test::read_body_json(resp).await;
+            assert_eq!(body["code"], code, "{}", uri);
+        }
+
+        // 学生的 id 与老师相同时也不能管理老师的数据
+        let req = test::TestRequest::get()
+            .uri("/courses/1/1/enrollments")
+            .insert_header(bearer(1, Role::Student))
+            .to_request();
+        assert_eq!(test::call_service(&app, req).await.status(), StatusCode::FORBIDDEN);
+        let req = test::TestRequest::post()
+            .uri("/courses/")
+            .insert_header(bearer(1, Role::Student))
+            .set_json(json!({ "teacher_id": 1, "name": "Not allowed" }))
+            .to_request();
+        assert_eq!(test::call_service(&app, req).await.status(), StatusCode::FORBIDDEN);
+        let req = test::TestRequest::get()
+            .uri("/students/2/courses")
+            .insert_header(bearer(1, Role::Student))
+            .to_request();
+        assert_eq!(test::call_service(&app, req).await.status(), StatusCode::FORBIDDEN);
+
+        // 管理员可以为学生选课, 课程的老师可以让学生退课
+        let req = test::TestRequest::post()
+            .uri("/courses/1/1/enrollments")
+            .insert_header(bearer(2, Role::Admin))
+            .set_json(json!({ "student_id": 2 }))
+            .to_request();
+        assert_eq!(test::call_service(&app, req).await.status(), StatusCode::CREATED);
+        let unenroll = |teacher_id: i32| {
+            test::TestRequest::delete()
+                .uri("/courses/1/1/enrollments/2")
+                .insert_header(bearer(teacher_id, Role::Teacher))
+                .to_request()
+        };
+        assert_eq!(test::call_service(&app, unenroll(2)).await.status(), StatusCode::FORBIDDEN);
+        assert_eq!(test::call_service(&app, unenroll(1)).await.status(), StatusCode::NO_CONTENT);
+        let resp = test::call_service(&app, unenroll(1)).await;
+        assert_eq!(resp.status(), StatusCode::NOT_FOUND);
+        let body: Value = test::read_body_json(resp).await;
+        assert_eq!(body["code"], "enrollment_not_found");
+
+        let req = test::TestRequest::post()
+            .uri("/courses/1/1/enrollments")
+            .insert_header(bearer(2, Role::Admin))
+            .set_json(json!({ "student_id": 100 }))
+            .to_request();
+        let resp = test::call_service(&app, req).await;
+        let body: Value = test::read_body_json(resp).await;
+        assert_eq!(body["code"], "student_not_found");
+    });
+
+    db_test!(register_then_login_student(app_state) {
+        let app = test::init_service(test_app(app_state)).await;
+        let register = |email: &str| {
+            test::TestRequest::post()
+                .uri("/students/")
+                .set_json(json!({ "name": "Dora", "email": email, "password": "a-secret-password" }))
+                .to_request()
+        };
+        let resp = test::call_service(&app, register("Dora@example.com")).await;
+        assert_eq!(resp.status(), StatusCode::CREATED);
+        let student: Value = test::read_body_json(resp).await;
+        assert_eq!(student["email"], "dora@example.com");
+        assert!(student.get("password_hash").is_none());
+        let resp = test::call_service(&app, register("DORA@example.com")).await;
+        assert_eq!(resp.status(), StatusCode::CONFLICT);
+
+        let login = |password: &str| {
+            test::TestRequest::post()
+                .uri("/students/login")
+                .set_json(json!({ "email": "dora@example.com", "password": password }))
+                .to_request()
+        };
+        let resp = test::call_service(&app, login("wrong-password")).await;
+        assert_eq!(resp.status(), StatusCode::UNAUTHORIZED);
+        let token: Value = test::call_and_read_body_json(&app, login("a-secret-password")).await;
+
+        let req = test::TestRequest::get()
+            .uri(&format!("/students/{}", student["id"]))
+            .insert_header(("Authorization", format!("Bearer {}", token["access_token"].as_str().unwrap())))
+            .to_request();
+        let details: Value = test::call_and_read_body_json(&app, req).await;
+        assert_eq!(details["name"], "Dora");
+    });
+}
@@ -1,11 +1,13 @@
 use actix_web::{web, HttpResponse};
 
 use crate::{
-    dbaccess::teacher::{
-        delete_teacher_db, get_all_teacher_db, get_teacher_detail_db, post_new_teacher_db, update_teacher_details_db
-    }, 
+    auth::{hash_password, verify_password, AuthUser},
     errors::AppError, 
-    models::teacher::{CreateTeacher, UpdateTeacher}, 
+    etag::{etag, IfMatch},
+    models::{
+        auth::{ChangePassword, Role},
+        teacher::{CreateTeacher, DeleteTeacherQuery, PatchTeacher, Teacher, UpdateTeacher},
+    },
     state::AppState
 };
 
@@ -13,7 +15,7 @@ use crate::{
 pub async fn get_all_teacher(
     app_state: web::Data<AppState>,
 ) -> Result<HttpResponse, AppError> {
-    get_all_teacher_db(&app_state.db)
+    app_state.teachers.get_all_teacher()
         .await
         .map(|teachers|HttpResponse::Ok().json(teachers))
 }
@@ -24,113 +26,191 @@ pub async fn get_teacher_details(
     params: web::Path<i32>,
 ) -> Result<HttpResponse, AppError> {
     let teacher_id = params.into_inner();
-    get_teacher_detail_db(&app_state.db, teacher_id)
+    app_state.teachers.get_teacher_detail(teacher_id)
         .await
-        .map(|teacher|HttpResponse::Ok().json(teacher))
+        .map(|teacher|HttpResponse::Ok().insert_header(etag(teacher.version)).json(teacher))
 }
 
 pub async fn post_new_teacher(
     app_state: web::Data<AppState>,
     new_teacher: web::Json<CreateTeacher>,
 ) -> Result<HttpResponse, AppError> {
-    post_new_teacher_db(&app_state.db, new_teacher.into())
+    create_teacher(&app_state, new_teacher.try_into()?)
         .await
-        .map(|teacher| HttpResponse::Ok().json(teacher))
+        .map(|teacher| HttpResponse::Ok().insert_header(etag(teacher.version)).json(teacher))
 }
 
+// 检查邮箱是否已被注册, 哈希密码后保存
+pub(crate) async fn create_teacher(
+    app_state: &AppState,
+    new_teacher: CreateTeacher,
+) -> Result<Teacher, AppError> {
+    if let Some(email) = &new_teacher.email {
+        if app_state.teachers.teacher_email_exists(email).await? {
+            return Err(AppError::Conflict("Email already registered".into()));
+        }
+    }
+    let password_hash = new_teacher
+        .password
+        .as_deref()
+        .map(hash_password)
+        .transpose()?;
+    app_state.teachers.post_new_teacher(new_teacher, password_hash).await
+}
+
+// 修改密码, 老师本人需要提供当前密码
+pub async fn change_teacher_password(
+    app_state: web::Data<AppState>,
+    params: web::Path<i32>,
+    change: web::Json<ChangePassword>,
+    user: AuthUser,
+) -> Result<HttpResponse, AppError> {
+    let teacher_id = params.into_inner();
+    user.ensure_can_manage(teacher_id)?;
+    let change: ChangePassword = change.try_into()?;
+
+    let credential = app_state.teachers.get_teacher_credential(teacher_id)
+        .await?
+        .ok_or_else(|| AppError::NotFound("teacher".into()))?;
+    let current_ok = match (&credential.password_hash, &change.current_password) {
+        (Some(hash), Some(current)) => verify_password(current, hash),
+        // 还没有设置过密码
+        (None, _) => true,
+        (Some(_), None) => false,
+    };
+    if !current_ok && user.role != Role::Admin {
+        return Err(AppError::Unauthorized("Current password is incorrect".into()));
+    }
+
+    app_state.teachers.update_teacher_password(teacher_id, hash_password(&change.new_password)?)
+        .await
+        .map(|_| HttpResponse::NoContent().finish())
+}
+
+// 需要携带 If-Match, 与当前版本不一致时返回 412
 pub async fn update_teacher_details(
     app_state: web::Data<AppState>,
     params: web::Path<i32>,
-    update_teacher: web::Json<UpdateTeacher>
+    update_teacher: web::Json<UpdateTeacher>,
+    if_match: IfMatch,
+    user: AuthUser,
 ) -> Result<HttpResponse, AppError> {
     let teacher_id = params.into_inner();
-    update_teacher_details_db(&app_state.db, teacher_id, UpdateTeacher::from(update_teacher))
+    user.ensure_can_manage(teacher_id)?;
+    app_state.teachers.update_teacher_details(
+        teacher_id,
+        &if_match,
+        UpdateTeacher::try_from(update_teacher)?.into(),
+    )
         .await
-        .map(|teacher| HttpResponse::Ok().json(teacher))
+        .map(|teacher| HttpResponse::Ok().insert_header(etag(teacher.version)).json(teacher))
 }
 
+// 部分修改, 不传入的字段保持不变, picture_url 和 profile 传入 null 时清空
+pub async fn patch_teacher_details(
+    app_state: web::Data<AppState>,
+    params: web::Path<i32>,
+    patch: web::Json<PatchTeacher>,
+    if_match: IfMatch,
+    user: AuthUser,
+) -> Result<HttpResponse, AppError> {
+    let teacher_id = params.into_inner();
+    user.ensure_can_manage(teacher_id)?;
+    app_state.teachers.update_teacher_details(
+        teacher_id,
+        &if_match,
+        PatchTeacher::try_from(patch)?,
+    )
+        .await
+        .map(|teacher| HttpResponse::Ok().insert_header(etag(teacher.version)).json(teacher))
+}
+
+// 老师还有课程时返回 409 及课程数, ?cascade=true 时连同课程一起删除
 pub async fn delete_teacher(
     app_state: web::Data<AppState>,
     params: web::Path<i32>,
+    query: web::Query<DeleteTeacherQuery>,
+    if_match: IfMatch,
+    user: AuthUser,
 ) -> Result<HttpResponse, AppError> {
     let  teacher_id = params.into_inner();
-    delete_teacher_db(&app_state.db, teacher_id)
+    user.ensure_can_manage(teacher_id)?;
+    app_state.teachers.delete_teacher(teacher_id, &if_match, query.cascade)
+        .await
+        .map(|deleted| HttpResponse::Ok().json(deleted))
+}
+
+// 已删除的老师无法登录, 只有管理员可以查看和恢复
+pub async fn get_deleted_teachers(
+    app_state: web::Data<AppState>,
+    user: AuthUser,
+) -> Result<HttpResponse, AppError> {
+    user.ensure_admin()?;
+    app_state.teachers.get_deleted_teachers()
         .await
-        .map(|resp| HttpResponse::Ok().json(resp))
+        .map(|teachers| HttpResponse::Ok().json(teachers))
+}
+
+pub async fn restore_teacher(
+    app_state: web::Data<AppState>,
+    params: web::Path<i32>,
+    user: AuthUser,
+) -> Result<HttpResponse, AppError> {
+    user.ensure_admin()?;
+    app_state.teachers.restore_teacher(params.into_inner())
+        .await
+        .map(|teacher| HttpResponse::Ok().insert_header(etag(teacher.version)).json(teacher))
 }
 
 #[cfg(test)]
 mod tests {
-    use std::{env, sync::Mutex};
+    use actix_web::{http::StatusCode, test, web};
 
-    use actix_web::{guard::Connect, http::StatusCode, web};
-    use dotenv::dotenv;
-    use sqlx::postgres::PgPoolOptions;
+    use actix_web::ResponseError;
 
-    use crate::{models::teacher::CreateTeacher, state::AppState};
+    use crate::{
+        auth::{verify_password, AuthUser},
+        errors::AppError,
+        etag::IfMatch,
+        models::{
+            auth::{ChangePassword, Role},
+            course::CreateCourse,
+            teacher::{CreateTeacher, DeleteTeacherQuery, DeletedTeacher, PatchTeacher},
+        },
+        test_support::{bearer, db_test, test_app},
+    };
 
-    use super::{delete_teacher, get_all_teacher, get_teacher_details, post_new_teacher};
+    use super::{
+        change_teacher_password, create_teacher, delete_teacher, get_all_teacher,
+        get_deleted_teachers, get_teacher_details, post_new_teacher, restore_teacher,
+    };
 
-    #[actix_rt::test]
-    async fn get_all_teacher_success_test() {
-        dotenv().ok();
-        let db_url = env::var("DATABASE_URL")
-            .expect("DATABASE_URL is not set in .env file");
-        let db_pool = PgPoolOptions::new().connect(&db_url)
-            .await
-            .unwrap();
-        
-        let app_state = web::Data::new(AppState {
-            health_check_response: "".to_string(),
-            visit_count: Mutex::new(0),
-            db: db_pool,
-        });
+    db_test!(get_all_teacher_success_test(app_state) fixtures("teachers") {
+        let app_state = web::Data::new(app_state);
 
         let resp = get_all_teacher(app_state)
             .await.unwrap();
         assert_eq!(resp.status(), StatusCode::OK);
-    }
+    });
 
-    #[actix_rt::test]
-    async fn get_teacher_details_success() {
-        dotenv().ok();
-        let db_url = env::var("DATABASE_URL")
-            .expect("DATABASE_URL is not set in .env file");
-        let db_pool = PgPoolOptions::new().connect(&db_url)
-            .await
-            .unwrap();
-        let app_state = web::Data::new(AppState {
-            health_check_response: "".to_string(),
-            visit_count: Mutex::new(0),
-            db: db_pool,
-        });
+    db_test!(get_teacher_details_success(app_state) fixtures("teachers") {
+        let app_state = web::Data::new(app_state);
 
         let params = web::Path::from(1);
         let resp = get_teacher_details(app_state, params)
             .await.unwrap();
         assert_eq!(resp.status(), StatusCode::OK);
-    }
-
-    #[actix_rt::test]
-    async fn post_new_teacher_success() {
-        dotenv().ok();
-        let db_url = env::var("DATABASE_URL")
-            .expect("DATABASE_URL is not set in .env file");
-        
-        let db_pool = PgPoolOptions::new().connect(&db_url)
-            .await
-            .unwrap();
+    });
 
-        let app_state = web::Data::new(AppState {
-            health_check_response: "".to_string(),
-            visit_count: Mutex::new(0),
-            db: db_pool,
-        });
+    db_test!(post_new_teacher_success(app_state) {
+        let app_state = web::Data::new(app_state);
 
         let new_teacher = CreateTeacher {
             name: "Third teacher".into(),
             picture_url: "http://unicorn.pro".into(),
             profile: "A teacher in Machine learning".into(),
+            email: None,
+            password: Some("a-secret-password".into()),
         };
 
         let resp = post_new_teacher(app_state, web::Json(new_teacher))
@@ -138,32 +218,271 @@ mod tests {
             .unwrap();
 
         assert_eq!(resp.status(), StatusCode::OK);
-    }
+    });
 
+    db_test!(post_invalid_teacher_lists_fields(app_state) {
+        let app_state = web::Data::new(app_state);
 
-    #[actix_rt::test()]
-    async fn delete_teacher_success() {
-        dotenv().ok();
-        let db_url = env::var("DATABASE_URL")
-            .expect("DATABASE_URL is not set in .env file");
+        let new_teacher = CreateTeacher {
+            name: "".into(),
+            picture_url: "not a url".into(),
+            profile: "".into(),
+            email: Some("nobody".into()),
+            password: Some("short".into()),
+        };
 
-        let db_pool = PgPoolOptions::new().connect(&db_url)
+        let err = post_new_teacher(app_state, web::Json(new_teacher))
+            .await
+            .unwrap_err();
+        assert_eq!(err.status_code(), StatusCode::UNPROCESSABLE_ENTITY);
+        assert_eq!(err.code(), "validation_failed");
+        let AppError::ValidationFailed(details) = err else {
+            panic!("expected validation error");
+        };
+        let fields: Vec<&str> = details.iter().map(|d| d.field.as_str()).collect();
+        assert_eq!(fields, ["email", "name", "password", "picture_url"]);
+    });
+
+
+    db_test!(change_password_requires_current_password(app_state) {
+        let app_state = web::Data::new(app_state);
+
+        let teacher = create_teacher(&app_state, CreateTeacher {
+            name: "Password teacher".into(),
+            picture_url: "http://unicorn.pro".into(),
+            profile: "A teacher changing password".into(),
+            email: None,
+            password: Some("old-password".into()),
+        })
             .await
             .unwrap();
+        let user = AuthUser { id: teacher.id, role: Role::Teacher };
 
-        let app_state = web::Data::new(AppState {
-            health_check_response: "".to_string(),
-            visit_count: Mutex::new(0),
-            db: db_pool,
+        let wrong = web::Json(ChangePassword {
+            current_password: Some("not-the-password".into()),
+            new_password: "new-password".into(),
         });
+        let resp = change_teacher_password(
+            app_state.clone(), web::Path::from(teacher.id), wrong, user.clone()
+        ).await;
+        match resp {
+            Ok(_) => panic!("wrong current password should be rejected"),
+            Err(err) => assert_eq!(err.status_code(), StatusCode::UNAUTHORIZED),
+        }
 
-        let params = web::Path::from(1);
+        let right = web::Json(ChangePassword {
+            current_password: Some("old-password".into()),
+            new_password: "new-password".into(),
+        });
+        let resp = change_teacher_password(
+            app_state.clone(), web::Path::from(teacher.id), right, user
+        )
+            .await
+            .unwrap();
+        assert_eq!(resp.status(), StatusCode::NO_CONTENT);
 
-        let resp = delete_teacher(app_state, params)
+        let credential = app_state.teachers.get_teacher_credential(teacher.id)
             .await
+            .unwrap()
             .unwrap();
+        assert!(verify_password("new-password", &credential.password_hash.unwrap()));
+    });
+
+    db_test!(duplicate_email_insert_conflict(app_state) {
+        // 绕过注册时的检查, 直接依赖数据库的唯一索引
+        let new_teacher = CreateTeacher {
+            name: "Duplicate teacher".into(),
+            picture_url: "http://unicorn.pro".into(),
+            profile: "A teacher with a duplicate email".into(),
+            email: Some("dup@unicorn.pro".into()),
+            password: None,
+        };
+        let _ = app_state.teachers.post_new_teacher(new_teacher.clone(), None).await;
+        let err = app_state.teachers.post_new_teacher(new_teacher, None)
+            .await
+            .unwrap_err();
+        assert_eq!(err.status_code(), StatusCode::CONFLICT);
+        assert!(err.to_string().contains("teacher_email_key"));
+    });
+
+    db_test!(delete_teacher_with_courses_conflict(app_state) fixtures("teachers", "courses") {
+        // 老师 1 还有 3 门未删除的课程, 不允许删除
+        let err = app_state.teachers.delete_teacher(1, &IfMatch::ANY, false).await.unwrap_err();
+        assert_eq!(err.status_code(), StatusCode::CONFLICT);
+        assert_eq!(err.code(), "has_dependents");
+        assert!(matches!(err, AppError::HasDependents(ref resource, 3) if resource == "course"));
+        assert_eq!(app_state.teachers.get_teacher_detail(1).await.unwrap().id, 1);
+
+        let err = app_state.teachers.delete_teacher(100_000, &IfMatch::ANY, false).await.unwrap_err();
+        assert_eq!(err.status_code(), StatusCode::NOT_FOUND);
+    });
+
+    db_test!(patch_teacher_clears_profile(app_state) fixtures("teachers") {
+        let app = test::init_service(test_app(app_state)).await;
+        let req = test::TestRequest::patch()
+            .uri("/teachers/2")
+            .insert_header(bearer(2, Role::Teacher))
+            .insert_header(("If-Match", "\"1\""))
+            .set_json(serde_json::json!({ "profile": null }))
+            .to_request();
+        let resp = test::call_service(&app, req).await;
         assert_eq!(resp.status(), StatusCode::OK);
-    }
+        assert_eq!(resp.headers().get("etag").unwrap(), "\"2\"");
+        let teacher: serde_json::Value = test::read_body_json(resp).await;
+        assert_eq!(teacher["name"], "Tom");
+        assert_eq!(teacher["profile"], "");
+
+        let req = test::TestRequest::patch()
+            .uri("/teachers/2")
+            .insert_header(bearer(2, Role::Teacher))
+            .insert_header(("If-Match", "\"2\""))
+            .set_json(serde_json::json!({ "picture_url": null }))
+            .to_request();
+        assert_eq!(test::call_service(&app, req).await.status(), StatusCode::OK);
+
+        // 清空后的字段在之后的读取中同样返回空字符串
+        let req = test::TestRequest::get().uri("/teachers/2").to_request();
+        let teacher: serde_json::Value = test::call_and_read_body_json(&app, req).await;
+        assert_eq!(teacher["picture_url"], "");
+        assert_eq!(teacher["profile"], "");
+        let req = test::TestRequest::get().uri("/teachers/").to_request();
+        let teachers: serde_json::Value = test::call_and_read_body_json(&app, req).await;
+        let tom = teachers.as_array().unwrap().iter().find(|t| t["id"] == 2).unwrap();
+        assert_eq!(tom["picture_url"], "");
+    });
+
+    // 版本号过期时修改和删除都返回 412, 老师保持不变
+    db_test!(stale_teacher_version_rejected(app_state) fixtures("teachers") {
+        let update = PatchTeacher {
+            name: Some(Some("Renamed".into())),
+            ..Default::default()
+        };
+        let teacher = app_state.teachers.update_teacher_details(2, &IfMatch::version(1), update.clone())
+            .await
+            .unwrap();
+        assert_eq!(teacher.version, 2);
+
+        let err = app_state.teachers.update_teacher_details(2, &IfMatch::version(1), update)
+            .await
+            .unwrap_err();
+        assert_eq!(err.status_code(), StatusCode::PRECONDITION_FAILED);
+        let err = app_state.teachers.delete_teacher(2, &IfMatch::version(1), false).await.unwrap_err();
+        assert_eq!(err.status_code(), StatusCode::PRECONDITION_FAILED);
+        assert_eq!(app_state.teachers.get_teacher_detail(2).await.unwrap().version, 2);
 
+        let deleted = app_state.teachers.delete_teacher(2, &IfMatch::version(2), false).await.unwrap();
+        assert_eq!(deleted.deleted_courses, 0);
+    });
+
+    // 通过完整的 App 发送请求, ?cascade=true 时连同课程一起移入回收站
+    db_test!(delete_teacher_cascade(app_state) fixtures("teachers", "courses") {
+        let app = test::init_service(test_app(app_state)).await;
+        let req = test::TestRequest::delete()
+            .uri("/teachers/1")
+            .insert_header(bearer(1, Role::Teacher))
+            .insert_header(("If-Match", "*"))
+            .to_request();
+        let resp = test::call_service(&app, req).await;
+        assert_eq!(resp.status(), StatusCode::CONFLICT);
+        let body: serde_json::Value = test::read_body_json(resp).await;
+        assert_eq!(body["code"], "has_dependents");
+        assert_eq!(body["dependents"], serde_json::json!({ "resource": "course", "count": 3 }));
+
+        let req = test::TestRequest::delete()
+            .uri("/teachers/1?cascade=true")
+            .insert_header(bearer(1, Role::Teacher))
+            .insert_header(("If-Match", "*"))
+            .to_request();
+        let deleted: DeletedTeacher = test::call_and_read_body_json(&app, req).await;
+        assert_eq!(deleted, DeletedTeacher { teacher_id: 1, deleted_courses: 3 });
+
+        let req = test::TestRequest::get().uri("/courses/1").to_request();
+        let page: serde_json::Value = test::call_and_read_body_json(&app, req).await;
+        assert_eq!(page["total"], 0);
+        let req = test::TestRequest::get().uri("/teachers/1").to_request();
+        assert_eq!(test::call_service(&app, req).await.status(), StatusCode::NOT_FOUND);
+    });
+
+    db_test!(delete_teacher_success(app_state) {
+        let app_state = web::Data::new(app_state);
+
+        let teacher = app_state.teachers.post_new_teacher(CreateTeacher {
+            name: "Teacher to delete".into(),
+            picture_url: "http://unicorn.pro".into(),
+            profile: "A teacher to be deleted".into(),
+            email: None,
+            password: None,
+        }, None)
+            .await
+            .unwrap();
+        let params = web::Path::from(teacher.id);
+        let user = AuthUser { id: teacher.id, role: Role::Teacher };
+
+        let query = || web::Query(DeleteTeacherQuery::default());
+        let resp = delete_teacher(app_state.clone(), params, query(), IfMatch::version(1), user.clone())
+            .await
+            .unwrap();
+        assert_eq!(resp.status(), StatusCode::OK);
+
+        // 已经删除的老师再次删除时返回 404
+        let err = delete_teacher(app_state, web::Path::from(teacher.id), query(), IfMatch::ANY, user)
+            .await
+            .unwrap_err();
+        assert_eq!(err.code(), "teacher_not_found");
+    });
+
+    // 删除的老师不再出现在列表中, 不能创建课程, 其回收站中的课程也不能恢复
+    db_test!(deleted_teacher_restored_by_admin(app_state) fixtures("teachers", "courses") {
+        let app_state = web::Data::new(app_state);
+        app_state.courses.delete_course(1, 1, &IfMatch::ANY).await.unwrap();
+        // 保证单独删除的课程与老师的删除时间不同
+        actix_rt::time::sleep(std::time::Duration::from_millis(5)).await;
+        let user = AuthUser { id: 1, role: Role::Teacher };
+        let query = web::Query(DeleteTeacherQuery { cascade: true });
+        delete_teacher(app_state.clone(), web::Path::from(1), query, IfMatch::ANY, user.clone())
+            .await
+            .unwrap();
+
+        let err = app_state.teachers.get_teacher_detail(1).await.unwrap_err();
+        assert_eq!(err.status_code(), StatusCode::NOT_FOUND);
+        assert_eq!(app_state.teachers.get_all_teacher().await.unwrap().len(), 1);
+        assert!(app_state.teachers.get_teacher_credential(1).await.unwrap().is_none());
+        let err = app_state.courses.post_new_course(CreateCourse {
+            teacher_id: 1,
+            name: "Course of a deleted teacher".into(),
+            description: None,
+            format: None,
+            structure: None,
+            duration: None,
+            price: None,
+            language: None,
+            level: None,
+            capacity: None,
+        })
+            .await
+            .unwrap_err();
+        assert!(err.to_string().contains("course_teacher_id_fkey"));
+        let err = app_state.courses.restore_course(1, 1).await.unwrap_err();
+        assert_eq!(err.status_code(), StatusCode::UNPROCESSABLE_ENTITY);
+
+        // 只有管理员可以查看和恢复已删除的老师
+        let err = get_deleted_teachers(app_state.clone(), user.clone()).await.unwrap_err();
+        assert_eq!(err.status_code(), StatusCode::FORBIDDEN);
+        let admin = AuthUser { id: 2, role: Role::Admin };
+        let resp = get_deleted_teachers(app_state.clone(), admin.clone()).await.unwrap();
+        assert_eq!(resp.status(), StatusCode::OK);
+        let resp = restore_teacher(app_state.clone(), web::Path::from(1), admin)
+            .await
+            .unwrap();
+        assert_eq!(resp.status(), StatusCode::OK);
 
+        // 与老师一起删除的课程一起恢复, 之前单独删除的课程仍在回收站中
+        assert_eq!(app_state.teachers.get_teacher_detail(1).await.unwrap().id, 1);
+        let deleted = app_state.courses.get_deleted_courses(1).await.unwrap();
+        assert_eq!(deleted.iter().map(|c| c.id).collect::<Vec<_>>(), [1]);
+        let page = app_state.courses.get_course_for_teacher(1, &Default::default()).await.unwrap();
+        assert_eq!(page.total, 2);
+        assert!(page.items.iter().all(|c| c.version == 3));
+        app_state.courses.restore_course(1, 1).await.unwrap();
+    });
 }
@@ -4,3 +4,19 @@ pub mod routers;
 pub mod state;
 pub mod models;
 pub mod errors;
+pub mod auth;
+pub mod etag;
+pub mod migration;
+pub mod request_id;
+pub mod telemetry;
+pub mod metrics;
+pub mod config;
+pub mod db;
+pub mod lifecycle;
+pub mod app;
+pub mod repository;
+pub mod trash;
+pub mod course_file;
+
+#[cfg(test)]
+pub mod test_support;
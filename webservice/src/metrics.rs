@@ -0,0 +1,238 @@
+use std::future::{ready, Future, Ready};
+use std::pin::Pin;
+use std::sync::{Arc, LazyLock};
+use std::time::{Duration, Instant};
+
+use actix_web::dev::{forward_ready, Service, ServiceRequest, ServiceResponse, Transform};
+use actix_web::Error;
+use prometheus::{
+    Encoder, Histogram, HistogramOpts, HistogramVec, IntCounterVec, IntGauge, Opts, Registry,
+    TextEncoder,
+};
+
+use crate::db::DbPool;
+use crate::errors::AppError;
+
+// 没有匹配到任何路由的请求统一使用这个标签, 避免 404 请求的路径导致标签数量无限增长
+const UNMATCHED_ROUTE: &str = "unmatched";
+
+// 从连接池获取连接的等待时间, 由数据库存储在每次获取连接时记录
+// 存储在 AppState 中创建, 早于 Metrics, 因此使用全局的指标, 由每个 Metrics 注册到自己的 Registry
+pub static DB_POOL_ACQUIRE_WAIT: LazyLock<Histogram> = LazyLock::new(|| {
+    Histogram::with_opts(HistogramOpts::new(
+        "db_pool_acquire_wait_seconds",
+        "Time spent waiting for a connection from the pool in seconds",
+    ))
+    .expect("valid histogram options")
+});
+
+// 服务的 Prometheus 指标, 作为 app_data 挂载, 由 MetricsMiddleware 记录, /metrics 输出
+pub struct Metrics {
+    registry: Registry,
+    http_requests_total: IntCounterVec,
+    http_request_duration_seconds: HistogramVec,
+    db_pool_size: IntGauge,
+    db_pool_idle: IntGauge,
+    db_pool_max_size: IntGauge,
+}
+
+impl Metrics {
+    pub fn new() -> Result<Self, prometheus::Error> {
+        let registry = Registry::new();
+        let http_requests_total = IntCounterVec::new(
+            Opts::new("http_requests_total", "Number of HTTP requests"),
+            &["method", "route", "status"],
+        )?;
+        let http_request_duration_seconds = HistogramVec::new(
+            HistogramOpts::new(
+                "http_request_duration_seconds",
+                "HTTP request latency in seconds",
+            ),
+            &["method", "route"],
+        )?;
+        let db_pool_size = IntGauge::new(
+            "db_pool_connections",
+            "Number of connections currently held by the pool, idle or in use",
+        )?;
+        let db_pool_idle = IntGauge::new("db_pool_idle_connections", "Number of idle connections")?;
+        let db_pool_max_size = IntGauge::new(
+            "db_pool_max_connections",
+            "Maximum number of connections the pool may open",
+        )?;
+
+        registry.register(Box::new(http_requests_total.clone()))?;
+        registry.register(Box::new(http_request_duration_seconds.clone()))?;
+        registry.register(Box::new(db_pool_size.clone()))?;
+        registry.register(Box::new(db_pool_idle.clone()))?;
+        registry.register(Box::new(db_pool_max_size.clone()))?;
+        registry.register(Box::new(DB_POOL_ACQUIRE_WAIT.clone()))?;
+
+        Ok(Metrics {
+            registry,
+            http_requests_total,
+            http_request_duration_seconds,
+            db_pool_size,
+            db_pool_idle,
+            db_pool_max_size,
+        })
+    }
+
+    pub fn observe_request(&self, method: &str, route: &str, status: u16, elapsed: Duration) {
+        self.http_requests_total
+            .with_label_values(&[method, route, &status.to_string()])
+            .inc();
+        self.http_request_duration_seconds
+            .with_label_values(&[method, route])
+            .observe(elapsed.as_secs_f64());
+    }
+
+    // 连接池的状态只在抓取时读取, 不占用连接, 连接池已满时也能正常抓取
+    pub fn observe_pool(&self, pool: &DbPool) {
+        self.db_pool_size.set(pool.size() as i64);
+        self.db_pool_idle.set(pool.num_idle() as i64);
+        self.db_pool_max_size.set(pool.max_connections() as i64);
+    }
+
+    // Prometheus 文本格式
+    pub fn render(&self) -> Result<String, AppError> {
+        let mut buffer = Vec::new();
+        TextEncoder::new()
+            .encode(&self.registry.gather(), &mut buffer)
+            .map_err(|e| AppError::ActixError(e.to_string()))?;
+        String::from_utf8(buffer).map_err(|e| AppError::ActixError(e.to_string()))
+    }
+}
+
+// 记录每个请求的次数、状态码与耗时, 路由标签使用路由模板(如 /courses/{teacher_id})而不是实际路径
+pub struct MetricsMiddleware {
+    metrics: Arc<Metrics>,
+}
+
+impl MetricsMiddleware {
+    pub fn new(metrics: impl Into<Arc<Metrics>>) -> Self {
+        MetricsMiddleware {
+            metrics: metrics.into(),
+        }
+    }
+}
+
+impl<S, B> Transform<S, ServiceRequest> for MetricsMiddleware
+where
+    S: Service<ServiceRequest, Response = ServiceResponse<B>, Error = Error> + 'static,
+    B: 'static,
+{
+    type Response = ServiceResponse<B>;
+    type Error = Error;
+    type Transform = MetricsService<S>;
+    type InitError = ();
+    type Future = Ready<Result<Self::Transform, Self::InitError>>;
+
+    fn new_transform(&self, service: S) -> Self::Future {
+        ready(Ok(MetricsService {
+            service,
+            metrics: self.metrics.clone(),
+        }))
+    }
+}
+
+pub struct MetricsService<S> {
+    service: S,
+    metrics: Arc<Metrics>,
+}
+
+impl<S, B> Service<ServiceRequest> for MetricsService<S>
+where
+    S: Service<ServiceRequest, Response = ServiceResponse<B>, Error = Error> + 'static,
+    B: 'static,
+{
+    type Response = ServiceResponse<B>;
+    type Error = Error;
+    type Future = Pin<Box<dyn Future<Output = Result<Self::Response, Self::Error>>>>;
+
+    forward_ready!(service);
+
+    fn call(&self, req: ServiceRequest) -> Self::Future {
+        let method = req.method().to_string();
+        let route = req
+            .match_pattern()
+            .unwrap_or_else(|| UNMATCHED_ROUTE.to_string());
+        let metrics = self.metrics.clone();
+        let start = Instant::now();
+        let fut = self.service.call(req);
+        Box::pin(async move {
+            let res = fut.await;
+            // 内层返回的错误同样计入, 状态码与错误最终生成的响应一致
+            let status = match &res {
+                Ok(res) => res.status(),
+                Err(err) => err.as_response_error().status_code(),
+            };
+            metrics.observe_request(&method, &route, status.as_u16(), start.elapsed());
+            res
+        })
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use actix_web::{test, web, App, HttpResponse};
+
+    async fn course_handler() -> HttpResponse {
+        HttpResponse::Ok().finish()
+    }
+
+    #[actix_rt::test]
+    async fn requests_are_counted_by_route_template() {
+        let metrics = Arc::new(Metrics::new().unwrap());
+        let app = test::init_service(
+            App::new()
+                .wrap(MetricsMiddleware::new(metrics.clone()))
+                .route("/courses/{teacher_id}", web::get().to(course_handler)),
+        )
+        .await;
+
+        for uri in ["/courses/1", "/courses/2", "/missing"] {
+            let req = test::TestRequest::get().uri(uri).to_request();
+            test::call_service(&app, req).await;
+        }
+
+        let text = metrics.render().unwrap();
+        assert!(text.contains(
+            r#"http_requests_total{method="GET",route="/courses/{teacher_id}",status="200"} 2"#
+        ));
+        assert!(text.contains(
+            r#"http_requests_total{method="GET",route="unmatched",status="404"} 1"#
+        ));
+        assert!(text.contains(
+            r#"http_request_duration_seconds_count{method="GET",route="/courses/{teacher_id}"} 2"#
+        ));
+    }
+
+    // 内层中间件直接返回错误时, 按错误对应的状态码计数
+    #[actix_rt::test]
+    async fn errors_from_inner_services_are_counted() {
+        let metrics = Arc::new(Metrics::new().unwrap());
+        let app = test::init_service(
+            App::new()
+                .wrap_fn(|req, srv| {
+                    let fut = srv.call(req);
+                    async move {
+                        fut.await?;
+                        let err = AppError::Unauthorized("Missing token".into());
+                        Err::<ServiceResponse, _>(err.into())
+                    }
+                })
+                .wrap(MetricsMiddleware::new(metrics.clone()))
+                .route("/courses/{teacher_id}", web::get().to(course_handler)),
+        )
+        .await;
+
+        let req = test::TestRequest::get().uri("/courses/1").to_request();
+        assert!(test::try_call_service(&app, req).await.is_err());
+
+        let text = metrics.render().unwrap();
+        assert!(text.contains(
+            r#"http_requests_total{method="GET",route="/courses/{teacher_id}",status="401"} 1"#
+        ));
+    }
+}
@@ -0,0 +1,233 @@
+use serde::Serialize;
+use sqlx::migrate::{Migrate, MigrateError, Migrator};
+
+use crate::db::{with_pool, DbPool};
+use crate::errors::AppError;
+
+// 编译时将 migrations 目录下的脚本嵌入到二进制文件中, 部署时无需再携带 sql 文件
+pub static MIGRATOR: Migrator = sqlx::migrate!();
+
+// SQLite 的迁移脚本, 版本号与 migrations 中的一一对应
+#[cfg(feature = "sqlite")]
+pub static SQLITE_MIGRATOR: Migrator = sqlx::migrate!("./migrations_sqlite");
+
+// 连接池对应的迁移脚本
+fn migrator(pool: &DbPool) -> &'static Migrator {
+    match pool {
+        DbPool::Postgres(_) => &MIGRATOR,
+        #[cfg(feature = "sqlite")]
+        DbPool::Sqlite(_) => &SQLITE_MIGRATOR,
+    }
+}
+
+// 单个迁移脚本的状态
+#[derive(Debug, Serialize, Clone)]
+pub struct MigrationStatus {
+    pub version: i64,
+    pub description: String,
+    pub applied: bool,
+    // 已执行的脚本内容被修改过时为 false
+    pub checksum_ok: bool,
+}
+
+// 执行所有未执行的迁移
+pub async fn migrate_up(pool: &DbPool) -> Result<(), AppError> {
+    with_pool!(pool, p => migrator(pool).run(p).await?);
+    Ok(())
+}
+
+// 回滚最近一次执行的迁移, 返回被回滚的版本号
+pub async fn migrate_down(pool: &DbPool) -> Result<Option<i64>, AppError> {
+    let mut applied: Vec<i64> = applied_migrations(pool)
+        .await?
+        .into_iter()
+        .map(|m| m.version)
+        .collect();
+    applied.sort_unstable();
+
+    let Some(latest) = applied.pop() else {
+        return Ok(None);
+    };
+    // undo 会回滚所有版本号大于 target 的迁移
+    let target = applied.last().copied().unwrap_or(0);
+    with_pool!(pool, p => migrator(pool).undo(p, target).await?);
+    Ok(Some(latest))
+}
+
+// 对比内嵌的迁移脚本与数据库中的执行记录
+pub async fn migration_status(pool: &DbPool) -> Result<Vec<MigrationStatus>, AppError> {
+    let applied = applied_migrations(pool).await?;
+
+    let mut status: Vec<MigrationStatus> = migrator(pool)
+        .iter()
+        .filter(|m| m.migration_type.is_up_migration())
+        .map(|m| {
+            let record = applied.iter().find(|a| a.version == m.version);
+            MigrationStatus {
+                version: m.version,
+                description: m.description.to_string(),
+                applied: record.is_some(),
+                checksum_ok: record.is_none_or(|a| a.checksum == m.checksum),
+            }
+        })
+        .collect();
+
+    // 数据库中存在, 但二进制中没有的迁移(通常是用新版本迁移后又部署了旧版本)
+    for a in applied.iter() {
+        if !status.iter().any(|s| s.version == a.version) {
+            status.push(MigrationStatus {
+                version: a.version,
+                description: "<unknown>".into(),
+                applied: true,
+                checksum_ok: false,
+            });
+        }
+    }
+    status.sort_by_key(|s| s.version);
+    Ok(status)
+}
+
+// 启动时检查数据库结构是否与当前二进制匹配, 不匹配时拒绝启动服务
+pub async fn check_schema(pool: &DbPool) -> Result<(), AppError> {
+    let dirty = with_pool!(pool, p => {
+        let mut conn = p.acquire().await?;
+        conn.ensure_migrations_table().await?;
+        conn.dirty_version().await?
+    });
+    if let Some(version) = dirty {
+        return Err(MigrateError::Dirty(version).into());
+    }
+
+    let status = migration_status(pool).await?;
+    if let Some(s) = status.iter().find(|s| !s.checksum_ok) {
+        return Err(AppError::DBError(format!(
+            "Migration {} ({}) does not match the embedded migrations",
+            s.version, s.description
+        )));
+    }
+    let pending: Vec<String> = status
+        .iter()
+        .filter(|s| !s.applied)
+        .map(|s| s.version.to_string())
+        .collect();
+    if !pending.is_empty() {
+        return Err(AppError::DBError(format!(
+            "Pending migrations: {}, run `main migrate up` first",
+            pending.join(", ")
+        )));
+    }
+    Ok(())
+}
+
+async fn applied_migrations(
+    pool: &DbPool,
+) -> Result<Vec<sqlx::migrate::AppliedMigration>, AppError> {
+    let applied = with_pool!(pool, p => {
+        let mut conn = p.acquire().await?;
+        conn.ensure_migrations_table().await?;
+        conn.list_applied_migrations().await?
+    });
+    Ok(applied)
+}
+
+#[cfg(test)]
+mod tests {
+    use sqlx::postgres::PgPool;
+    #[cfg(feature = "sqlite")]
+    use sqlx::sqlite::SqlitePool;
+
+    use super::*;
+
+    // 执行所有迁移后逐个回滚, 验证每个版本的 up 和 down 脚本
+    async fn migrate_up_and_down(pool: DbPool) {
+        migrate_up(&pool).await.unwrap();
+        check_schema(&pool).await.unwrap();
+        let status = migration_status(&pool).await.unwrap();
+        assert!(status.iter().all(|s| s.applied && s.checksum_ok));
+
+        let mut reverted = vec![];
+        while let Some(version) = migrate_down(&pool).await.unwrap() {
+            reverted.push(version);
+        }
+        assert_eq!(reverted.len(), status.len());
+        migrate_up(&pool).await.unwrap();
+    }
+
+    // 不自动执行迁移, 从空数据库开始验证所有迁移脚本
+    #[sqlx::test(migrations = false)]
+    async fn migrations_are_applied(db_pool: PgPool) {
+        migrate_up_and_down(DbPool::Postgres(db_pool)).await;
+    }
+
+    #[cfg(feature = "sqlite")]
+    #[sqlx::test(migrations = false)]
+    async fn sqlite_migrations_are_applied(db_pool: SqlitePool) {
+        migrate_up_and_down(DbPool::Sqlite(db_pool)).await;
+    }
+
+    // 迁移之前手工建表的数据库, 第一次执行迁移时保留原有的表和数据
+    #[sqlx::test(migrations = false)]
+    async fn hand_built_schema_is_adopted(db_pool: PgPool) {
+        for sql in [
+            "create table teacher (id serial primary key, name varchar(100), picture_url varchar(255), profile varchar(1000))",
+            "create table course (id serial primary key, teacher_id int not null, name varchar(140) not null, \
+             time timestamp default now(), description varchar(2000), format varchar(140), structure varchar(100), \
+             duration varchar(40), price int, language varchar(60), level varchar(40))",
+            "insert into teacher (name, picture_url, profile) values ('Alice', '', '')",
+            "insert into course (teacher_id, name, price) values (1, 'Hand built', 99)",
+        ] {
+            sqlx::query(sql).execute(&db_pool).await.unwrap();
+        }
+
+        let pool = DbPool::Postgres(db_pool.clone());
+        migrate_up(&pool).await.unwrap();
+        check_schema(&pool).await.unwrap();
+        let (name, price, currency): (String, Option<i32>, Option<String>) =
+            sqlx::query_as("select name, price, currency from course where id = 1")
+                .fetch_one(&db_pool)
+                .await
+                .unwrap();
+        assert_eq!(name, "Hand built");
+        assert_eq!(price, Some(9900));
+        assert_eq!(currency.as_deref(), Some("CNY"));
+    }
+
+    // 价格与货币必须同时有值, 货币只能是 CURRENCIES 中的一种
+    const INVALID_PRICES: [&str; 3] = [
+        "insert into course (teacher_id, name, price) values (1, 'No currency', 100)",
+        "insert into course (teacher_id, name, currency) values (1, 'No price', 'CNY')",
+        "insert into course (teacher_id, name, price, currency) values (1, 'Bitcoin', 100, 'BTC')",
+    ];
+
+    #[sqlx::test(fixtures("handlers/fixtures/teachers.sql"))]
+    async fn course_currency_is_checked(db_pool: PgPool) {
+        for sql in INVALID_PRICES {
+            let err = sqlx::query(sql).execute(&db_pool).await.unwrap_err();
+            let kind = err.as_database_error().map(|e| e.kind());
+            assert!(matches!(kind, Some(sqlx::error::ErrorKind::CheckViolation)), "{}", sql);
+        }
+    }
+
+    #[cfg(feature = "sqlite")]
+    #[sqlx::test(migrator = "SQLITE_MIGRATOR", fixtures("handlers/fixtures/teachers.sql"))]
+    async fn sqlite_course_currency_is_checked(db_pool: SqlitePool) {
+        for sql in INVALID_PRICES {
+            let err = sqlx::query(sql).execute(&db_pool).await.unwrap_err();
+            let kind = err.as_database_error().map(|e| e.kind());
+            assert!(matches!(kind, Some(sqlx::error::ErrorKind::CheckViolation)), "{}", sql);
+        }
+    }
+
+    // 两种数据库的迁移版本需要保持一致
+    #[cfg(feature = "sqlite")]
+    #[test]
+    fn sqlite_migrations_match_postgres() {
+        let versions = |m: &Migrator| -> Vec<(i64, String)> {
+            m.iter()
+                .filter(|m| m.migration_type.is_up_migration())
+                .map(|m| (m.version, m.description.to_string()))
+                .collect()
+        };
+        assert_eq!(versions(&MIGRATOR), versions(&SQLITE_MIGRATOR));
+    }
+}
@@ -0,0 +1,112 @@
+use actix_web::web;
+use serde::{Deserialize, Serialize};
+use validator::Validate;
+
+use crate::errors::AppError;
+use crate::models::validation::not_blank;
+
+pub const MIN_PASSWORD_LENGTH: u64 = 8;
+
+
+// 用户角色, admin 可以管理所有老师的数据, student 只能管理自己的选课
+#[derive(Deserialize, Serialize, Debug, Clone, Copy, PartialEq)]
+#[serde(rename_all = "lowercase")]
+pub enum Role {
+    Teacher,
+    Admin,
+    Student,
+}
+
+impl Role {
+    // teacher 表中的 role 列, 学生保存在单独的 student 表中
+    pub fn from_db(role: &str) -> Self {
+        match role {
+            "admin" => Role::Admin,
+            _ => Role::Teacher,
+        }
+    }
+}
+
+// JWT 中携带的数据
+#[derive(Deserialize, Serialize, Debug, Clone)]
+pub struct Claims {
+    // role 为 student 时是学生 id, 否则是老师 id
+    pub sub: i32,
+    pub role: Role,
+    // 过期时间, unix 时间戳(秒)
+    pub exp: i64,
+    pub iat: i64,
+}
+
+
+#[derive(Deserialize, Debug, Clone)]
+pub struct LoginRequest {
+    pub email: String,
+    pub password: String,
+}
+
+
+// 老师自助注册, 校验规则与 CreateTeacher 一致
+#[derive(Deserialize, Debug, Clone, Validate)]
+pub struct RegisterTeacher {
+    #[validate(length(max = 100, message = "must be at most 100 characters"), custom(function = "not_blank"))]
+    pub name: String,
+    #[validate(url(message = "must be a valid URL"), length(max = 255, message = "must be at most 255 characters"))]
+    pub picture_url: String,
+    #[validate(length(max = 1000, message = "must be at most 1000 characters"))]
+    pub profile: String,
+    #[validate(email(message = "must be a valid email address"), length(max = 255, message = "must be at most 255 characters"))]
+    pub email: String,
+    #[validate(length(min = MIN_PASSWORD_LENGTH, message = "must be at least 8 characters"))]
+    pub password: String,
+}
+
+impl TryFrom<web::Json<RegisterTeacher>> for RegisterTeacher {
+    type Error = AppError;
+
+    fn try_from(teacher: web::Json<RegisterTeacher>) -> Result<Self, Self::Error> {
+        teacher.validate()?;
+        Ok(RegisterTeacher {
+            name: teacher.name.clone(),
+            picture_url: teacher.picture_url.clone(),
+            profile: teacher.profile.clone(),
+            email: teacher.email.trim().to_lowercase(),
+            password: teacher.password.clone(),
+        })
+    }
+}
+
+
+// 修改密码, 管理员修改其他老师的密码时可以不提供 current_password
+#[derive(Deserialize, Debug, Clone, Validate)]
+pub struct ChangePassword {
+    pub current_password: Option<String>,
+    #[validate(length(min = MIN_PASSWORD_LENGTH, message = "must be at least 8 characters"))]
+    pub new_password: String,
+}
+
+impl TryFrom<web::Json<ChangePassword>> for ChangePassword {
+    type Error = AppError;
+
+    fn try_from(change: web::Json<ChangePassword>) -> Result<Self, Self::Error> {
+        change.validate()?;
+        Ok(change.into_inner())
+    }
+}
+
+#[derive(Serialize, Debug, Clone)]
+pub struct TokenResponse {
+    pub access_token: String,
+    pub token_type: String,
+    // 有效期(秒)
+    pub expires_in: i64,
+}
+
+
+// 数据库中保存的登录凭证, 只在服务端使用, 不会序列化输出
+#[derive(Debug, Clone)]
+pub struct TeacherCredential {
+    pub id: i32,
+    pub password_hash: Option<String>,
+    pub role: Role,
+}
@@ -1,43 +1,213 @@
 use actix_web::web;
 use chrono::NaiveDateTime;
 use serde::{Deserialize, Serialize};
+use validator::{Validate, ValidationError};
 
-use crate::errors::AppError;
+use crate::errors::{AppError, FieldError};
+use crate::models::patch::{not_null, nullable};
+use crate::models::section::Section;
+use crate::models::validation::{not_blank, one_of};
 
-// 作为数据对象, 与数据库对接，使用 sqlx::FromRow 可以直接从数据库中查询转换为对象
+// 课程难度与形式的可选值
+pub const COURSE_LEVELS: [&str; 3] = ["Beginner", "Intermediate", "Advanced"];
+pub const COURSE_FORMATS: [&str; 4] = ["Video", "Live", "Text", "Mixed"];
+
+fn course_level(level: &str) -> Result<(), ValidationError> {
+    one_of(level, &COURSE_LEVELS)
+}
+
+fn course_format(format: &str) -> Result<(), ValidationError> {
+    one_of(format, &COURSE_FORMATS)
+}
+
+// 支持的货币, ISO 4217 代码
+pub const CURRENCIES: [&str; 6] = ["CNY", "USD", "EUR", "GBP", "HKD", "JPY"];
+
+// 金额, amount 以最小货币单位表示, 如 CNY 的 1999 表示 19.99 元, JPY 没有辅币单位
+// 使用整数避免浮点数的精度问题, 数据库中保存为 price 与 currency 两列
+#[derive(Debug, Clone, PartialEq, Eq, Serialize, Deserialize)]
+pub struct Money {
+    pub amount: i32,
+    pub currency: String,
+}
+
+impl Money {
+    pub fn new(amount: i32, currency: impl Into<String>) -> Self {
+        Money {
+            amount,
+            currency: currency.into(),
+        }
+    }
+
+    // 货币的小数位数
+    pub fn minor_digits(&self) -> u32 {
+        match self.currency.as_str() {
+            "JPY" => 0,
+            _ => 2,
+        }
+    }
+}
+
+fn valid_money(money: &Money) -> Result<(), ValidationError> {
+    if money.amount < 0 {
+        return Err(ValidationError::new("range").with_message("amount must not be negative".into()));
+    }
+    one_of(&money.currency, &CURRENCIES)
+}
+
+// 作为数据对象, 与数据库对接，通过 CourseRow 从数据库中查询转换为对象
 // 由于转换为数据对象后有可能需要序列化输出，因此需要实现 Serialize
 // 不会存在反序列化为对象的情况，因此去掉反序列化 Deserilized
-#[derive(Debug, Serialize, Clone, sqlx::FromRow)]
+#[derive(Debug, Serialize, Clone)]
 pub struct Course {
     pub teacher_id: i32,
     pub id: i32,
     pub name: String, 
     pub time: Option<NaiveDateTime>,
 
+    pub description: Option<String>,
+    pub format: Option<String>,
+    pub structure: Option<String>,
+    pub duration: Option<String>,
+    pub price: Option<Money>,
+    pub language: Option<String>,
+    pub level: Option<String>,
+    // 名额, 为空时不限制选课人数
+    pub capacity: Option<i32>,
+    // 版本号, 每次修改或删除后加一, 作为响应头 ETag 返回
+    pub version: i32,
+    // 移入回收站的时间, 只有回收站中的课程才有值
+    #[serde(skip_serializing_if = "Option::is_none")]
+    pub deleted_at: Option<NaiveDateTime>,
+}
+
+// 数据库中 course 表的一行, 价格拆分为 price 与 currency 两列
+#[derive(Debug, Clone, sqlx::FromRow)]
+pub struct CourseRow {
+    pub teacher_id: i32,
+    pub id: i32,
+    pub name: String,
+    pub time: Option<NaiveDateTime>,
     pub description: Option<String>,
     pub format: Option<String>,
     pub structure: Option<String>,
     pub duration: Option<String>,
     pub price: Option<i32>,
+    pub currency: Option<String>,
     pub language: Option<String>,
     pub level: Option<String>,
+    pub capacity: Option<i32>,
+    pub version: i32,
+    pub deleted_at: Option<NaiveDateTime>,
+}
+
+// 数据库的 check 约束保证 price 与 currency 同时为空或同时有值, 不一致时视为数据错误
+impl TryFrom<CourseRow> for Course {
+    type Error = sqlx::Error;
+
+    fn try_from(row: CourseRow) -> Result<Self, Self::Error> {
+        let price = match (row.price, row.currency) {
+            (Some(amount), Some(currency)) => Some(Money::new(amount, currency)),
+            (None, None) => None,
+            _ => {
+                return Err(sqlx::Error::ColumnDecode {
+                    index: "currency".into(),
+                    source: format!("course {} must have both price and currency or neither", row.id).into(),
+                })
+            }
+        };
+        Ok(Course {
+            teacher_id: row.teacher_id,
+            id: row.id,
+            name: row.name,
+            time: row.time,
+            description: row.description,
+            format: row.format,
+            structure: row.structure,
+            duration: row.duration,
+            price,
+            language: row.language,
+            level: row.level,
+            capacity: row.capacity,
+            version: row.version,
+            deleted_at: row.deleted_at,
+        })
+    }
+}
+
+// 运行时的查询(query_as 与 QueryBuilder)直接转换为 Course
+impl<'r, R: sqlx::Row> sqlx::FromRow<'r, R> for Course
+where
+    CourseRow: sqlx::FromRow<'r, R>,
+{
+    fn from_row(row: &'r R) -> Result<Self, sqlx::Error> {
+        CourseRow::from_row(row).and_then(Course::try_from)
+    }
+}
+
+// 获取课程详情的参数, include 为逗号分隔的关联数据, 目前只支持 sections
+#[derive(Deserialize, Debug, Clone, Default)]
+pub struct CourseDetailQuery {
+    pub include: Option<String>,
+}
+
+impl CourseDetailQuery {
+    pub const INCLUDES: [&'static str; 1] = ["sections"];
+
+    // 是否需要返回章节与课时, 不支持的值返回校验错误
+    pub fn include_sections(&self) -> Result<bool, AppError> {
+        let mut sections = false;
+        for item in self.include.iter().flat_map(|s| s.split(',')).map(str::trim) {
+            match item {
+                "" => {}
+                "sections" => sections = true,
+                _ => {
+                    return Err(AppError::invalid_field(
+                        "include",
+                        format!("must be one of: {}", Self::INCLUDES.join(", ")),
+                    ))
+                }
+            }
+        }
+        Ok(sections)
+    }
+}
+
+// 课程详情, ?include=sections 时包含章节与课时
+#[derive(Debug, Serialize, Clone)]
+pub struct CourseDetail {
+    #[serde(flatten)]
+    pub course: Course,
+    #[serde(skip_serializing_if = "Option::is_none")]
+    pub sections: Option<Vec<Section>>,
 }
 
 // 作为客户端创建课程的数据接收对象，需要反序列化 Deserilized
 //
-#[derive(Deserialize, Debug, Clone)]
+// 校验规则与数据库中字段的长度保持一致
+#[derive(Deserialize, Debug, Clone, Validate)]
 pub struct CreateCourse {
     // id 在数据库生成，不需要传入，
     // time 在数据库生成，不需要传入
     pub teacher_id: i32,
+    #[validate(length(max = 140, message = "must be at most 140 characters"), custom(function = "not_blank"))]
     pub name: String,
+    #[validate(length(max = 2000, message = "must be at most 2000 characters"))]
     pub description: Option<String>,
+    #[validate(custom(function = "course_format"))]
     pub format: Option<String>,
+    #[validate(length(max = 100, message = "must be at most 100 characters"))]
     pub structure: Option<String>,
+    #[validate(length(max = 40, message = "must be at most 40 characters"))]
     pub duration: Option<String>,
-    pub price: Option<i32>,
+    #[validate(custom(function = "valid_money"))]
+    pub price: Option<Money>,
+    #[validate(length(max = 60, message = "must be at most 60 characters"))]
     pub language: Option<String>,
+    #[validate(custom(function = "course_level"))]
     pub level: Option<String>,
+    #[validate(range(min = 0, message = "must not be negative"))]
+    pub capacity: Option<i32>,
 }
 
 // impl From<web::Json<CreateCourse>> for CreateCourse {
@@ -63,6 +233,7 @@ impl TryFrom<web::Json<CreateCourse>> for CreateCourse {
     type Error = AppError;
 
     fn try_from(course: web::Json<CreateCourse>) -> Result<Self, Self::Error> {
+        course.validate()?;
         Ok(CreateCourse {
             teacher_id: course.teacher_id,
             name: course.name.clone(),
@@ -70,38 +241,467 @@ impl TryFrom<web::Json<CreateCourse>> for CreateCourse {
             format: course.format.clone(),
             structure: course.structure.clone(),
             duration: course.duration.clone(),
-            price: course.price,
+            price: course.price.clone(),
             language: course.language.clone(),
             level: course.level.clone(),
+            capacity: course.capacity,
         })
     }
 }
 
-#[derive(Deserialize, Debug, Clone)]
+#[derive(Deserialize, Debug, Clone, Validate)]
 pub struct UpdateCourse {
+    #[validate(length(max = 140, message = "must be at most 140 characters"), custom(function = "not_blank"))]
     pub name: Option<String>,
+    #[validate(length(max = 2000, message = "must be at most 2000 characters"))]
     pub description: Option<String>,
+    #[validate(custom(function = "course_format"))]
     pub format: Option<String>,
+    #[validate(length(max = 100, message = "must be at most 100 characters"))]
     pub structure: Option<String>,
+    #[validate(length(max = 40, message = "must be at most 40 characters"))]
     pub duration: Option<String>,
-    pub price: Option<i32>,
+    #[validate(custom(function = "valid_money"))]
+    pub price: Option<Money>,
+    #[validate(length(max = 60, message = "must be at most 60 characters"))]
     pub language: Option<String>,
+    #[validate(custom(function = "course_level"))]
     pub level: Option<String>,
+    #[validate(range(min = 0, message = "must not be negative"))]
+    pub capacity: Option<i32>,
 }
 
 
-impl From<web::Json<UpdateCourse>> for UpdateCourse {
-    fn from(course: web::Json<UpdateCourse>) -> Self {
-        UpdateCourse {
+impl TryFrom<web::Json<UpdateCourse>> for UpdateCourse {
+    type Error = AppError;
+
+    fn try_from(course: web::Json<UpdateCourse>) -> Result<Self, Self::Error> {
+        course.validate()?;
+        Ok(UpdateCourse {
             name: course.name.clone(),
             description: course.description.clone(),
             format: course.format.clone(),
             structure: course.structure.clone(),
             duration: course.duration.clone(),
-            price: course.price,
+            price: course.price.clone(),
             language: course.language.clone(),
             level: course.level.clone(),
+            capacity: course.capacity,
+        })
+    }
+}
+
+
+// PATCH 请求的数据对象, 不传入的字段保持不变, 传入 null 时清空, name 不能为 null
+// PUT 请求的 UpdateCourse 也转换为 PatchCourse 保存, 不传入的字段同样保持不变
+#[derive(Deserialize, Debug, Clone, Default, Validate)]
+#[serde(default)]
+pub struct PatchCourse {
+    #[serde(deserialize_with = "nullable")]
+    #[validate(length(max = 140, message = "must be at most 140 characters"), custom(function = "not_blank"))]
+    pub name: Option<Option<String>>,
+    #[serde(deserialize_with = "nullable")]
+    #[validate(length(max = 2000, message = "must be at most 2000 characters"))]
+    pub description: Option<Option<String>>,
+    #[serde(deserialize_with = "nullable")]
+    #[validate(custom(function = "course_format"))]
+    pub format: Option<Option<String>>,
+    #[serde(deserialize_with = "nullable")]
+    #[validate(length(max = 100, message = "must be at most 100 characters"))]
+    pub structure: Option<Option<String>>,
+    #[serde(deserialize_with = "nullable")]
+    #[validate(length(max = 40, message = "must be at most 40 characters"))]
+    pub duration: Option<Option<String>>,
+    #[serde(deserialize_with = "nullable")]
+    #[validate(custom(function = "valid_money"))]
+    pub price: Option<Option<Money>>,
+    #[serde(deserialize_with = "nullable")]
+    #[validate(length(max = 60, message = "must be at most 60 characters"))]
+    pub language: Option<Option<String>>,
+    #[serde(deserialize_with = "nullable")]
+    #[validate(custom(function = "course_level"))]
+    pub level: Option<Option<String>>,
+    #[serde(deserialize_with = "nullable")]
+    #[validate(range(min = 0, message = "must not be negative"))]
+    pub capacity: Option<Option<i32>>,
+}
+
+impl TryFrom<web::Json<PatchCourse>> for PatchCourse {
+    type Error = AppError;
+
+    fn try_from(course: web::Json<PatchCourse>) -> Result<Self, Self::Error> {
+        not_null("name", &course.name)?;
+        course.validate()?;
+        Ok(course.into_inner())
+    }
+}
+
+impl From<UpdateCourse> for PatchCourse {
+    fn from(course: UpdateCourse) -> Self {
+        PatchCourse {
+            name: course.name.map(Some),
+            description: course.description.map(Some),
+            format: course.format.map(Some),
+            structure: course.structure.map(Some),
+            duration: course.duration.map(Some),
+            price: course.price.map(Some),
+            language: course.language.map(Some),
+            level: course.level.map(Some),
+            capacity: course.capacity.map(Some),
+        }
+    }
+}
+
+impl PatchCourse {
+    // 合并到原始的课程中, 没有传入的字段保留原值
+    pub fn apply(self, course: &mut Course) {
+        if let Some(Some(name)) = self.name {
+            course.name = name;
+        }
+        let merge = |patch: Option<Option<String>>, current: &mut Option<String>| {
+            if let Some(value) = patch {
+                *current = value;
+            }
+        };
+        merge(self.description, &mut course.description);
+        merge(self.format, &mut course.format);
+        merge(self.structure, &mut course.structure);
+        merge(self.duration, &mut course.duration);
+        if let Some(price) = self.price {
+            course.price = price;
+        }
+        merge(self.language, &mut course.language);
+        merge(self.level, &mut course.level);
+        if let Some(capacity) = self.capacity {
+            course.capacity = capacity;
         }
     }
 }
 
+
+// 批量导入时的一行课程, 老师由路径参数指定, 其他列与 CreateCourse 相同
+// 导出的数据中多出的 id、time 等列会被忽略, 因此导出的文件可以直接导入
+#[derive(Deserialize, Debug, Clone)]
+pub struct CourseRecord {
+    pub name: String,
+    pub description: Option<String>,
+    pub format: Option<String>,
+    pub structure: Option<String>,
+    pub duration: Option<String>,
+    pub price: Option<Money>,
+    pub language: Option<String>,
+    pub level: Option<String>,
+    pub capacity: Option<i32>,
+}
+
+impl CourseRecord {
+    // 转换为 CreateCourse 后使用相同的校验规则
+    pub fn into_course(self, teacher_id: i32) -> CreateCourse {
+        CreateCourse {
+            teacher_id,
+            name: self.name,
+            description: self.description,
+            format: self.format,
+            structure: self.structure,
+            duration: self.duration,
+            price: self.price,
+            language: self.language,
+            level: self.level,
+            capacity: self.capacity,
+        }
+    }
+}
+
+// 导入与导出的文件格式
+#[derive(Deserialize, Debug, Clone, Copy, Default, PartialEq)]
+#[serde(rename_all = "lowercase")]
+pub enum CourseFileFormat {
+    #[default]
+    Csv,
+    // 每行一个 Json 对象
+    Ndjson,
+}
+
+impl CourseFileFormat {
+    pub fn content_type(self) -> &'static str {
+        match self {
+            CourseFileFormat::Csv => "text/csv; charset=utf-8",
+            CourseFileFormat::Ndjson => "application/x-ndjson",
+        }
+    }
+
+    pub fn extension(self) -> &'static str {
+        match self {
+            CourseFileFormat::Csv => "csv",
+            CourseFileFormat::Ndjson => "ndjson",
+        }
+    }
+
+    // 根据请求头 Content-Type 判断导入的格式, 忽略 charset 等参数
+    pub fn from_content_type(content_type: &str) -> Option<Self> {
+        let essence = content_type.split(';').next().unwrap_or_default().trim();
+        match essence.to_ascii_lowercase().as_str() {
+            "text/csv" => Some(CourseFileFormat::Csv),
+            "application/x-ndjson" | "application/jsonl" => Some(CourseFileFormat::Ndjson),
+            _ => None,
+        }
+    }
+}
+
+// POST /courses/{teacher_id}/import 的参数, dry_run 为 true 时只校验不保存
+#[derive(Deserialize, Debug, Clone, Default)]
+pub struct ImportCoursesQuery {
+    #[serde(default)]
+    pub dry_run: bool,
+}
+
+// 导入的结果, dry_run 时 imported 为 0, errors 中为每一行的错误, 字段名如 rows[3].price
+#[derive(Serialize, Debug, Clone, PartialEq)]
+pub struct ImportReport {
+    pub dry_run: bool,
+    // 通过校验的行数
+    pub valid: usize,
+    pub imported: u64,
+    pub errors: Vec<FieldError>,
+}
+
+// GET /courses/{teacher_id}/export 的参数
+#[derive(Deserialize, Debug, Clone, Default)]
+pub struct ExportCoursesQuery {
+    #[serde(default)]
+    pub format: CourseFileFormat,
+}
+
+
+// 课程列表的排序字段
+#[derive(Deserialize, Debug, Clone, Copy, Default, PartialEq)]
+#[serde(rename_all = "lowercase")]
+pub enum CourseSort {
+    #[default]
+    Time,
+    Name,
+    Price,
+}
+
+// 游标中时间的格式
+const CURSOR_TIME_FORMAT: &str = "%Y-%m-%dT%H:%M:%S%.f";
+
+// 排序字段的值, 游标分页时与 id 一起确定位置
+#[derive(Debug, Clone, PartialEq, Eq, PartialOrd, Ord)]
+pub enum SortKey {
+    Time(NaiveDateTime),
+    Name(String),
+    Price(i32),
+}
+
+impl CourseSort {
+    // 空值的处理与数据库中的排序表达式一致: 时间为空视为 epoch, 价格为空视为 0
+    // 按价格排序时 CourseQuery 要求指定货币, 只比较同一货币的金额
+    pub fn key(self, course: &Course) -> SortKey {
+        match self {
+            CourseSort::Time => SortKey::Time(course.time.unwrap_or_default()),
+            CourseSort::Name => SortKey::Name(course.name.clone()),
+            CourseSort::Price => SortKey::Price(course.price.as_ref().map_or(0, |p| p.amount)),
+        }
+    }
+
+    fn as_str(self) -> &'static str {
+        match self {
+            CourseSort::Time => "time",
+            CourseSort::Name => "name",
+            CourseSort::Price => "price",
+        }
+    }
+
+    // 游标格式为 <排序字段>:<排序方向>|<排序值>|<id>, 如 price:desc|9900|3
+    pub fn cursor(self, order: SortOrder, course: &Course) -> String {
+        let key = match self.key(course) {
+            SortKey::Time(time) => time.format(CURSOR_TIME_FORMAT).to_string(),
+            SortKey::Name(name) => name,
+            SortKey::Price(price) => price.to_string(),
+        };
+        format!("{}:{}|{}|{}", self.as_str(), order.as_str(), key, course.id)
+    }
+
+    // 游标只能用于生成它时的排序字段和方向, 否则会跳过或重复课程
+    pub fn parse_cursor(self, order: SortOrder, cursor: &str) -> Result<(SortKey, i32), AppError> {
+        let invalid = || AppError::invalid_field("cursor", "Invalid cursor");
+        let (sort, rest) = cursor.split_once('|').ok_or_else(invalid)?;
+        let (key, id) = rest.rsplit_once('|').ok_or_else(invalid)?;
+        if sort != format!("{}:{}", self.as_str(), order.as_str()) {
+            return Err(AppError::invalid_field(
+                "cursor",
+                "does not match the sort and order of the request",
+            ));
+        }
+        let id = id.parse::<i32>().map_err(|_| invalid())?;
+        let key = match self {
+            CourseSort::Time => SortKey::Time(
+                NaiveDateTime::parse_from_str(key, CURSOR_TIME_FORMAT).map_err(|_| invalid())?,
+            ),
+            CourseSort::Name => SortKey::Name(key.to_string()),
+            CourseSort::Price => SortKey::Price(key.parse::<i32>().map_err(|_| invalid())?),
+        };
+        Ok((key, id))
+    }
+}
+
+#[derive(Deserialize, Debug, Clone, Copy, Default, PartialEq)]
+#[serde(rename_all = "lowercase")]
+pub enum SortOrder {
+    #[default]
+    Asc,
+    Desc,
+}
+
+impl SortOrder {
+    fn as_str(self) -> &'static str {
+        match self {
+            SortOrder::Asc => "asc",
+            SortOrder::Desc => "desc",
+        }
+    }
+}
+
+// 获取老师课程列表时的查询参数, 如: ?limit=10&sort=price&order=desc&currency=CNY&level=Beginner
+// 提供 cursor 时使用游标分页, 忽略 offset
+#[derive(Deserialize, Debug, Clone, Default)]
+pub struct CourseQuery {
+    pub limit: Option<i64>,
+    pub offset: Option<i64>,
+    pub cursor: Option<String>,
+    #[serde(default)]
+    pub sort: CourseSort,
+    #[serde(default)]
+    pub order: SortOrder,
+    pub level: Option<String>,
+    pub language: Option<String>,
+    pub format: Option<String>,
+    // 只返回该货币定价的课程, 按价格排序或过滤时必须指定, 不同货币的金额不能直接比较
+    pub currency: Option<String>,
+    // 价格区间, 以 currency 的最小货币单位表示
+    pub min_price: Option<i32>,
+    pub max_price: Option<i32>,
+}
+
+impl CourseQuery {
+    pub const DEFAULT_LIMIT: i64 = 20;
+    pub const MAX_LIMIT: i64 = 100;
+
+    pub fn limit(&self) -> i64 {
+        self.limit.unwrap_or(Self::DEFAULT_LIMIT)
+    }
+
+    pub fn offset(&self) -> i64 {
+        self.offset.unwrap_or(0)
+    }
+
+    pub fn validate(&self) -> Result<(), AppError> {
+        if !(1..=Self::MAX_LIMIT).contains(&self.limit()) {
+            return Err(AppError::invalid_field(
+                "limit",
+                format!("must be between 1 and {}", Self::MAX_LIMIT),
+            ));
+        }
+        if self.offset() < 0 {
+            return Err(AppError::invalid_field("offset", "must not be negative"));
+        }
+        match &self.currency {
+            Some(currency) if !CURRENCIES.contains(&currency.as_str()) => {
+                return Err(AppError::invalid_field(
+                    "currency",
+                    format!("must be one of: {}", CURRENCIES.join(", ")),
+                ));
+            }
+            None if self.sort == CourseSort::Price
+                || self.min_price.is_some()
+                || self.max_price.is_some() =>
+            {
+                return Err(AppError::invalid_field(
+                    "currency",
+                    "is required when sorting or filtering by price",
+                ));
+            }
+            _ => {}
+        }
+        if let (Some(min), Some(max)) = (self.min_price, self.max_price) {
+            if min > max {
+                return Err(AppError::invalid_field(
+                    "min_price",
+                    "must not be greater than max_price",
+                ));
+            }
+        }
+        Ok(())
+    }
+}
+
+// 分页返回的课程列表
+#[derive(Serialize, Debug, Clone)]
+pub struct CoursePage {
+    pub items: Vec<Course>,
+    // 满足过滤条件的课程总数
+    pub total: i64,
+    pub limit: i64,
+    pub offset: i64,
+    // 存在下一页时返回, 作为下一次请求的 cursor 参数
+    pub next_cursor: Option<String>,
+}
+
+// 跨老师搜索课程的查询参数, 如: ?q=rust web&level=Beginner
+#[derive(Deserialize, Debug, Clone, Default)]
+pub struct CourseSearchQuery {
+    pub q: String,
+    pub level: Option<String>,
+    pub language: Option<String>,
+    pub limit: Option<i64>,
+    pub offset: Option<i64>,
+}
+
+impl CourseSearchQuery {
+    pub fn limit(&self) -> i64 {
+        self.limit.unwrap_or(CourseQuery::DEFAULT_LIMIT)
+    }
+
+    pub fn offset(&self) -> i64 {
+        self.offset.unwrap_or(0)
+    }
+
+    pub fn validate(&self) -> Result<(), AppError> {
+        if self.q.trim().is_empty() {
+            return Err(AppError::invalid_field("q", "must not be empty"));
+        }
+        if !(1..=CourseQuery::MAX_LIMIT).contains(&self.limit()) {
+            return Err(AppError::invalid_field(
+                "limit",
+                format!("must be between 1 and {}", CourseQuery::MAX_LIMIT),
+            ));
+        }
+        if self.offset() < 0 {
+            return Err(AppError::invalid_field("offset", "must not be negative"));
+        }
+        Ok(())
+    }
+}
+
+// 单条搜索结果, 高亮部分使用 <mark></mark> 包裹
+#[derive(Serialize, Debug, Clone, sqlx::FromRow)]
+pub struct CourseSearchHit {
+    #[serde(flatten)]
+    #[sqlx(flatten)]
+    pub course: Course,
+    // 相关度, 越大越相关
+    pub rank: f32,
+    pub name_highlight: String,
+    // 从描述或课程结构中截取的片段
+    pub snippet: String,
+}
+
+#[derive(Serialize, Debug, Clone)]
+pub struct CourseSearchPage {
+    pub items: Vec<CourseSearchHit>,
+    pub total: i64,
+    pub limit: i64,
+    pub offset: i64,
+}
+
@@ -0,0 +1,55 @@
+use serde::Serialize;
+
+use crate::config::StorageBackend;
+
+
+#[derive(Serialize, Debug, Clone, Copy, PartialEq)]
+#[serde(rename_all = "lowercase")]
+pub enum HealthStatus {
+    Ok,
+    Degraded,
+}
+
+// /health/ready 的返回值, status 为 degraded 时状态码为 503
+#[derive(Serialize, Debug, Clone)]
+pub struct ReadinessReport {
+    pub status: HealthStatus,
+    // 收到关闭信号后为 true, 此时 status 一定为 degraded
+    pub shutting_down: bool,
+    pub storage: StorageBackend,
+    // 以下检查只在使用数据库存储时返回
+    #[serde(skip_serializing_if = "Option::is_none")]
+    pub database: Option<DatabaseCheck>,
+    #[serde(skip_serializing_if = "Option::is_none")]
+    pub migrations: Option<MigrationCheck>,
+    #[serde(skip_serializing_if = "Option::is_none")]
+    pub pool: Option<PoolCheck>,
+}
+
+#[derive(Serialize, Debug, Clone)]
+pub struct DatabaseCheck {
+    pub ok: bool,
+    pub latency_ms: u64,
+    #[serde(skip_serializing_if = "Option::is_none")]
+    pub error: Option<String>,
+}
+
+#[derive(Serialize, Debug, Clone)]
+pub struct MigrationCheck {
+    // 所有迁移都已执行且脚本未被修改
+    pub ok: bool,
+    pub pending: Vec<i64>,
+    pub modified: Vec<i64>,
+    #[serde(skip_serializing_if = "Option::is_none")]
+    pub error: Option<String>,
+}
+
+// 连接池的使用情况, 只用于展示, 不影响 status
+#[derive(Serialize, Debug, Clone)]
+pub struct PoolCheck {
+    pub size: u32,
+    pub idle: usize,
+    pub max_size: u32,
+    // 正在使用的连接数 / 最大连接数
+    pub saturation: f64,
+}
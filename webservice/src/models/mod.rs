@@ -1,2 +1,8 @@
 pub mod course;
+pub mod section;
+pub mod student;
 pub mod teacher;
+pub mod auth;
+pub mod validation;
+pub mod patch;
+pub mod health;
@@ -0,0 +1,47 @@
+use serde::{Deserialize, Deserializer};
+
+use crate::errors::AppError;
+
+// PATCH 请求中字段的三种状态, 与 JSON Merge Patch (RFC 7386) 的语义一致:
+// 不传入时为 None(保持不变), 传入 null 时为 Some(None)(清空), 传入值时为 Some(Some(value))(修改)
+//
+// serde 默认把 null 和不传入都反序列化为 None, 需要配合 #[serde(default, deserialize_with = "nullable")] 使用
+pub fn nullable<'de, T, D>(deserializer: D) -> Result<Option<Option<T>>, D::Error>
+where
+    T: Deserialize<'de>,
+    D: Deserializer<'de>,
+{
+    Option::<T>::deserialize(deserializer).map(Some)
+}
+
+// 数据库中不能为空的字段, 传入 null 时返回校验错误
+pub fn not_null<T>(field: &str, value: &Option<Option<T>>) -> Result<(), AppError> {
+    match value {
+        Some(None) => Err(AppError::invalid_field(field, "must not be null")),
+        _ => Ok(()),
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    #[derive(Deserialize)]
+    struct Patch {
+        #[serde(default, deserialize_with = "nullable")]
+        level: Option<Option<String>>,
+    }
+
+    #[test]
+    fn nullable_distinguishes_absent_and_null() {
+        let absent: Patch = serde_json::from_str("{}").unwrap();
+        assert_eq!(absent.level, None);
+        let null: Patch = serde_json::from_str(r#"{ "level": null }"#).unwrap();
+        assert_eq!(null.level, Some(None));
+        let value: Patch = serde_json::from_str(r#"{ "level": "Beginner" }"#).unwrap();
+        assert_eq!(value.level, Some(Some("Beginner".into())));
+
+        assert!(not_null("level", &null.level).is_err());
+        assert!(not_null("level", &value.level).is_ok());
+    }
+}
@@ -0,0 +1,198 @@
+use std::collections::HashSet;
+
+use actix_web::web;
+use serde::{Deserialize, Serialize};
+use validator::{Validate, ValidationError};
+
+use crate::errors::AppError;
+use crate::models::validation::not_blank;
+
+// 课程的章节, position 为从 1 开始的连续序号, lessons 按 position 排序
+#[derive(Debug, Serialize, Clone, PartialEq)]
+pub struct Section {
+    pub course_id: i32,
+    pub id: i32,
+    pub title: String,
+    pub position: i32,
+    pub lessons: Vec<Lesson>,
+}
+
+// 数据库中 section 表的一行, 课时需要另外查询
+#[derive(Debug, Clone, sqlx::FromRow)]
+pub struct SectionRow {
+    pub course_id: i32,
+    pub id: i32,
+    pub title: String,
+    pub position: i32,
+}
+
+impl From<SectionRow> for Section {
+    fn from(row: SectionRow) -> Self {
+        Section {
+            course_id: row.course_id,
+            id: row.id,
+            title: row.title,
+            position: row.position,
+            lessons: Vec::new(),
+        }
+    }
+}
+
+impl Section {
+    // 把按 position 排序的课时放入所属的章节
+    pub fn assemble(sections: Vec<SectionRow>, lessons: Vec<Lesson>) -> Vec<Section> {
+        let mut sections: Vec<Section> = sections.into_iter().map(Section::from).collect();
+        for lesson in lessons {
+            if let Some(section) = sections.iter_mut().find(|s| s.id == lesson.section_id) {
+                section.lessons.push(lesson);
+            }
+        }
+        sections
+    }
+}
+
+// 章节中的课时, position 为章节内从 1 开始的连续序号
+#[derive(Debug, Serialize, Clone, PartialEq, sqlx::FromRow)]
+pub struct Lesson {
+    pub section_id: i32,
+    pub id: i32,
+    pub title: String,
+    pub content: Option<String>,
+    pub duration_minutes: Option<i32>,
+    pub position: i32,
+}
+
+#[derive(Deserialize, Debug, Clone, Validate)]
+pub struct CreateSection {
+    #[validate(length(max = 140, message = "must be at most 140 characters"), custom(function = "not_blank"))]
+    pub title: String,
+    // 插入的位置, 之后的章节依次后移; 不传入或大于章节数时添加到最后
+    #[validate(range(min = 1, message = "must be at least 1"))]
+    pub position: Option<i32>,
+}
+
+impl TryFrom<web::Json<CreateSection>> for CreateSection {
+    type Error = AppError;
+
+    fn try_from(section: web::Json<CreateSection>) -> Result<Self, Self::Error> {
+        section.validate()?;
+        Ok(section.into_inner())
+    }
+}
+
+// 修改章节的标题, 调整顺序使用 Reorder
+#[derive(Deserialize, Debug, Clone, Validate)]
+pub struct UpdateSection {
+    #[validate(length(max = 140, message = "must be at most 140 characters"), custom(function = "not_blank"))]
+    pub title: String,
+}
+
+impl TryFrom<web::Json<UpdateSection>> for UpdateSection {
+    type Error = AppError;
+
+    fn try_from(section: web::Json<UpdateSection>) -> Result<Self, Self::Error> {
+        section.validate()?;
+        Ok(section.into_inner())
+    }
+}
+
+#[derive(Deserialize, Debug, Clone, Validate)]
+pub struct CreateLesson {
+    #[validate(length(max = 140, message = "must be at most 140 characters"), custom(function = "not_blank"))]
+    pub title: String,
+    #[validate(length(max = 10000, message = "must be at most 10000 characters"))]
+    pub content: Option<String>,
+    #[validate(range(min = 0, message = "must not be negative"))]
+    pub duration_minutes: Option<i32>,
+    // 与 CreateSection 的 position 相同
+    #[validate(range(min = 1, message = "must be at least 1"))]
+    pub position: Option<i32>,
+}
+
+impl TryFrom<web::Json<CreateLesson>> for CreateLesson {
+    type Error = AppError;
+
+    fn try_from(lesson: web::Json<CreateLesson>) -> Result<Self, Self::Error> {
+        lesson.validate()?;
+        Ok(lesson.into_inner())
+    }
+}
+
+// 与 UpdateCourse 一样, 不传入的字段保持不变
+#[derive(Deserialize, Debug, Clone, Validate)]
+pub struct UpdateLesson {
+    #[validate(length(max = 140, message = "must be at most 140 characters"), custom(function = "not_blank"))]
+    pub title: Option<String>,
+    #[validate(length(max = 10000, message = "must be at most 10000 characters"))]
+    pub content: Option<String>,
+    #[validate(range(min = 0, message = "must not be negative"))]
+    pub duration_minutes: Option<i32>,
+}
+
+impl TryFrom<web::Json<UpdateLesson>> for UpdateLesson {
+    type Error = AppError;
+
+    fn try_from(lesson: web::Json<UpdateLesson>) -> Result<Self, Self::Error> {
+        lesson.validate()?;
+        Ok(lesson.into_inner())
+    }
+}
+
+impl UpdateLesson {
+    // 合并到原始的课时中, 没有传入的字段保留原值
+    pub fn apply(self, lesson: &mut Lesson) {
+        if let Some(title) = self.title {
+            lesson.title = title;
+        }
+        if let Some(content) = self.content {
+            lesson.content = Some(content);
+        }
+        if let Some(duration_minutes) = self.duration_minutes {
+            lesson.duration_minutes = Some(duration_minutes);
+        }
+    }
+}
+
+fn distinct_ids(ids: &[i32]) -> Result<(), ValidationError> {
+    let mut seen = HashSet::new();
+    if !ids.iter().all(|id| seen.insert(*id)) {
+        return Err(ValidationError::new("distinct").with_message("must not contain duplicates".into()));
+    }
+    Ok(())
+}
+
+// 调整章节或课时的顺序, ids 为新的顺序, 必须恰好包含课程的所有章节(或章节的所有课时)
+#[derive(Deserialize, Debug, Clone, Validate)]
+pub struct Reorder {
+    #[validate(custom(function = "distinct_ids"))]
+    pub ids: Vec<i32>,
+}
+
+impl TryFrom<web::Json<Reorder>> for Reorder {
+    type Error = AppError;
+
+    fn try_from(order: web::Json<Reorder>) -> Result<Self, Self::Error> {
+        order.validate()?;
+        Ok(order.into_inner())
+    }
+}
+
+impl Reorder {
+    // ids 与现有的 id 是否为同一组, 顺序不限
+    pub fn matches(&self, current: &mut [i32]) -> bool {
+        let mut ids = self.ids.clone();
+        ids.sort_unstable();
+        current.sort_unstable();
+        ids == current
+    }
+
+    // ids 与现有的 id 不是同一组时返回的错误, resource 为 section 或 lesson
+    pub fn mismatch(resource: &str) -> AppError {
+        AppError::invalid_field("ids", format!("must list every {} exactly once", resource))
+    }
+}
+
+// 新增时插入的位置, count 为现有的数量, 超出范围时添加到最后
+pub fn insert_position(position: Option<i32>, count: i32) -> i32 {
+    position.map_or(count + 1, |p| p.clamp(1, count + 1))
+}
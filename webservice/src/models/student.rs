@@ -0,0 +1,100 @@
+use actix_web::web;
+use chrono::NaiveDateTime;
+use serde::{Deserialize, Serialize};
+use validator::Validate;
+
+use crate::errors::AppError;
+use crate::models::auth::MIN_PASSWORD_LENGTH;
+use crate::models::course::Course;
+use crate::models::validation::not_blank;
+
+
+// 学生, 密码只保存在 StudentCredential 中
+#[derive(Serialize, Debug, Clone, PartialEq, sqlx::FromRow)]
+pub struct Student {
+    pub id: i32,
+    pub name: String,
+    pub email: String,
+    pub time: Option<NaiveDateTime>,
+}
+
+// 学生自助注册, 校验规则与数据库中字段的长度保持一致
+#[derive(Deserialize, Debug, Clone, Validate)]
+pub struct RegisterStudent {
+    #[validate(length(max = 100, message = "must be at most 100 characters"), custom(function = "not_blank"))]
+    pub name: String,
+    #[validate(email(message = "must be a valid email address"), length(max = 255, message = "must be at most 255 characters"))]
+    pub email: String,
+    #[validate(length(min = MIN_PASSWORD_LENGTH, message = "must be at least 8 characters"))]
+    pub password: String,
+}
+
+impl TryFrom<web::Json<RegisterStudent>> for RegisterStudent {
+    type Error = AppError;
+
+    fn try_from(student: web::Json<RegisterStudent>) -> Result<Self, Self::Error> {
+        student.validate()?;
+        Ok(RegisterStudent {
+            name: student.name.clone(),
+            email: student.email.trim().to_lowercase(),
+            password: student.password.clone(),
+        })
+    }
+}
+
+// 数据库中保存的登录凭证, 只在服务端使用, 不会序列化输出
+#[derive(Debug, Clone)]
+pub struct StudentCredential {
+    pub id: i32,
+    pub password_hash: String,
+}
+
+// POST /courses/{teacher_id}/{course_id}/enrollments 的请求, 学生只能为自己选课
+#[derive(Deserialize, Debug, Clone)]
+pub struct EnrollStudent {
+    pub student_id: i32,
+}
+
+#[derive(Serialize, Debug, Clone, PartialEq, sqlx::FromRow)]
+pub struct Enrollment {
+    pub course_id: i32,
+    pub student_id: i32,
+    pub enrolled_at: NaiveDateTime,
+}
+
+// 课程的选课学生
+#[derive(Serialize, Debug, Clone, PartialEq, sqlx::FromRow)]
+pub struct EnrolledStudent {
+    pub student_id: i32,
+    pub name: String,
+    pub email: String,
+    pub enrolled_at: NaiveDateTime,
+}
+
+// GET /courses/{teacher_id}/{course_id}/enrollments 的结果, 学生按选课时间排序
+// capacity 为空时不限制人数
+#[derive(Serialize, Debug, Clone)]
+pub struct CourseEnrollments {
+    pub course_id: i32,
+    pub capacity: Option<i32>,
+    pub total: i64,
+    pub students: Vec<EnrolledStudent>,
+}
+
+// 学生已选的课程, 最近选的在前
+#[derive(Serialize, Debug, Clone, sqlx::FromRow)]
+pub struct EnrolledCourse {
+    #[serde(flatten)]
+    #[sqlx(flatten)]
+    pub course: Course,
+    pub enrolled_at: NaiveDateTime,
+}
+
+// 课程已满时的错误, 所有存储实现使用相同的提示
+pub fn course_full() -> AppError {
+    AppError::Conflict("Course has reached its capacity".into())
+}
+
+pub fn already_enrolled() -> AppError {
+    AppError::Conflict("Student is already enrolled in the course".into())
+}
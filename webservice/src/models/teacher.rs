@@ -1,5 +1,12 @@
 use actix_web::web;
+use chrono::NaiveDateTime;
 use serde::{Deserialize, Serialize};
+use validator::Validate;
+
+use crate::errors::AppError;
+use crate::models::auth::{RegisterTeacher, MIN_PASSWORD_LENGTH};
+use crate::models::patch::{not_null, nullable};
+use crate::models::validation::not_blank;
 
 
 #[derive(Deserialize, Serialize, Debug, Clone)]
@@ -8,44 +15,132 @@ pub struct Teacher {
     pub name: String, 
     pub picture_url: String,
     pub profile: String,
+    // 版本号, 每次修改或删除后加一, 作为响应头 ETag 返回
+    pub version: i32,
+    // 移入回收站的时间, 只有回收站中的老师才有值
+    #[serde(skip_serializing_if = "Option::is_none")]
+    pub deleted_at: Option<NaiveDateTime>,
+}
+
+
+// DELETE /teachers/{teacher_id} 的参数, cascade 为 true 时同时删除老师的课程
+#[derive(Deserialize, Debug, Clone, Default)]
+pub struct DeleteTeacherQuery {
+    #[serde(default)]
+    pub cascade: bool,
 }
 
+// 删除老师的结果, deleted_courses 为一起移入回收站的课程数
+#[derive(Serialize, Deserialize, Debug, Clone, PartialEq)]
+pub struct DeletedTeacher {
+    pub teacher_id: i32,
+    pub deleted_courses: u64,
+}
 
-#[derive(Deserialize, Debug, Clone)]
+// 校验规则与数据库中字段的长度保持一致
+#[derive(Deserialize, Debug, Clone, Validate)]
 pub struct CreateTeacher{ 
+    #[validate(length(max = 100, message = "must be at most 100 characters"), custom(function = "not_blank"))]
     pub name: String, 
+    #[validate(url(message = "must be a valid URL"), length(max = 255, message = "must be at most 255 characters"))]
     pub picture_url: String,
+    #[validate(length(max = 1000, message = "must be at most 1000 characters"))]
     pub profile: String,
+    // 登录邮箱
+    #[validate(email(message = "must be a valid email address"), length(max = 255, message = "must be at most 255 characters"))]
+    pub email: Option<String>,
+    // 登录密码, 保存时只存储哈希值
+    #[validate(length(min = MIN_PASSWORD_LENGTH, message = "must be at least 8 characters"))]
+    pub password: Option<String>,
 }
 
 
-#[derive(Deserialize, Debug, Clone)]
+#[derive(Deserialize, Debug, Clone, Validate)]
 pub struct UpdateTeacher {
+    #[validate(length(max = 100, message = "must be at most 100 characters"), custom(function = "not_blank"))]
     pub name: Option<String>,
+    #[validate(url(message = "must be a valid URL"), length(max = 255, message = "must be at most 255 characters"))]
     pub picture_url: Option<String>,
+    #[validate(length(max = 1000, message = "must be at most 1000 characters"))]
     pub profile: Option<String>,
 }
 
 
 
-impl From<web::Json<CreateTeacher>> for CreateTeacher {
-    fn from(teacher: web::Json<CreateTeacher>) -> Self {
-        CreateTeacher {
+impl TryFrom<web::Json<CreateTeacher>> for CreateTeacher {
+    type Error = AppError;
+
+    fn try_from(teacher: web::Json<CreateTeacher>) -> Result<Self, Self::Error> {
+        teacher.validate()?;
+        Ok(CreateTeacher {
             name: teacher.name.clone(),
             picture_url: teacher.picture_url.clone(),
             profile: teacher.profile.clone(),
-        }
+            email: teacher.email.as_ref().map(|email| email.trim().to_lowercase()),
+            password: teacher.password.clone(),
+        })
     }
 }
 
 
-impl From<web::Json<UpdateTeacher>> for UpdateTeacher {
-    fn from(teacher: web::Json<UpdateTeacher>) -> Self {
-        UpdateTeacher {
+impl TryFrom<web::Json<UpdateTeacher>> for UpdateTeacher {
+    type Error = AppError;
+
+    fn try_from(teacher: web::Json<UpdateTeacher>) -> Result<Self, Self::Error> {
+        teacher.validate()?;
+        Ok(UpdateTeacher {
             name: teacher.name.clone(),
             picture_url: teacher.picture_url.clone(),
             profile: teacher.profile.clone(),
+        })
+    }
+}
+
+
+// PATCH 请求的数据对象, 不传入的字段保持不变, 传入 null 时清空, name 不能为 null
+#[derive(Deserialize, Debug, Clone, Default, Validate)]
+#[serde(default)]
+pub struct PatchTeacher {
+    #[serde(deserialize_with = "nullable")]
+    #[validate(length(max = 100, message = "must be at most 100 characters"), custom(function = "not_blank"))]
+    pub name: Option<Option<String>>,
+    #[serde(deserialize_with = "nullable")]
+    #[validate(url(message = "must be a valid URL"), length(max = 255, message = "must be at most 255 characters"))]
+    pub picture_url: Option<Option<String>>,
+    #[serde(deserialize_with = "nullable")]
+    #[validate(length(max = 1000, message = "must be at most 1000 characters"))]
+    pub profile: Option<Option<String>>,
+}
+
+impl TryFrom<web::Json<PatchTeacher>> for PatchTeacher {
+    type Error = AppError;
+
+    fn try_from(teacher: web::Json<PatchTeacher>) -> Result<Self, Self::Error> {
+        not_null("name", &teacher.name)?;
+        teacher.validate()?;
+        Ok(teacher.into_inner())
+    }
+}
+
+impl From<UpdateTeacher> for PatchTeacher {
+    fn from(teacher: UpdateTeacher) -> Self {
+        PatchTeacher {
+            name: teacher.name.map(Some),
+            picture_url: teacher.picture_url.map(Some),
+            profile: teacher.profile.map(Some),
         }
     }
 }
 
+
+impl From<RegisterTeacher> for CreateTeacher {
+    fn from(teacher: RegisterTeacher) -> Self {
+        CreateTeacher {
+            name: teacher.name,
+            picture_url: teacher.picture_url,
+            profile: teacher.profile,
+            email: Some(teacher.email),
+            password: Some(teacher.password),
+        }
+    }
+}
@@ -0,0 +1,28 @@
+use std::borrow::Cow;
+
+use validator::ValidationError;
+
+// 供 #[validate(custom(...))] 使用的校验函数
+
+// 不能只包含空白字符
+pub fn not_blank(value: &str) -> Result<(), ValidationError> {
+    if value.trim().is_empty() {
+        return Err(error("blank", "must not be blank"));
+    }
+    Ok(())
+}
+
+// 取值必须是 allowed 中的一个
+pub fn one_of(value: &str, allowed: &[&str]) -> Result<(), ValidationError> {
+    if !allowed.contains(&value) {
+        return Err(error(
+            "one_of",
+            format!("must be one of: {}", allowed.join(", ")),
+        ));
+    }
+    Ok(())
+}
+
+fn error(code: &'static str, message: impl Into<Cow<'static, str>>) -> ValidationError {
+    ValidationError::new(code).with_message(message.into())
+}
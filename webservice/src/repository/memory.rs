@@ -0,0 +1,1139 @@
+use std::cmp::{Ordering as CmpOrdering, Reverse};
+use std::sync::atomic::{AtomicI32, Ordering};
+use std::sync::{Mutex, MutexGuard};
+
+use async_trait::async_trait;
+use chrono::{Duration, NaiveDateTime, Utc};
+
+use crate::dbaccess::{
+    COURSE_CAPACITY_CHECK, COURSE_PRICE_CHECK, COURSE_TEACHER_FKEY, STUDENT_EMAIL_KEY, TEACHER_EMAIL_KEY,
+};
+use crate::errors::AppError;
+use crate::etag::{check_version, IfMatch};
+use crate::models::auth::{Role, TeacherCredential};
+use crate::models::course::{
+    Course, CoursePage, CourseQuery, CourseSearchHit, CourseSearchPage, CourseSearchQuery,
+    CreateCourse, Money, PatchCourse, SortOrder,
+};
+use crate::models::section::{
+    insert_position, CreateLesson, CreateSection, Lesson, Reorder, Section, UpdateLesson,
+    UpdateSection,
+};
+use crate::models::student::{
+    already_enrolled, course_full, CourseEnrollments, EnrolledCourse, EnrolledStudent, Enrollment,
+    RegisterStudent, Student, StudentCredential,
+};
+use crate::models::teacher::{CreateTeacher, DeletedTeacher, PatchTeacher, Teacher};
+
+use super::{CourseRepository, SectionRepository, StudentRepository, TeacherRepository};
+
+
+// 与 ts_rank 的默认权重一致: 名称 A, 描述 B, 课程结构 C
+const SEARCH_WEIGHTS: [f32; 3] = [1.0, 0.4, 0.2];
+
+// 数据保存在内存中的存储, 用于不依赖数据库的开发和测试, 进程退出后数据丢失
+// 模拟了数据库的自增主键、外键、唯一索引和 check 约束, 错误与 Postgres 实现一致
+#[derive(Default)]
+pub struct InMemoryRepository {
+    courses: Mutex<Vec<Course>>,
+    teachers: Mutex<Vec<TeacherRecord>>,
+    // 章节中包含其课时, 需要同时锁住 courses 时先锁 courses
+    sections: Mutex<Vec<Section>>,
+    students: Mutex<Vec<StudentRecord>>,
+    // 需要同时锁住 courses 时先锁 courses, 再锁 students
+    enrollments: Mutex<Vec<Enrollment>>,
+    // 与数据库序列一样, 删除后的 id 不会被再次使用
+    course_seq: AtomicI32,
+    teacher_seq: AtomicI32,
+    section_seq: AtomicI32,
+    lesson_seq: AtomicI32,
+    student_seq: AtomicI32,
+}
+
+// teacher 表中的一行, 登录信息不出现在 Teacher 中
+struct TeacherRecord {
+    teacher: Teacher,
+    email: Option<String>,
+    password_hash: Option<String>,
+    role: Role,
+}
+
+impl TeacherRecord {
+    fn is_active(&self) -> bool {
+        self.teacher.deleted_at.is_none()
+    }
+
+    fn credential(&self) -> TeacherCredential {
+        TeacherCredential {
+            id: self.teacher.id,
+            password_hash: self.password_hash.clone(),
+            role: self.role,
+        }
+    }
+}
+
+// student 表中的一行
+struct StudentRecord {
+    student: Student,
+    password_hash: String,
+}
+
+impl InMemoryRepository {
+    pub fn new() -> Self {
+        Self::default()
+    }
+
+    // 课程必须存在、未删除且属于该老师, 返回的锁在修改章节期间一直持有, 与数据库实现中锁住课程一致
+    fn lock_course(&self, teacher_id: i32, course_id: i32) -> Result<MutexGuard<'_, Vec<Course>>, AppError> {
+        let courses = self.courses.lock().unwrap();
+        match courses
+            .iter()
+            .any(|c| c.id == course_id && c.teacher_id == teacher_id && c.deleted_at.is_none())
+        {
+            true => Ok(courses),
+            false => Err(AppError::NotFound("course".into())),
+        }
+    }
+
+    // 调用方需要先检查老师存在以及 check_course
+    fn new_course(&self, new_course: CreateCourse) -> Course {
+        Course {
+            teacher_id: new_course.teacher_id,
+            id: self.course_seq.fetch_add(1, Ordering::SeqCst) + 1,
+            name: new_course.name,
+            time: Some(now()),
+            description: new_course.description,
+            format: new_course.format,
+            structure: new_course.structure,
+            duration: new_course.duration,
+            price: new_course.price,
+            language: new_course.language,
+            level: new_course.level,
+            capacity: new_course.capacity,
+            version: 1,
+            deleted_at: None,
+        }
+    }
+}
+
+#[async_trait]
+impl CourseRepository for InMemoryRepository {
+    async fn get_course_for_teacher(
+        &self,
+        teacher_id: i32,
+        query: &CourseQuery,
+    ) -> Result<CoursePage, AppError> {
+        query.validate()?;
+        let limit = query.limit();
+        let offset = query.offset();
+
+        let mut items: Vec<Course> = self
+            .courses
+            .lock()
+            .unwrap()
+            .iter()
+            .filter(|c| {
+                c.teacher_id == teacher_id && c.deleted_at.is_none() && matches_course_filters(c, query)
+            })
+            .cloned()
+            .collect();
+        let total = items.len() as i64;
+
+        items.sort_by(|a, b| {
+            let ordering = query.sort.key(a).cmp(&query.sort.key(b)).then(a.id.cmp(&b.id));
+            match query.order {
+                SortOrder::Asc => ordering,
+                SortOrder::Desc => ordering.reverse(),
+            }
+        });
+        let start = match &query.cursor {
+            // 游标分页: 从上一页最后一条记录的 (排序值, id) 之后开始
+            Some(cursor) => {
+                let (key, id) = query.sort.parse_cursor(query.order, cursor)?;
+                let after = match query.order {
+                    SortOrder::Asc => CmpOrdering::Greater,
+                    SortOrder::Desc => CmpOrdering::Less,
+                };
+                items
+                    .iter()
+                    .position(|c| (query.sort.key(c), c.id).cmp(&(key.clone(), id)) == after)
+                    .unwrap_or(items.len())
+            }
+            None => (offset as usize).min(items.len()),
+        };
+        let mut items: Vec<Course> = items.drain(start..).take(limit as usize + 1).collect();
+
+        let next_cursor = if items.len() as i64 > limit {
+            items.truncate(limit as usize);
+            items.last().map(|c| query.sort.cursor(query.order, c))
+        } else {
+            None
+        };
+
+        Ok(CoursePage {
+            items,
+            total,
+            limit,
+            offset: if query.cursor.is_some() { 0 } else { offset },
+            next_cursor,
+        })
+    }
+
+    // 只做简单的分词匹配: 每个关键词都是课程中某个词的前缀时命中, 不支持词干与搜索语法
+    async fn search_courses(&self, query: &CourseSearchQuery) -> Result<CourseSearchPage, AppError> {
+        query.validate()?;
+        let limit = query.limit();
+        let offset = query.offset();
+        let terms = search_terms(&query.q);
+
+        let mut items: Vec<CourseSearchHit> = self
+            .courses
+            .lock()
+            .unwrap()
+            .iter()
+            .filter(|c| c.deleted_at.is_none())
+            .filter(|c| query.level.is_none() || c.level == query.level)
+            .filter(|c| query.language.is_none() || c.language == query.language)
+            .filter_map(|c| {
+                let fields = [
+                    c.name.as_str(),
+                    c.description.as_deref().unwrap_or_default(),
+                    c.structure.as_deref().unwrap_or_default(),
+                ];
+                let mut rank = 0.0;
+                for term in &terms {
+                    let hits: Vec<usize> = fields.iter().map(|f| count_matches(f, term)).collect();
+                    if hits.iter().all(|&n| n == 0) {
+                        return None;
+                    }
+                    rank += hits
+                        .iter()
+                        .zip(SEARCH_WEIGHTS)
+                        .map(|(&n, weight)| n as f32 * weight)
+                        .sum::<f32>();
+                }
+                let snippet_source = c.description.as_deref().or(c.structure.as_deref());
+                Some(CourseSearchHit {
+                    course: c.clone(),
+                    rank,
+                    name_highlight: highlight(&c.name, &terms),
+                    snippet: highlight(snippet_source.unwrap_or_default(), &terms),
+                })
+            })
+            .collect();
+        let total = items.len() as i64;
+
+        items.sort_by(|a, b| {
+            b.rank
+                .total_cmp(&a.rank)
+                .then(a.course.id.cmp(&b.course.id))
+        });
+        let items = items
+            .into_iter()
+            .skip(offset as usize)
+            .take(limit as usize)
+            .collect();
+
+        Ok(CourseSearchPage {
+            items,
+            total,
+            limit,
+            offset,
+        })
+    }
+
+    async fn get_course_detail(&self, teacher_id: i32, course_id: i32) -> Result<Course, AppError> {
+        self.courses
+            .lock()
+            .unwrap()
+            .iter()
+            .find(|c| c.teacher_id == teacher_id && c.id == course_id && c.deleted_at.is_none())
+            .cloned()
+            .ok_or_else(|| AppError::NotFound("course".into()))
+    }
+
+    async fn post_new_course(&self, new_course: CreateCourse) -> Result<Course, AppError> {
+        // 先锁老师再锁课程, 所有方法保持同样的顺序避免死锁
+        let teachers = self.teachers.lock().unwrap();
+        // 已删除的老师也不能再创建课程
+        if !teachers.iter().any(|t| t.teacher.id == new_course.teacher_id && t.is_active()) {
+            return Err(AppError::missing_reference(COURSE_TEACHER_FKEY));
+        }
+        check_course(new_course.price.as_ref(), new_course.capacity)?;
+        let course = self.new_course(new_course);
+        self.courses.lock().unwrap().push(course.clone());
+        Ok(course)
+    }
+
+    // 先检查所有课程再保存, 与数据库事务一样要么全部保存要么都不保存
+    async fn import_courses(&self, teacher_id: i32, courses: Vec<CreateCourse>) -> Result<u64, AppError> {
+        let teachers = self.teachers.lock().unwrap();
+        if !teachers.iter().any(|t| t.teacher.id == teacher_id && t.is_active()) {
+            return Err(AppError::missing_reference(COURSE_TEACHER_FKEY));
+        }
+        for course in &courses {
+            check_course(course.price.as_ref(), course.capacity)?;
+        }
+        let imported = courses.len() as u64;
+        let mut stored = self.courses.lock().unwrap();
+        for course in courses {
+            stored.push(self.new_course(CreateCourse { teacher_id, ..course }));
+        }
+        Ok(imported)
+    }
+
+    // 与 Postgres 实现一致, 没有传入的字段保留原值, 传入 null 的字段清空
+    async fn update_course(
+        &self,
+        teacher_id: i32,
+        course_id: i32,
+        if_match: &IfMatch,
+        patch: PatchCourse,
+    ) -> Result<Course, AppError> {
+        let mut courses = self.courses.lock().unwrap();
+        let course = courses
+            .iter_mut()
+            .find(|c| c.teacher_id == teacher_id && c.id == course_id && c.deleted_at.is_none())
+            .ok_or_else(|| AppError::NotFound("course".into()))?;
+        check_version(if_match, course.version)?;
+
+        let mut updated = course.clone();
+        patch.apply(&mut updated);
+        check_course(updated.price.as_ref(), updated.capacity)?;
+        updated.version += 1;
+        *course = updated;
+        Ok(course.clone())
+    }
+
+    async fn delete_course(
+        &self,
+        teacher_id: i32,
+        course_id: i32,
+        if_match: &IfMatch,
+    ) -> Result<(), AppError> {
+        let mut courses = self.courses.lock().unwrap();
+        let course = courses
+            .iter_mut()
+            .find(|c| c.teacher_id == teacher_id && c.id == course_id && c.deleted_at.is_none())
+            .ok_or_else(|| AppError::NotFound("course".into()))?;
+        check_version(if_match, course.version)?;
+        course.deleted_at = Some(now());
+        course.version += 1;
+        Ok(())
+    }
+
+    async fn get_deleted_courses(&self, teacher_id: i32) -> Result<Vec<Course>, AppError> {
+        let mut courses: Vec<Course> = self
+            .courses
+            .lock()
+            .unwrap()
+            .iter()
+            .filter(|c| c.teacher_id == teacher_id && c.deleted_at.is_some())
+            .cloned()
+            .collect();
+        courses.sort_by_key(|c| Reverse((c.deleted_at, c.id)));
+        Ok(courses)
+    }
+
+    async fn restore_course(&self, teacher_id: i32, course_id: i32) -> Result<Course, AppError> {
+        let teachers = self.teachers.lock().unwrap();
+        let mut courses = self.courses.lock().unwrap();
+        let course = courses
+            .iter_mut()
+            .find(|c| c.teacher_id == teacher_id && c.id == course_id && c.deleted_at.is_some())
+            .ok_or_else(|| AppError::NotFound("course".into()))?;
+        if !teachers.iter().any(|t| t.teacher.id == teacher_id && t.is_active()) {
+            return Err(AppError::missing_reference(COURSE_TEACHER_FKEY));
+        }
+        course.deleted_at = None;
+        course.version += 1;
+        Ok(course.clone())
+    }
+
+    async fn purge_deleted_courses(&self, retention: Duration) -> Result<u64, AppError> {
+        let cutoff = now() - retention;
+        let mut courses = self.courses.lock().unwrap();
+        let before = courses.len();
+        courses.retain(|c| c.deleted_at.is_none_or(|at| at >= cutoff));
+        // 与外键的 on delete cascade 一样删除课程的章节与课时, 以及选课记录
+        self.sections
+            .lock()
+            .unwrap()
+            .retain(|s| courses.iter().any(|c| c.id == s.course_id));
+        self.enrollments
+            .lock()
+            .unwrap()
+            .retain(|e| courses.iter().any(|c| c.id == e.course_id));
+        Ok((before - courses.len()) as u64)
+    }
+}
+
+#[async_trait]
+impl SectionRepository for InMemoryRepository {
+    async fn get_sections(&self, teacher_id: i32, course_id: i32) -> Result<Vec<Section>, AppError> {
+        let _courses = self.lock_course(teacher_id, course_id)?;
+        let mut sections: Vec<Section> = self
+            .sections
+            .lock()
+            .unwrap()
+            .iter()
+            .filter(|s| s.course_id == course_id)
+            .cloned()
+            .collect();
+        sections.sort_by_key(|s| s.position);
+        Ok(sections)
+    }
+
+    async fn post_new_section(
+        &self,
+        teacher_id: i32,
+        course_id: i32,
+        new_section: CreateSection,
+    ) -> Result<Section, AppError> {
+        let _courses = self.lock_course(teacher_id, course_id)?;
+        let mut sections = self.sections.lock().unwrap();
+        let count = sections.iter().filter(|s| s.course_id == course_id).count() as i32;
+        let position = insert_position(new_section.position, count);
+        for section in sections.iter_mut().filter(|s| s.course_id == course_id && s.position >= position) {
+            section.position += 1;
+        }
+        let section = Section {
+            course_id,
+            id: self.section_seq.fetch_add(1, Ordering::SeqCst) + 1,
+            title: new_section.title,
+            position,
+            lessons: Vec::new(),
+        };
+        sections.push(section.clone());
+        Ok(section)
+    }
+
+    async fn update_section(
+        &self,
+        teacher_id: i32,
+        course_id: i32,
+        section_id: i32,
+        update: UpdateSection,
+    ) -> Result<Section, AppError> {
+        let _courses = self.lock_course(teacher_id, course_id)?;
+        let mut sections = self.sections.lock().unwrap();
+        let section = find_section(&mut sections, course_id, section_id)?;
+        section.title = update.title;
+        Ok(section.clone())
+    }
+
+    async fn delete_section(&self, teacher_id: i32, course_id: i32, section_id: i32) -> Result<(), AppError> {
+        let _courses = self.lock_course(teacher_id, course_id)?;
+        let mut sections = self.sections.lock().unwrap();
+        let position = find_section(&mut sections, course_id, section_id)?.position;
+        sections.retain(|s| s.id != section_id);
+        for section in sections.iter_mut().filter(|s| s.course_id == course_id && s.position > position) {
+            section.position -= 1;
+        }
+        Ok(())
+    }
+
+    async fn reorder_sections(
+        &self,
+        teacher_id: i32,
+        course_id: i32,
+        order: Reorder,
+    ) -> Result<Vec<Section>, AppError> {
+        let _courses = self.lock_course(teacher_id, course_id)?;
+        let mut sections = self.sections.lock().unwrap();
+        let mut current: Vec<i32> = sections.iter().filter(|s| s.course_id == course_id).map(|s| s.id).collect();
+        if !order.matches(&mut current) {
+            return Err(Reorder::mismatch("section"));
+        }
+        let mut reordered = Vec::with_capacity(order.ids.len());
+        for (index, id) in order.ids.iter().enumerate() {
+            let section = find_section(&mut sections, course_id, *id)?;
+            section.position = index as i32 + 1;
+            reordered.push(section.clone());
+        }
+        Ok(reordered)
+    }
+
+    async fn post_new_lesson(
+        &self,
+        teacher_id: i32,
+        course_id: i32,
+        section_id: i32,
+        new_lesson: CreateLesson,
+    ) -> Result<Lesson, AppError> {
+        let _courses = self.lock_course(teacher_id, course_id)?;
+        let mut sections = self.sections.lock().unwrap();
+        let section = find_section(&mut sections, course_id, section_id)?;
+        let position = insert_position(new_lesson.position, section.lessons.len() as i32);
+        for lesson in section.lessons.iter_mut().filter(|l| l.position >= position) {
+            lesson.position += 1;
+        }
+        let lesson = Lesson {
+            section_id,
+            id: self.lesson_seq.fetch_add(1, Ordering::SeqCst) + 1,
+            title: new_lesson.title,
+            content: new_lesson.content,
+            duration_minutes: new_lesson.duration_minutes,
+            position,
+        };
+        section.lessons.push(lesson.clone());
+        section.lessons.sort_by_key(|l| l.position);
+        Ok(lesson)
+    }
+
+    async fn update_lesson(
+        &self,
+        teacher_id: i32,
+        course_id: i32,
+        section_id: i32,
+        lesson_id: i32,
+        update: UpdateLesson,
+    ) -> Result<Lesson, AppError> {
+        let _courses = self.lock_course(teacher_id, course_id)?;
+        let mut sections = self.sections.lock().unwrap();
+        let section = find_section(&mut sections, course_id, section_id)?;
+        let lesson = section
+            .lessons
+            .iter_mut()
+            .find(|l| l.id == lesson_id)
+            .ok_or_else(|| AppError::NotFound("lesson".into()))?;
+        update.apply(lesson);
+        Ok(lesson.clone())
+    }
+
+    async fn delete_lesson(
+        &self,
+        teacher_id: i32,
+        course_id: i32,
+        section_id: i32,
+        lesson_id: i32,
+    ) -> Result<(), AppError> {
+        let _courses = self.lock_course(teacher_id, course_id)?;
+        let mut sections = self.sections.lock().unwrap();
+        let section = find_section(&mut sections, course_id, section_id)?;
+        let position = section
+            .lessons
+            .iter()
+            .find(|l| l.id == lesson_id)
+            .map(|l| l.position)
+            .ok_or_else(|| AppError::NotFound("lesson".into()))?;
+        section.lessons.retain(|l| l.id != lesson_id);
+        for lesson in section.lessons.iter_mut().filter(|l| l.position > position) {
+            lesson.position -= 1;
+        }
+        Ok(())
+    }
+
+    async fn reorder_lessons(
+        &self,
+        teacher_id: i32,
+        course_id: i32,
+        section_id: i32,
+        order: Reorder,
+    ) -> Result<Vec<Lesson>, AppError> {
+        let _courses = self.lock_course(teacher_id, course_id)?;
+        let mut sections = self.sections.lock().unwrap();
+        let section = find_section(&mut sections, course_id, section_id)?;
+        let mut current: Vec<i32> = section.lessons.iter().map(|l| l.id).collect();
+        if !order.matches(&mut current) {
+            return Err(Reorder::mismatch("lesson"));
+        }
+        for lesson in section.lessons.iter_mut() {
+            lesson.position = order.ids.iter().position(|id| *id == lesson.id).unwrap_or_default() as i32 + 1;
+        }
+        section.lessons.sort_by_key(|l| l.position);
+        Ok(section.lessons.clone())
+    }
+}
+
+#[async_trait]
+impl StudentRepository for InMemoryRepository {
+    async fn get_student(&self, student_id: i32) -> Result<Student, AppError> {
+        self.students
+            .lock()
+            .unwrap()
+            .iter()
+            .find(|s| s.student.id == student_id)
+            .map(|s| s.student.clone())
+            .ok_or_else(|| AppError::NotFound("student".into()))
+    }
+
+    async fn get_student_credential_by_email(
+        &self,
+        email: &str,
+    ) -> Result<Option<StudentCredential>, AppError> {
+        Ok(self
+            .students
+            .lock()
+            .unwrap()
+            .iter()
+            .find(|s| s.student.email.to_lowercase() == email.to_lowercase())
+            .map(|s| StudentCredential {
+                id: s.student.id,
+                password_hash: s.password_hash.clone(),
+            }))
+    }
+
+    async fn post_new_student(
+        &self,
+        new_student: RegisterStudent,
+        password_hash: String,
+    ) -> Result<Student, AppError> {
+        let mut students = self.students.lock().unwrap();
+        let email = new_student.email.to_lowercase();
+        if students.iter().any(|s| s.student.email.to_lowercase() == email) {
+            return Err(AppError::unique_violation(STUDENT_EMAIL_KEY));
+        }
+        let student = Student {
+            id: self.student_seq.fetch_add(1, Ordering::SeqCst) + 1,
+            name: new_student.name,
+            email: new_student.email,
+            time: Some(now()),
+        };
+        students.push(StudentRecord {
+            student: student.clone(),
+            password_hash,
+        });
+        Ok(student)
+    }
+
+    // 持有课程的锁直到写入完成, 与数据库实现一样同一门课程的选课依次执行
+    async fn enroll(
+        &self,
+        teacher_id: i32,
+        course_id: i32,
+        student_id: i32,
+    ) -> Result<Enrollment, AppError> {
+        let courses = self.lock_course(teacher_id, course_id)?;
+        if !self.students.lock().unwrap().iter().any(|s| s.student.id == student_id) {
+            return Err(AppError::NotFound("student".into()));
+        }
+        let mut enrollments = self.enrollments.lock().unwrap();
+        if enrollments.iter().any(|e| e.course_id == course_id && e.student_id == student_id) {
+            return Err(already_enrolled());
+        }
+        let capacity = courses.iter().find(|c| c.id == course_id).and_then(|c| c.capacity);
+        let count = enrollments.iter().filter(|e| e.course_id == course_id).count();
+        if capacity.is_some_and(|capacity| count >= capacity as usize) {
+            return Err(course_full());
+        }
+        let enrollment = Enrollment {
+            course_id,
+            student_id,
+            enrolled_at: now(),
+        };
+        enrollments.push(enrollment.clone());
+        Ok(enrollment)
+    }
+
+    async fn unenroll(&self, teacher_id: i32, course_id: i32, student_id: i32) -> Result<(), AppError> {
+        let _courses = self.lock_course(teacher_id, course_id)?;
+        let mut enrollments = self.enrollments.lock().unwrap();
+        let before = enrollments.len();
+        enrollments.retain(|e| !(e.course_id == course_id && e.student_id == student_id));
+        match enrollments.len() < before {
+            true => Ok(()),
+            false => Err(AppError::NotFound("enrollment".into())),
+        }
+    }
+
+    async fn get_course_enrollments(
+        &self,
+        teacher_id: i32,
+        course_id: i32,
+    ) -> Result<CourseEnrollments, AppError> {
+        let courses = self.lock_course(teacher_id, course_id)?;
+        let capacity = courses.iter().find(|c| c.id == course_id).and_then(|c| c.capacity);
+        let students = self.students.lock().unwrap();
+        let mut enrolled: Vec<EnrolledStudent> = self
+            .enrollments
+            .lock()
+            .unwrap()
+            .iter()
+            .filter(|e| e.course_id == course_id)
+            .filter_map(|e| {
+                students.iter().find(|s| s.student.id == e.student_id).map(|s| EnrolledStudent {
+                    student_id: s.student.id,
+                    name: s.student.name.clone(),
+                    email: s.student.email.clone(),
+                    enrolled_at: e.enrolled_at,
+                })
+            })
+            .collect();
+        enrolled.sort_by_key(|s| (s.enrolled_at, s.student_id));
+        Ok(CourseEnrollments {
+            course_id,
+            capacity,
+            total: enrolled.len() as i64,
+            students: enrolled,
+        })
+    }
+
+    async fn get_student_courses(&self, student_id: i32) -> Result<Vec<EnrolledCourse>, AppError> {
+        let courses = self.courses.lock().unwrap();
+        if !self.students.lock().unwrap().iter().any(|s| s.student.id == student_id) {
+            return Err(AppError::NotFound("student".into()));
+        }
+        let mut enrolled: Vec<EnrolledCourse> = self
+            .enrollments
+            .lock()
+            .unwrap()
+            .iter()
+            .filter(|e| e.student_id == student_id)
+            .filter_map(|e| {
+                courses
+                    .iter()
+                    .find(|c| c.id == e.course_id && c.deleted_at.is_none())
+                    .map(|c| EnrolledCourse {
+                        course: c.clone(),
+                        enrolled_at: e.enrolled_at,
+                    })
+            })
+            .collect();
+        enrolled.sort_by_key(|e| Reverse((e.enrolled_at, e.course.id)));
+        Ok(enrolled)
+    }
+}
+
+#[async_trait]
+impl TeacherRepository for InMemoryRepository {
+    async fn get_all_teacher(&self) -> Result<Vec<Teacher>, AppError> {
+        let teachers: Vec<Teacher> = self
+            .teachers
+            .lock()
+            .unwrap()
+            .iter()
+            .filter(|t| t.is_active())
+            .map(|t| t.teacher.clone())
+            .collect();
+        match teachers.len() {
+            0 => Err(AppError::NotFound("teacher".into())),
+            _ => Ok(teachers),
+        }
+    }
+
+    async fn get_teacher_detail(&self, teacher_id: i32) -> Result<Teacher, AppError> {
+        self.teachers
+            .lock()
+            .unwrap()
+            .iter()
+            .find(|t| t.teacher.id == teacher_id && t.is_active())
+            .map(|t| t.teacher.clone())
+            .ok_or_else(|| AppError::NotFound("teacher".into()))
+    }
+
+    async fn get_teacher_credential(
+        &self,
+        teacher_id: i32,
+    ) -> Result<Option<TeacherCredential>, AppError> {
+        Ok(self
+            .teachers
+            .lock()
+            .unwrap()
+            .iter()
+            .find(|t| t.teacher.id == teacher_id && t.is_active())
+            .map(TeacherRecord::credential))
+    }
+
+    async fn get_teacher_credential_by_email(
+        &self,
+        email: &str,
+    ) -> Result<Option<TeacherCredential>, AppError> {
+        Ok(self
+            .teachers
+            .lock()
+            .unwrap()
+            .iter()
+            .find(|t| same_email(t, email) && t.is_active())
+            .map(TeacherRecord::credential))
+    }
+
+    async fn teacher_email_exists(&self, email: &str) -> Result<bool, AppError> {
+        Ok(self.teachers.lock().unwrap().iter().any(|t| same_email(t, email)))
+    }
+
+    async fn update_teacher_password(
+        &self,
+        teacher_id: i32,
+        password_hash: String,
+    ) -> Result<(), AppError> {
+        let mut teachers = self.teachers.lock().unwrap();
+        let record = teachers
+            .iter_mut()
+            .find(|t| t.teacher.id == teacher_id && t.is_active())
+            .ok_or_else(|| AppError::NotFound("teacher".into()))?;
+        record.password_hash = Some(password_hash);
+        Ok(())
+    }
+
+    async fn post_new_teacher(
+        &self,
+        new_teacher: CreateTeacher,
+        password_hash: Option<String>,
+    ) -> Result<Teacher, AppError> {
+        let mut teachers = self.teachers.lock().unwrap();
+        if let Some(email) = &new_teacher.email {
+            if teachers.iter().any(|t| same_email(t, email)) {
+                return Err(AppError::unique_violation(TEACHER_EMAIL_KEY));
+            }
+        }
+        let teacher = Teacher {
+            id: self.teacher_seq.fetch_add(1, Ordering::SeqCst) + 1,
+            name: new_teacher.name,
+            picture_url: new_teacher.picture_url,
+            profile: new_teacher.profile,
+            version: 1,
+            deleted_at: None,
+        };
+        teachers.push(TeacherRecord {
+            teacher: teacher.clone(),
+            email: new_teacher.email,
+            password_hash,
+            role: Role::Teacher,
+        });
+        Ok(teacher)
+    }
+
+    async fn update_teacher_details(
+        &self,
+        teacher_id: i32,
+        if_match: &IfMatch,
+        patch: PatchTeacher,
+    ) -> Result<Teacher, AppError> {
+        let mut teachers = self.teachers.lock().unwrap();
+        let record = teachers
+            .iter_mut()
+            .find(|t| t.teacher.id == teacher_id && t.is_active())
+            .ok_or_else(|| AppError::NotFound("teacher".into()))?;
+        check_version(if_match, record.teacher.version)?;
+        // 数据库中的空值作为空字符串返回, 这里直接保存为空字符串
+        if let Some(Some(name)) = patch.name {
+            record.teacher.name = name;
+        }
+        if let Some(picture_url) = patch.picture_url {
+            record.teacher.picture_url = picture_url.unwrap_or_default();
+        }
+        if let Some(profile) = patch.profile {
+            record.teacher.profile = profile.unwrap_or_default();
+        }
+        record.teacher.version += 1;
+        Ok(record.teacher.clone())
+    }
+
+    async fn delete_teacher(
+        &self,
+        teacher_id: i32,
+        if_match: &IfMatch,
+        cascade: bool,
+    ) -> Result<DeletedTeacher, AppError> {
+        let mut teachers = self.teachers.lock().unwrap();
+        let mut courses = self.courses.lock().unwrap();
+        let record = teachers
+            .iter_mut()
+            .find(|t| t.teacher.id == teacher_id && t.is_active())
+            .ok_or_else(|| AppError::NotFound("teacher".into()))?;
+        check_version(if_match, record.teacher.version)?;
+        let mut dependents: Vec<&mut Course> = courses
+            .iter_mut()
+            .filter(|c| c.teacher_id == teacher_id && c.deleted_at.is_none())
+            .collect();
+        if !cascade && !dependents.is_empty() {
+            return Err(AppError::HasDependents("course".into(), dependents.len() as i64));
+        }
+
+        let deleted_at = now();
+        for course in dependents.iter_mut() {
+            course.deleted_at = Some(deleted_at);
+            course.version += 1;
+        }
+        record.teacher.deleted_at = Some(deleted_at);
+        record.teacher.version += 1;
+        Ok(DeletedTeacher {
+            teacher_id,
+            deleted_courses: dependents.len() as u64,
+        })
+    }
+
+    async fn get_deleted_teachers(&self) -> Result<Vec<Teacher>, AppError> {
+        let mut teachers: Vec<Teacher> = self
+            .teachers
+            .lock()
+            .unwrap()
+            .iter()
+            .filter(|t| !t.is_active())
+            .map(|t| t.teacher.clone())
+            .collect();
+        teachers.sort_by_key(|t| Reverse((t.deleted_at, t.id)));
+        Ok(teachers)
+    }
+
+    async fn restore_teacher(&self, teacher_id: i32) -> Result<Teacher, AppError> {
+        let mut teachers = self.teachers.lock().unwrap();
+        let mut courses = self.courses.lock().unwrap();
+        let record = teachers
+            .iter_mut()
+            .find(|t| t.teacher.id == teacher_id && !t.is_active())
+            .ok_or_else(|| AppError::NotFound("teacher".into()))?;
+        // 与老师一起删除的课程删除时间与老师相同, 一起恢复
+        for course in courses
+            .iter_mut()
+            .filter(|c| c.teacher_id == teacher_id && c.deleted_at == record.teacher.deleted_at)
+        {
+            course.deleted_at = None;
+            course.version += 1;
+        }
+        record.teacher.deleted_at = None;
+        record.teacher.version += 1;
+        Ok(record.teacher.clone())
+    }
+
+    // 老师的课程(包括回收站中的)全部被彻底删除后才能删除老师
+    async fn purge_deleted_teachers(&self, retention: Duration) -> Result<u64, AppError> {
+        let cutoff = now() - retention;
+        let mut teachers = self.teachers.lock().unwrap();
+        let courses = self.courses.lock().unwrap();
+        let before = teachers.len();
+        teachers.retain(|t| {
+            t.teacher.deleted_at.is_none_or(|at| at >= cutoff)
+                || courses.iter().any(|c| c.teacher_id == t.teacher.id)
+        });
+        Ok((before - teachers.len()) as u64)
+    }
+}
+
+// 与 sql 中的 `level = $1` 一致, 字段为空时不匹配任何过滤条件
+fn matches_course_filters(course: &Course, query: &CourseQuery) -> bool {
+    let eq = |filter: &Option<String>, value: &Option<String>| {
+        filter.is_none() || (value.is_some() && filter == value)
+    };
+    eq(&query.level, &course.level)
+        && eq(&query.language, &course.language)
+        && eq(&query.format, &course.format)
+        && query.currency.as_ref().is_none_or(|currency| {
+            course.price.as_ref().is_some_and(|p| &p.currency == currency)
+        })
+        && query.min_price.is_none_or(|min| course.price.as_ref().is_some_and(|p| p.amount >= min))
+        && query.max_price.is_none_or(|max| course.price.as_ref().is_some_and(|p| p.amount <= max))
+}
+
+fn find_section(
+    sections: &mut [Section],
+    course_id: i32,
+    section_id: i32,
+) -> Result<&mut Section, AppError> {
+    sections
+        .iter_mut()
+        .find(|s| s.id == section_id && s.course_id == course_id)
+        .ok_or_else(|| AppError::NotFound("section".into()))
+}
+
+// 与 course 表的 check 约束一致, 价格与名额都不能为负数
+fn check_course(price: Option<&Money>, capacity: Option<i32>) -> Result<(), AppError> {
+    if price.is_some_and(|price| price.amount < 0) {
+        return Err(AppError::check_violation(COURSE_PRICE_CHECK));
+    }
+    if capacity.is_some_and(|capacity| capacity < 0) {
+        return Err(AppError::check_violation(COURSE_CAPACITY_CHECK));
+    }
+    Ok(())
+}
+
+fn now() -> NaiveDateTime {
+    Utc::now().naive_utc()
+}
+
+fn same_email(record: &TeacherRecord, email: &str) -> bool {
+    record
+        .email
+        .as_deref()
+        .is_some_and(|e| e.to_lowercase() == email.to_lowercase())
+}
+
+fn search_terms(q: &str) -> Vec<String> {
+    q.split(|c: char| !c.is_alphanumeric())
+        .filter(|term| !term.is_empty())
+        .map(str::to_lowercase)
+        .collect()
+}
+
+fn word_matches(word: &str, terms: &[String]) -> bool {
+    let word = word.to_lowercase();
+    terms.iter().any(|term| word.starts_with(term.as_str()))
+}
+
+fn count_matches(text: &str, term: &str) -> usize {
+    text.split(|c: char| !c.is_alphanumeric())
+        .filter(|word| word.to_lowercase().starts_with(term))
+        .count()
+}
+
+// 使用 <mark></mark> 包裹命中的词, 与 ts_headline 的输出格式一致
+fn highlight(text: &str, terms: &[String]) -> String {
+    let mut out = String::with_capacity(text.len());
+    let mut word = String::new();
+    let flush = |out: &mut String, word: &mut String| {
+        if word_matches(word, terms) {
+            out.push_str("<mark>");
+            out.push_str(word);
+            out.push_str("</mark>");
+        } else {
+            out.push_str(word);
+        }
+        word.clear();
+    };
+    for ch in text.chars() {
+        if ch.is_alphanumeric() {
+            word.push(ch);
+        } else {
+            flush(&mut out, &mut word);
+            out.push(ch);
+        }
+    }
+    flush(&mut out, &mut word);
+    out
+}
+
+#[cfg(test)]
+mod tests {
+    use actix_web::{http::StatusCode, ResponseError};
+
+    use crate::models::course::{CourseSort, Money};
+
+    use super::*;
+
+    fn new_teacher(name: &str, email: Option<&str>) -> CreateTeacher {
+        CreateTeacher {
+            name: name.into(),
+            picture_url: "http://unicorn.pro".into(),
+            profile: "An in-memory teacher".into(),
+            email: email.map(String::from),
+            password: None,
+        }
+    }
+
+    fn new_course(teacher_id: i32, name: &str, price: i32) -> CreateCourse {
+        CreateCourse {
+            teacher_id,
+            name: name.into(),
+            description: Some(format!("{} description", name)),
+            format: None,
+            structure: None,
+            duration: None,
+            price: Some(Money::new(price, "CNY")),
+            language: Some("English".into()),
+            level: Some("Beginner".into()),
+            capacity: None,
+        }
+    }
+
+    #[actix_rt::test]
+    async fn courses_page_by_cursor() {
+        let repo = InMemoryRepository::new();
+        let teacher = repo.post_new_teacher(new_teacher("Dave", None), None).await.unwrap();
+        for (name, price) in [("Third", 300), ("First", 100), ("Second", 200)] {
+            repo.post_new_course(new_course(teacher.id, name, price)).await.unwrap();
+        }
+
+        let query = CourseQuery {
+            limit: Some(2),
+            sort: CourseSort::Price,
+            order: SortOrder::Desc,
+            currency: Some("CNY".into()),
+            ..Default::default()
+        };
+        let first = repo.get_course_for_teacher(teacher.id, &query).await.unwrap();
+        assert_eq!(first.total, 3);
+        let names: Vec<&str> = first.items.iter().map(|c| c.name.as_str()).collect();
+        assert_eq!(names, ["Third", "Second"]);
+
+        let query = CourseQuery {
+            cursor: first.next_cursor.clone(),
+            ..query
+        };
+        let second = repo.get_course_for_teacher(teacher.id, &query).await.unwrap();
+        assert_eq!(second.items.len(), 1);
+        assert_eq!(second.items[0].name, "First");
+        assert!(second.next_cursor.is_none());
+
+        let query = CourseQuery {
+            currency: Some("CNY".into()),
+            min_price: Some(150),
+            ..Default::default()
+        };
+        let page = repo.get_course_for_teacher(teacher.id, &query).await.unwrap();
+        assert_eq!(page.total, 2);
+    }
+
+    #[actix_rt::test]
+    async fn constraints_match_postgres_errors() {
+        let repo = InMemoryRepository::new();
+        let err = repo.post_new_course(new_course(1, "Orphan", 0)).await.unwrap_err();
+        assert_eq!(err.status_code(), StatusCode::UNPROCESSABLE_ENTITY);
+        assert!(err.to_string().contains("course_teacher_id_fkey"));
+
+        let teacher = repo
+            .post_new_teacher(new_teacher("Dave", Some("dave@unicorn.pro")), None)
+            .await
+            .unwrap();
+        let err = repo
+            .post_new_teacher(new_teacher("Another Dave", Some("DAVE@unicorn.pro")), None)
+            .await
+            .unwrap_err();
+        assert_eq!(err.status_code(), StatusCode::CONFLICT);
+        assert!(err.to_string().contains("teacher_email_key"));
+
+        let course = repo.post_new_course(new_course(teacher.id, "Rust", 100)).await.unwrap();
+        let err = repo.delete_teacher(teacher.id, &IfMatch::ANY, false).await.unwrap_err();
+        assert_eq!(err.status_code(), StatusCode::CONFLICT);
+
+        repo.delete_course(teacher.id, course.id, &IfMatch::version(1)).await.unwrap();
+        repo.delete_teacher(teacher.id, &IfMatch::ANY, false).await.unwrap();
+        let err = repo.get_teacher_detail(teacher.id).await.unwrap_err();
+        assert_eq!(err.code(), "teacher_not_found");
+
+        // 删除后的 id 不会被再次使用
+        let teacher = repo.post_new_teacher(new_teacher("Tom", None), None).await.unwrap();
+        assert_eq!(teacher.id, 2);
+    }
+
+    #[actix_rt::test]
+    async fn search_highlights_terms() {
+        let repo = InMemoryRepository::new();
+        let teacher = repo.post_new_teacher(new_teacher("Dave", None), None).await.unwrap();
+        let mut compilers = new_course(teacher.id, "Compilers", 100);
+        compilers.description = Some("Writing a tokenizer and parser from scratch".into());
+        let compilers = repo.post_new_course(compilers).await.unwrap();
+        repo.post_new_course(new_course(teacher.id, "Tokenizers", 100)).await.unwrap();
+        repo.post_new_course(new_course(teacher.id, "Databases", 100)).await.unwrap();
+
+        let query = CourseSearchQuery {
+            q: "tokenizer".into(),
+            ..Default::default()
+        };
+        let page = repo.search_courses(&query).await.unwrap();
+        assert_eq!(page.total, 2);
+        // 名称中命中的权重更高
+        assert_eq!(page.items[0].name_highlight, "<mark>Tokenizers</mark>");
+        let hit = page.items.iter().find(|h| h.course.id == compilers.id).unwrap();
+        assert_eq!(hit.snippet, "Writing a <mark>tokenizer</mark> and parser from scratch");
+    }
+
+    // 课程被彻底删除时与外键的 on delete cascade 一样删除其章节
+    #[actix_rt::test]
+    async fn sections_purged_with_course() {
+        let repo = InMemoryRepository::new();
+        let teacher = repo.post_new_teacher(new_teacher("Dave", None), None).await.unwrap();
+        let course = repo.post_new_course(new_course(teacher.id, "Rust", 100)).await.unwrap();
+        let section = CreateSection { title: "Basics".into(), position: None };
+        let section = repo.post_new_section(teacher.id, course.id, section).await.unwrap();
+        let lesson = CreateLesson { title: "Hello".into(), content: None, duration_minutes: None, position: Some(5) };
+        let lesson = repo.post_new_lesson(teacher.id, course.id, section.id, lesson).await.unwrap();
+        assert_eq!(lesson.position, 1);
+
+        repo.delete_course(teacher.id, course.id, &IfMatch::ANY).await.unwrap();
+        let err = repo.get_sections(teacher.id, course.id).await.unwrap_err();
+        assert_eq!(err.code(), "course_not_found");
+        repo.purge_deleted_courses(Duration::zero()).await.unwrap();
+        assert!(repo.sections.lock().unwrap().is_empty());
+    }
+}
@@ -0,0 +1,254 @@
+use async_trait::async_trait;
+use chrono::Duration;
+
+use crate::errors::AppError;
+use crate::etag::IfMatch;
+use crate::models::auth::TeacherCredential;
+use crate::models::course::{
+    Course, CoursePage, CourseQuery, CourseSearchPage, CourseSearchQuery, CreateCourse,
+    PatchCourse,
+};
+use crate::models::section::{
+    CreateLesson, CreateSection, Lesson, Reorder, Section, UpdateLesson, UpdateSection,
+};
+use crate::models::student::{
+    CourseEnrollments, EnrolledCourse, Enrollment, RegisterStudent, Student, StudentCredential,
+};
+use crate::models::teacher::{CreateTeacher, DeletedTeacher, PatchTeacher, Teacher};
+
+pub mod memory;
+pub mod postgres;
+mod sql;
+#[cfg(feature = "sqlite")]
+pub mod sqlite;
+
+pub use memory::InMemoryRepository;
+pub use postgres::PgRepository;
+#[cfg(feature = "sqlite")]
+pub use sqlite::SqliteRepository;
+
+
+// 课程的存储接口, handler 只依赖这个 trait, 不关心数据保存在哪里
+// 所有实现需要返回相同的错误, 如记录不存在时返回 NotFound("course")
+#[async_trait]
+pub trait CourseRepository: Send + Sync {
+    async fn get_course_for_teacher(
+        &self,
+        teacher_id: i32,
+        query: &CourseQuery,
+    ) -> Result<CoursePage, AppError>;
+
+    async fn search_courses(&self, query: &CourseSearchQuery) -> Result<CourseSearchPage, AppError>;
+
+    async fn get_course_detail(&self, teacher_id: i32, course_id: i32) -> Result<Course, AppError>;
+
+    // 老师不存在时返回 ConstraintViolation
+    async fn post_new_course(&self, new_course: CreateCourse) -> Result<Course, AppError>;
+
+    // 批量导入, 所有课程属于同一个老师, 在一个事务中保存, 任何一门失败时都不保存
+    // 返回保存的课程数, 老师不存在或已删除时返回 ConstraintViolation
+    async fn import_courses(&self, teacher_id: i32, courses: Vec<CreateCourse>) -> Result<u64, AppError>;
+
+    // if_match 为请求中的 If-Match, 不包含当前版本时返回 PreconditionFailed, If-Match: * 时不检查
+    // 读取、合并与写入是原子的, 修改后版本号加一
+    // patch 中没有传入的字段保留原值, 传入 null 的字段清空
+    async fn update_course(
+        &self,
+        teacher_id: i32,
+        course_id: i32,
+        if_match: &IfMatch,
+        patch: PatchCourse,
+    ) -> Result<Course, AppError>;
+
+    // 软删除, 课程移入回收站, 其他查询不再返回该课程
+    // 课程不存在或已经在回收站中时返回 NotFound, 版本号的检查与 update_course 相同
+    async fn delete_course(
+        &self,
+        teacher_id: i32,
+        course_id: i32,
+        if_match: &IfMatch,
+    ) -> Result<(), AppError>;
+
+    // 回收站中的课程, 最近删除的在前
+    async fn get_deleted_courses(&self, teacher_id: i32) -> Result<Vec<Course>, AppError>;
+
+    // 课程不在回收站中时返回 NotFound("course"), 老师已被删除时返回 ConstraintViolation
+    async fn restore_course(&self, teacher_id: i32, course_id: i32) -> Result<Course, AppError>;
+
+    // 彻底删除移入回收站超过 retention 的课程, 返回删除的数量
+    async fn purge_deleted_courses(&self, retention: Duration) -> Result<u64, AppError>;
+}
+
+// 课程章节与课时的存储接口
+// 课程不存在、已删除或不属于该老师时返回 NotFound("course"),
+// 章节、课时不存在或不属于路径中的课程、章节时分别返回 NotFound("section")、NotFound("lesson")
+// 新增、删除与调整顺序都会保持 position 从 1 开始连续
+#[async_trait]
+pub trait SectionRepository: Send + Sync {
+    // 按顺序返回课程的所有章节及其课时
+    async fn get_sections(&self, teacher_id: i32, course_id: i32) -> Result<Vec<Section>, AppError>;
+
+    async fn post_new_section(
+        &self,
+        teacher_id: i32,
+        course_id: i32,
+        new_section: CreateSection,
+    ) -> Result<Section, AppError>;
+
+    async fn update_section(
+        &self,
+        teacher_id: i32,
+        course_id: i32,
+        section_id: i32,
+        update: UpdateSection,
+    ) -> Result<Section, AppError>;
+
+    // 同时删除章节中的课时
+    async fn delete_section(&self, teacher_id: i32, course_id: i32, section_id: i32) -> Result<(), AppError>;
+
+    // order.ids 不是课程的所有章节时返回 ValidationFailed, 返回调整后的所有章节
+    async fn reorder_sections(
+        &self,
+        teacher_id: i32,
+        course_id: i32,
+        order: Reorder,
+    ) -> Result<Vec<Section>, AppError>;
+
+    async fn post_new_lesson(
+        &self,
+        teacher_id: i32,
+        course_id: i32,
+        section_id: i32,
+        new_lesson: CreateLesson,
+    ) -> Result<Lesson, AppError>;
+
+    async fn update_lesson(
+        &self,
+        teacher_id: i32,
+        course_id: i32,
+        section_id: i32,
+        lesson_id: i32,
+        update: UpdateLesson,
+    ) -> Result<Lesson, AppError>;
+
+    async fn delete_lesson(
+        &self,
+        teacher_id: i32,
+        course_id: i32,
+        section_id: i32,
+        lesson_id: i32,
+    ) -> Result<(), AppError>;
+
+    // 与 reorder_sections 相同, 返回调整后章节中的所有课时
+    async fn reorder_lessons(
+        &self,
+        teacher_id: i32,
+        course_id: i32,
+        section_id: i32,
+        order: Reorder,
+    ) -> Result<Vec<Lesson>, AppError>;
+}
+
+// 学生与选课的存储接口
+// 课程不存在、已删除或不属于该老师时返回 NotFound("course"), 学生不存在时返回 NotFound("student")
+#[async_trait]
+pub trait StudentRepository: Send + Sync {
+    async fn get_student(&self, student_id: i32) -> Result<Student, AppError>;
+
+    // 邮箱忽略大小写
+    async fn get_student_credential_by_email(
+        &self,
+        email: &str,
+    ) -> Result<Option<StudentCredential>, AppError>;
+
+    // password_hash 为已经哈希过的密码, 邮箱重复时返回 Conflict
+    async fn post_new_student(
+        &self,
+        new_student: RegisterStudent,
+        password_hash: String,
+    ) -> Result<Student, AppError>;
+
+    // 检查人数与写入是原子的, 同一门课程的选课依次执行, 不会超出 capacity
+    // 已经选过该课程或课程已满时返回 Conflict
+    async fn enroll(
+        &self,
+        teacher_id: i32,
+        course_id: i32,
+        student_id: i32,
+    ) -> Result<Enrollment, AppError>;
+
+    // 学生没有选该课程时返回 NotFound("enrollment")
+    async fn unenroll(&self, teacher_id: i32, course_id: i32, student_id: i32) -> Result<(), AppError>;
+
+    async fn get_course_enrollments(
+        &self,
+        teacher_id: i32,
+        course_id: i32,
+    ) -> Result<CourseEnrollments, AppError>;
+
+    // 不包含已删除的课程
+    async fn get_student_courses(&self, student_id: i32) -> Result<Vec<EnrolledCourse>, AppError>;
+}
+
+// 老师的存储接口
+#[async_trait]
+pub trait TeacherRepository: Send + Sync {
+    // 没有任何老师时返回 NotFound("teacher")
+    async fn get_all_teacher(&self) -> Result<Vec<Teacher>, AppError>;
+
+    async fn get_teacher_detail(&self, teacher_id: i32) -> Result<Teacher, AppError>;
+
+    async fn get_teacher_credential(
+        &self,
+        teacher_id: i32,
+    ) -> Result<Option<TeacherCredential>, AppError>;
+
+    // 邮箱忽略大小写
+    async fn get_teacher_credential_by_email(
+        &self,
+        email: &str,
+    ) -> Result<Option<TeacherCredential>, AppError>;
+
+    async fn teacher_email_exists(&self, email: &str) -> Result<bool, AppError>;
+
+    async fn update_teacher_password(
+        &self,
+        teacher_id: i32,
+        password_hash: String,
+    ) -> Result<(), AppError>;
+
+    // password_hash 为已经哈希过的密码, 邮箱重复时返回 Conflict
+    async fn post_new_teacher(
+        &self,
+        new_teacher: CreateTeacher,
+        password_hash: Option<String>,
+    ) -> Result<Teacher, AppError>;
+
+    // 版本号的检查与 CourseRepository::update_course 相同
+    async fn update_teacher_details(
+        &self,
+        teacher_id: i32,
+        if_match: &IfMatch,
+        patch: PatchTeacher,
+    ) -> Result<Teacher, AppError>;
+
+    // 软删除, 老师不存在时返回 NotFound, 还有未删除的课程时返回 HasDependents 及课程数
+    // cascade 为 true 时把老师和他的课程在一个事务中一起移入回收站
+    // 已删除的老师不能登录, 也不能再创建课程
+    async fn delete_teacher(
+        &self,
+        teacher_id: i32,
+        if_match: &IfMatch,
+        cascade: bool,
+    ) -> Result<DeletedTeacher, AppError>;
+
+    // 回收站中的老师, 最近删除的在前
+    async fn get_deleted_teachers(&self) -> Result<Vec<Teacher>, AppError>;
+
+    // 老师不在回收站中时返回 NotFound("teacher")
+    // 级联删除时与老师一起移入回收站的课程一起恢复, 之前单独删除的课程仍留在回收站中
+    async fn restore_teacher(&self, teacher_id: i32) -> Result<Teacher, AppError>;
+
+    // 彻底删除移入回收站超过 retention 且已经没有任何课程的老师, 返回删除的数量
+    async fn purge_deleted_teachers(&self, retention: Duration) -> Result<u64, AppError>;
+}
@@ -0,0 +1,6 @@
+use sqlx::postgres::Postgres;
+
+use super::sql::sql_repository;
+
+// 基于 sqlx 的 Postgres 存储, 具体的 sql 在 dbaccess 中
+sql_repository!(PgRepository, Postgres, crate::dbaccess);
@@ -0,0 +1,332 @@
+// Postgres 与 SQLite 的存储只是把调用转发给 dbaccess 中对应的函数, 两者的函数名与参数一致,
+// 由这个宏生成存储的结构体及其对各个存储接口的实现, 例如:
+//
+//     sql_repository!(PgRepository, Postgres, crate::dbaccess);
+macro_rules! sql_repository {
+    ($repository:ident, $db:ty, $($dbaccess:ident)::+) => {
+        use std::time::Instant;
+
+        use async_trait::async_trait;
+        use chrono::Duration;
+        use sqlx::pool::{Pool, PoolConnection};
+
+        use $($dbaccess)::+::course::{
+            delete_course_db, get_course_detail_db, get_course_for_teacher_db, get_deleted_courses_db,
+            import_courses_db, post_new_course_db, purge_deleted_courses_db, restore_course_db,
+            search_courses_db, update_course_db,
+        };
+        use $($dbaccess)::+::teacher::{
+            delete_teacher_db, get_all_teacher_db, get_deleted_teachers_db,
+            get_teacher_credential_by_email_db, get_teacher_credential_db, get_teacher_detail_db,
+            post_new_teacher_db, purge_deleted_teachers_db, restore_teacher_db, teacher_email_exists_db,
+            update_teacher_details_db, update_teacher_password_db,
+        };
+        use $($dbaccess)::+::section::{
+            delete_lesson_db, delete_section_db, get_sections_db, post_new_lesson_db, post_new_section_db,
+            reorder_lessons_db, reorder_sections_db, update_lesson_db, update_section_db,
+        };
+        use $($dbaccess)::+::student::{
+            enroll_db, get_course_enrollments_db, get_student_courses_db,
+            get_student_credential_by_email_db, get_student_db,
+            post_new_student_db, unenroll_db,
+        };
+        use $crate::errors::AppError;
+        use $crate::etag::IfMatch;
+        use $crate::models::auth::TeacherCredential;
+        use $crate::models::course::{
+            Course, CoursePage, CourseQuery, CourseSearchPage, CourseSearchQuery, CreateCourse,
+            PatchCourse,
+        };
+        use $crate::models::section::{
+            CreateLesson, CreateSection, Lesson, Reorder, Section, UpdateLesson, UpdateSection,
+        };
+        use $crate::models::student::{
+            CourseEnrollments, EnrolledCourse, Enrollment, RegisterStudent, Student, StudentCredential,
+        };
+        use $crate::models::teacher::{CreateTeacher, DeletedTeacher, PatchTeacher, Teacher};
+
+        use $crate::repository::{
+            CourseRepository, SectionRepository, StudentRepository, TeacherRepository,
+        };
+
+        #[derive(Clone)]
+        pub struct $repository {
+            pool: Pool<$db>,
+        }
+
+        impl $repository {
+            pub fn new(pool: Pool<$db>) -> Self {
+                $repository { pool }
+            }
+
+            // 每次调用只从连接池获取一个连接, 等待的时间计入连接池的等待时间指标
+            async fn conn(&self) -> Result<PoolConnection<$db>, AppError> {
+                let start = Instant::now();
+                let conn = self.pool.acquire().await;
+                $crate::metrics::DB_POOL_ACQUIRE_WAIT.observe(start.elapsed().as_secs_f64());
+                Ok(conn?)
+            }
+        }
+
+        #[async_trait]
+        impl CourseRepository for $repository {
+            async fn get_course_for_teacher(
+                &self,
+                teacher_id: i32,
+                query: &CourseQuery,
+            ) -> Result<CoursePage, AppError> {
+                get_course_for_teacher_db(&mut *self.conn().await?, teacher_id, query).await
+            }
+
+            async fn search_courses(&self, query: &CourseSearchQuery) -> Result<CourseSearchPage, AppError> {
+                search_courses_db(&mut *self.conn().await?, query).await
+            }
+
+            async fn get_course_detail(&self, teacher_id: i32, course_id: i32) -> Result<Course, AppError> {
+                get_course_detail_db(&mut *self.conn().await?, teacher_id, course_id).await
+            }
+
+            async fn post_new_course(&self, new_course: CreateCourse) -> Result<Course, AppError> {
+                post_new_course_db(&mut *self.conn().await?, new_course).await
+            }
+
+            async fn import_courses(&self, teacher_id: i32, courses: Vec<CreateCourse>) -> Result<u64, AppError> {
+                import_courses_db(&mut *self.conn().await?, teacher_id, courses).await
+            }
+
+            async fn update_course(
+                &self,
+                teacher_id: i32,
+                course_id: i32,
+                if_match: &IfMatch,
+                patch: PatchCourse,
+            ) -> Result<Course, AppError> {
+                update_course_db(&mut *self.conn().await?, teacher_id, course_id, if_match, patch).await
+            }
+
+            async fn delete_course(
+                &self,
+                teacher_id: i32,
+                course_id: i32,
+                if_match: &IfMatch,
+            ) -> Result<(), AppError> {
+                delete_course_db(&mut *self.conn().await?, teacher_id, course_id, if_match).await
+            }
+
+            async fn get_deleted_courses(&self, teacher_id: i32) -> Result<Vec<Course>, AppError> {
+                get_deleted_courses_db(&mut *self.conn().await?, teacher_id).await
+            }
+
+            async fn restore_course(&self, teacher_id: i32, course_id: i32) -> Result<Course, AppError> {
+                restore_course_db(&mut *self.conn().await?, teacher_id, course_id).await
+            }
+
+            async fn purge_deleted_courses(&self, retention: Duration) -> Result<u64, AppError> {
+                purge_deleted_courses_db(&mut *self.conn().await?, retention).await
+            }
+        }
+
+        #[async_trait]
+        impl SectionRepository for $repository {
+            async fn get_sections(&self, teacher_id: i32, course_id: i32) -> Result<Vec<Section>, AppError> {
+                get_sections_db(&mut *self.conn().await?, teacher_id, course_id).await
+            }
+
+            async fn post_new_section(
+                &self,
+                teacher_id: i32,
+                course_id: i32,
+                new_section: CreateSection,
+            ) -> Result<Section, AppError> {
+                post_new_section_db(&mut *self.conn().await?, teacher_id, course_id, new_section).await
+            }
+
+            async fn update_section(
+                &self,
+                teacher_id: i32,
+                course_id: i32,
+                section_id: i32,
+                update: UpdateSection,
+            ) -> Result<Section, AppError> {
+                update_section_db(&mut *self.conn().await?, teacher_id, course_id, section_id, update).await
+            }
+
+            async fn delete_section(&self, teacher_id: i32, course_id: i32, section_id: i32) -> Result<(), AppError> {
+                delete_section_db(&mut *self.conn().await?, teacher_id, course_id, section_id).await
+            }
+
+            async fn reorder_sections(
+                &self,
+                teacher_id: i32,
+                course_id: i32,
+                order: Reorder,
+            ) -> Result<Vec<Section>, AppError> {
+                reorder_sections_db(&mut *self.conn().await?, teacher_id, course_id, order).await
+            }
+
+            async fn post_new_lesson(
+                &self,
+                teacher_id: i32,
+                course_id: i32,
+                section_id: i32,
+                new_lesson: CreateLesson,
+            ) -> Result<Lesson, AppError> {
+                post_new_lesson_db(&mut *self.conn().await?, teacher_id, course_id, section_id, new_lesson).await
+            }
+
+            async fn update_lesson(
+                &self,
+                teacher_id: i32,
+                course_id: i32,
+                section_id: i32,
+                lesson_id: i32,
+                update: UpdateLesson,
+            ) -> Result<Lesson, AppError> {
+                update_lesson_db(&mut *self.conn().await?, teacher_id, course_id, section_id, lesson_id, update).await
+            }
+
+            async fn delete_lesson(
+                &self,
+                teacher_id: i32,
+                course_id: i32,
+                section_id: i32,
+                lesson_id: i32,
+            ) -> Result<(), AppError> {
+                delete_lesson_db(&mut *self.conn().await?, teacher_id, course_id, section_id, lesson_id).await
+            }
+
+            async fn reorder_lessons(
+                &self,
+                teacher_id: i32,
+                course_id: i32,
+                section_id: i32,
+                order: Reorder,
+            ) -> Result<Vec<Lesson>, AppError> {
+                reorder_lessons_db(&mut *self.conn().await?, teacher_id, course_id, section_id, order).await
+            }
+        }
+
+        #[async_trait]
+        impl StudentRepository for $repository {
+            async fn get_student(&self, student_id: i32) -> Result<Student, AppError> {
+                get_student_db(&mut *self.conn().await?, student_id).await
+            }
+
+            async fn get_student_credential_by_email(
+                &self,
+                email: &str,
+            ) -> Result<Option<StudentCredential>, AppError> {
+                get_student_credential_by_email_db(&mut *self.conn().await?, email).await
+            }
+
+            async fn post_new_student(
+                &self,
+                new_student: RegisterStudent,
+                password_hash: String,
+            ) -> Result<Student, AppError> {
+                post_new_student_db(&mut *self.conn().await?, new_student, password_hash).await
+            }
+
+            async fn enroll(
+                &self,
+                teacher_id: i32,
+                course_id: i32,
+                student_id: i32,
+            ) -> Result<Enrollment, AppError> {
+                enroll_db(&mut *self.conn().await?, teacher_id, course_id, student_id).await
+            }
+
+            async fn unenroll(&self, teacher_id: i32, course_id: i32, student_id: i32) -> Result<(), AppError> {
+                unenroll_db(&mut *self.conn().await?, teacher_id, course_id, student_id).await
+            }
+
+            async fn get_course_enrollments(
+                &self,
+                teacher_id: i32,
+                course_id: i32,
+            ) -> Result<CourseEnrollments, AppError> {
+                get_course_enrollments_db(&mut *self.conn().await?, teacher_id, course_id).await
+            }
+
+            async fn get_student_courses(&self, student_id: i32) -> Result<Vec<EnrolledCourse>, AppError> {
+                get_student_courses_db(&mut *self.conn().await?, student_id).await
+            }
+        }
+
+        #[async_trait]
+        impl TeacherRepository for $repository {
+            async fn get_all_teacher(&self) -> Result<Vec<Teacher>, AppError> {
+                get_all_teacher_db(&mut *self.conn().await?).await
+            }
+
+            async fn get_teacher_detail(&self, teacher_id: i32) -> Result<Teacher, AppError> {
+                get_teacher_detail_db(&mut *self.conn().await?, teacher_id).await
+            }
+
+            async fn get_teacher_credential(
+                &self,
+                teacher_id: i32,
+            ) -> Result<Option<TeacherCredential>, AppError> {
+                get_teacher_credential_db(&mut *self.conn().await?, teacher_id).await
+            }
+
+            async fn get_teacher_credential_by_email(
+                &self,
+                email: &str,
+            ) -> Result<Option<TeacherCredential>, AppError> {
+                get_teacher_credential_by_email_db(&mut *self.conn().await?, email).await
+            }
+
+            async fn teacher_email_exists(&self, email: &str) -> Result<bool, AppError> {
+                teacher_email_exists_db(&mut *self.conn().await?, email).await
+            }
+
+            async fn update_teacher_password(
+                &self,
+                teacher_id: i32,
+                password_hash: String,
+            ) -> Result<(), AppError> {
+                update_teacher_password_db(&mut *self.conn().await?, teacher_id, password_hash).await
+            }
+
+            async fn post_new_teacher(
+                &self,
+                new_teacher: CreateTeacher,
+                password_hash: Option<String>,
+            ) -> Result<Teacher, AppError> {
+                post_new_teacher_db(&mut *self.conn().await?, new_teacher, password_hash).await
+            }
+
+            async fn update_teacher_details(
+                &self,
+                teacher_id: i32,
+                if_match: &IfMatch,
+                patch: PatchTeacher,
+            ) -> Result<Teacher, AppError> {
+                update_teacher_details_db(&mut *self.conn().await?, teacher_id, if_match, patch).await
+            }
+
+            async fn delete_teacher(
+                &self,
+                teacher_id: i32,
+                if_match: &IfMatch,
+                cascade: bool,
+            ) -> Result<DeletedTeacher, AppError> {
+                delete_teacher_db(&mut *self.conn().await?, teacher_id, if_match, cascade).await
+            }
+
+            async fn get_deleted_teachers(&self) -> Result<Vec<Teacher>, AppError> {
+                get_deleted_teachers_db(&mut *self.conn().await?).await
+            }
+
+            async fn restore_teacher(&self, teacher_id: i32) -> Result<Teacher, AppError> {
+                restore_teacher_db(&mut *self.conn().await?, teacher_id).await
+            }
+
+            async fn purge_deleted_teachers(&self, retention: Duration) -> Result<u64, AppError> {
+                purge_deleted_teachers_db(&mut *self.conn().await?, retention).await
+            }
+        }
+    };
+}
+pub(super) use sql_repository;
@@ -0,0 +1,6 @@
+use sqlx::sqlite::Sqlite;
+
+use super::sql::sql_repository;
+
+// 基于 sqlx 的 SQLite 存储, 具体的 sql 在 dbaccess::sqlite 中
+sql_repository!(SqliteRepository, Sqlite, crate::dbaccess::sqlite);
@@ -1,34 +1,96 @@
 use actix_web::web;
 
+use crate::handlers::auth::{login, register};
 use crate::handlers::course::{
             post_new_course, 
             get_courses_for_teacher, 
+            search_courses,
             get_course_detail,
             update_course_details,
+            patch_course_details,
             delete_course,
+            get_deleted_courses,
+            restore_course,
+            import_courses,
+            export_courses,
 };
-use crate::handlers::general::health_check_handler;
+use crate::handlers::section::{
+            get_sections,
+            post_new_section,
+            update_section,
+            delete_section,
+            reorder_sections,
+            post_new_lesson,
+            update_lesson,
+            delete_lesson,
+            reorder_lessons,
+};
+use crate::handlers::student::{
+            register_student,
+            student_login,
+            get_student_details,
+            get_student_courses,
+            enroll,
+            unenroll,
+            get_course_enrollments,
+};
+use crate::handlers::general::{liveness_handler, metrics_handler, readiness_handler};
 use crate::handlers::teacher::{
+            change_teacher_password,
             delete_teacher, 
             get_all_teacher, 
             get_teacher_details, 
             post_new_teacher, 
-            update_teacher_details
+            update_teacher_details,
+            patch_teacher_details,
+            get_deleted_teachers,
+            restore_teacher,
 };
 
 pub fn general_routes(cfg: &mut web::ServiceConfig) {
-    cfg.route("/health", web::get().to(health_check_handler));
+    cfg.route("/health/live", web::get().to(liveness_handler))
+        .route("/health/ready", web::get().to(readiness_handler))
+        .route("/metrics", web::get().to(metrics_handler));
 } 
 
+pub fn auth_routes(cfg: &mut web::ServiceConfig) {
+    cfg.service(
+        web::scope("/auth")
+            .route("/register", web::post().to(register))
+            .route("/login", web::post().to(login))
+    );
+}
+
 
 pub fn course_routes(cfg: &mut web::ServiceConfig) {
     cfg.service(
         web::scope("/courses")
             .route("/", web::post().to(post_new_course))
+            // 需要在 /{teacher_id} 之前注册, 否则会被当作 teacher_id 匹配
+            .route("/search", web::get().to(search_courses))
             .route("/{teacher_id}", web::get().to(get_courses_for_teacher))
+            // 需要在 /{teacher_id}/{course_id} 之前注册
+            .route("/{teacher_id}/trash", web::get().to(get_deleted_courses))
+            .route("/{teacher_id}/import", web::post().to(import_courses))
+            .route("/{teacher_id}/export", web::get().to(export_courses))
             .route("/{teacher_id}/{course_id}", web::get().to(get_course_detail))
             .route("/{teacher_id}/{course_id}", web::delete().to(delete_course))
             .route("/{teacher_id}/{course_id}", web::put().to(update_course_details))
+            .route("/{teacher_id}/{course_id}", web::patch().to(patch_course_details))
+            .route("/{teacher_id}/{course_id}/restore", web::post().to(restore_course))
+            .route("/{teacher_id}/{course_id}/sections", web::get().to(get_sections))
+            .route("/{teacher_id}/{course_id}/sections", web::post().to(post_new_section))
+            // 需要在 /sections/{section_id} 之前注册
+            .route("/{teacher_id}/{course_id}/sections/order", web::put().to(reorder_sections))
+            .route("/{teacher_id}/{course_id}/sections/{section_id}", web::put().to(update_section))
+            .route("/{teacher_id}/{course_id}/sections/{section_id}", web::delete().to(delete_section))
+            .route("/{teacher_id}/{course_id}/sections/{section_id}/lessons", web::post().to(post_new_lesson))
+            .route("/{teacher_id}/{course_id}/sections/{section_id}/lessons/order", web::put().to(reorder_lessons))
+            .route("/{teacher_id}/{course_id}/sections/{section_id}/lessons/{lesson_id}", web::put().to(update_lesson))
+            .route("/{teacher_id}/{course_id}/sections/{section_id}/lessons/{lesson_id}", web::delete().to(delete_lesson))
+            .route("/{teacher_id}/{course_id}/enrollments", web::get().to(get_course_enrollments))
+            .route("/{teacher_id}/{course_id}/enrollments", web::post().to(enroll))
+            .route("/{teacher_id}/{course_id}/enrollments/{student_id}", web::delete().to(unenroll))
     );
 }
 
@@ -37,8 +99,22 @@ pub fn teacher_routes(cfg: &mut web::ServiceConfig) {
         web::scope("/teachers")
             .route("/", web::post().to(post_new_teacher))
             .route("/", web::get().to(get_all_teacher))
+            .route("/trash", web::get().to(get_deleted_teachers))
             .route("/{teacher_id}", web::get().to(get_teacher_details))
             .route("/{teacher_id}",web::put().to(update_teacher_details))
+            .route("/{teacher_id}", web::patch().to(patch_teacher_details))
             .route("/{teacher_id}", web::delete().to(delete_teacher))
+            .route("/{teacher_id}/password", web::put().to(change_teacher_password))
+            .route("/{teacher_id}/restore", web::post().to(restore_teacher))
+    );
+}
+
+pub fn student_routes(cfg: &mut web::ServiceConfig) {
+    cfg.service(
+        web::scope("/students")
+            .route("/", web::post().to(register_student))
+            .route("/login", web::post().to(student_login))
+            .route("/{student_id}", web::get().to(get_student_details))
+            .route("/{student_id}/courses", web::get().to(get_student_courses))
     );
 }
@@ -1,10 +1,68 @@
-use std::sync::Mutex;
+use std::sync::Arc;
+
 use sqlx::postgres::PgPool;
-// use super::models::Course;
+#[cfg(feature = "sqlite")]
+use sqlx::sqlite::SqlitePool;
+
+use crate::db::DbPool;
+use crate::repository::{
+    CourseRepository, InMemoryRepository, PgRepository, SectionRepository, StudentRepository,
+    TeacherRepository,
+};
+#[cfg(feature = "sqlite")]
+use crate::repository::SqliteRepository;
 
 pub struct AppState {
-    pub health_check_response: String,
-    pub visit_count: Mutex<u32>,
-    // pub courses: Mutex<Vec<Course>>,
-    pub db: PgPool,
+    pub courses: Arc<dyn CourseRepository>,
+    pub teachers: Arc<dyn TeacherRepository>,
+    pub sections: Arc<dyn SectionRepository>,
+    pub students: Arc<dyn StudentRepository>,
+    // 使用数据库存储时的连接池, 只用于健康检查和连接池指标, 读写数据都通过上面的存储接口
+    pub db: Option<DbPool>,
+}
+
+impl AppState {
+    // 根据连接池的类型选择对应的存储实现
+    pub fn database(pool: DbPool) -> Self {
+        match pool {
+            DbPool::Postgres(pool) => AppState::postgres(pool),
+            #[cfg(feature = "sqlite")]
+            DbPool::Sqlite(pool) => AppState::sqlite(pool),
+        }
+    }
+
+    pub fn postgres(pool: PgPool) -> Self {
+        let repository = Arc::new(PgRepository::new(pool.clone()));
+        AppState {
+            courses: repository.clone(),
+            teachers: repository.clone(),
+            sections: repository.clone(),
+            students: repository,
+            db: Some(DbPool::Postgres(pool)),
+        }
+    }
+
+    #[cfg(feature = "sqlite")]
+    pub fn sqlite(pool: SqlitePool) -> Self {
+        let repository = Arc::new(SqliteRepository::new(pool.clone()));
+        AppState {
+            courses: repository.clone(),
+            teachers: repository.clone(),
+            sections: repository.clone(),
+            students: repository,
+            db: Some(DbPool::Sqlite(pool)),
+        }
+    }
+
+    // 不需要数据库, 所有存储接口共用一份内存数据, 以便检查课程所属的老师、章节与选课所属的课程是否存在
+    pub fn in_memory() -> Self {
+        let repository = Arc::new(InMemoryRepository::new());
+        AppState {
+            courses: repository.clone(),
+            teachers: repository.clone(),
+            sections: repository.clone(),
+            students: repository,
+            db: None,
+        }
+    }
 }
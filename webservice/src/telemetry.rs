@@ -0,0 +1,20 @@
+use tracing_subscriber::fmt::format::FmtSpan;
+use tracing_subscriber::EnvFilter;
+
+// 未设置 RUST_LOG 时的日志级别
+pub const DEFAULT_LOG_FILTER: &str = "info,sqlx::postgres::notice=warn";
+
+// 以 JSON 格式输出日志到标准输出, 每条日志都带有所在 span(请求 id、dbaccess 调用等)的字段,
+// span 结束时输出一条 close 日志, 其中的 time.busy 即为该次调用(如一次 SQL 查询)的耗时
+pub fn init_tracing() {
+    let filter = EnvFilter::try_from_default_env()
+        .unwrap_or_else(|_| EnvFilter::new(DEFAULT_LOG_FILTER));
+    // 重复初始化(如测试中)时忽略错误
+    let _ = tracing_subscriber::fmt()
+        .json()
+        .with_env_filter(filter)
+        .with_current_span(true)
+        .with_span_list(true)
+        .with_span_events(FmtSpan::CLOSE)
+        .try_init();
+}
@@ -0,0 +1,184 @@
+// 测试辅助函数, 只在 cargo test 时编译
+//
+// 数据库测试使用 #[sqlx::test]: 每个测试在 DATABASE_URL 指向的 Postgres 上创建一个临时数据库,
+// 执行 migrations 目录下的迁移和 fixtures 中的测试数据, 测试通过后删除该数据库
+// 使用 db_test! 的测试在启用 sqlite feature 时还会在临时的 SQLite 数据库上再执行一次,
+// 并且总是在内存存储上执行一次, 内存存储的测试数据由 seed_memory 通过存储接口写入
+use actix_web::body::MessageBody;
+use actix_web::dev::{ServiceFactory, ServiceRequest, ServiceResponse};
+use actix_web::http::header::{HeaderName, AUTHORIZATION};
+use actix_web::{web, App, Error};
+
+use crate::app::build_app;
+use crate::auth::JwtConfig;
+use crate::config::Settings;
+use crate::lifecycle::Lifecycle;
+use crate::metrics::Metrics;
+use crate::models::auth::Role;
+use crate::models::course::{CreateCourse, Money};
+use crate::models::section::{CreateLesson, CreateSection};
+use crate::models::student::RegisterStudent;
+use crate::models::teacher::CreateTeacher;
+use crate::state::AppState;
+
+pub const TEST_JWT_SECRET: &str = "test-secret";
+
+pub fn test_jwt_config() -> JwtConfig {
+    JwtConfig::new(TEST_JWT_SECRET, 60)
+}
+
+// 使用默认配置构建完整的 App, 配合 actix_web::test::init_service 使用
+// 数据库测试传入 AppState::postgres(pool), 不需要数据库时传入 AppState::in_memory()
+pub fn test_app(
+    app_state: AppState,
+) -> App<
+    impl ServiceFactory<
+        ServiceRequest,
+        Config = (),
+        Response = ServiceResponse<impl MessageBody>,
+        Error = Error,
+        InitError = (),
+    >,
+> {
+    build_app(
+        web::Data::new(app_state),
+        web::Data::new(test_jwt_config()),
+        web::Data::new(Metrics::new().unwrap()),
+        web::Data::new(Lifecycle::default()),
+        &Settings::default(),
+    )
+}
+
+// 以指定身份访问需要登录的接口, 返回 Authorization 请求头
+pub fn bearer(id: i32, role: Role) -> (HeaderName, String) {
+    let token = test_jwt_config().issue_token(id, role).unwrap();
+    (AUTHORIZATION, format!("Bearer {}", token))
+}
+
+// 通过存储接口写入与 handlers/fixtures 中同名文件相同的数据, 生成的 id 也与数据库中一致
+pub async fn seed_memory(app_state: &AppState, fixtures: &[&str]) {
+    for fixture in fixtures {
+        match *fixture {
+            "teachers" => seed_teachers(app_state).await,
+            "courses" => seed_courses(app_state).await,
+            "sections" => seed_sections(app_state).await,
+            "students" => seed_students(app_state).await,
+            other => panic!("unknown fixture {}", other),
+        }
+    }
+}
+
+async fn seed_teachers(app_state: &AppState) {
+    let teachers = [
+        ("Dave", "http://unicorn.pro/dave.png", "Dave is an experienced Rust teacher", "dave@unicorn.pro"),
+        ("Tom", "http://unicorn.pro/tom.png", "Tom teaches machine learning", "tom@unicorn.pro"),
+    ];
+    for (name, picture_url, profile, email) in teachers {
+        let teacher = CreateTeacher {
+            name: name.into(),
+            picture_url: picture_url.into(),
+            profile: profile.into(),
+            email: Some(email.into()),
+            password: None,
+        };
+        app_state.teachers.post_new_teacher(teacher, None).await.unwrap();
+    }
+}
+
+async fn seed_courses(app_state: &AppState) {
+    let courses = [
+        ("First course", "An introduction to Rust", "Video", 100, "English", "Beginner"),
+        ("Second course", "Web services with actix-web", "Live", 200, "English", "Intermediate"),
+        ("Third course", "Async Rust in depth", "Text", 300, "Chinese", "Advanced"),
+    ];
+    for (name, description, format, price, language, level) in courses {
+        let course = CreateCourse {
+            teacher_id: 1,
+            name: name.into(),
+            description: Some(description.into()),
+            format: Some(format.into()),
+            structure: None,
+            duration: None,
+            price: Some(Money::new(price, "CNY")),
+            language: Some(language.into()),
+            level: Some(level.into()),
+            capacity: None,
+        };
+        app_state.courses.post_new_course(course).await.unwrap();
+    }
+}
+
+async fn seed_sections(app_state: &AppState) {
+    for title in ["Getting started", "Ownership"] {
+        let section = CreateSection {
+            title: title.into(),
+            position: None,
+        };
+        app_state.sections.post_new_section(1, 1, section).await.unwrap();
+    }
+    let lessons = [
+        (1, "Installing Rust", Some("rustup and cargo"), 10),
+        (2, "Moves", None, 20),
+        (2, "Borrowing", None, 25),
+    ];
+    for (section_id, title, content, duration_minutes) in lessons {
+        let lesson = CreateLesson {
+            title: title.into(),
+            content: content.map(String::from),
+            duration_minutes: Some(duration_minutes),
+            position: None,
+        };
+        app_state.sections.post_new_lesson(1, 1, section_id, lesson).await.unwrap();
+    }
+}
+
+async fn seed_students(app_state: &AppState) {
+    for (name, email) in [
+        ("Alice", "alice@example.com"),
+        ("Bob", "bob@example.com"),
+        ("Carol", "carol@example.com"),
+    ] {
+        let student = RegisterStudent {
+            name: name.into(),
+            email: email.into(),
+            password: String::new(),
+        };
+        app_state.students.post_new_student(student, "not-a-hash".into()).await.unwrap();
+    }
+}
+
+// 存储测试, 生成与测试同名的模块, 其中的 postgres、sqlite(启用 sqlite feature 时)和 memory
+// 三个测试分别使用对应存储的 AppState 执行同样的测试代码, fixtures 需要两种数据库都能执行,
+// 新增 fixture 时需要同时在 seed_memory 中添加对应的数据
+//
+//     db_test!(get_all_teacher(app_state) fixtures("teachers") {
+//         let teachers = app_state.teachers.get_all_teacher().await.unwrap();
+//     });
+macro_rules! db_test {
+    ($name:ident($state:ident) $(fixtures($($fixture:literal),*))? $body:block) => {
+        mod $name {
+            use super::*;
+
+            #[sqlx::test($(fixtures($($fixture),*))?)]
+            async fn postgres(db_pool: sqlx::postgres::PgPool) {
+                let $state = $crate::state::AppState::postgres(db_pool);
+                $body
+            }
+
+            #[cfg(feature = "sqlite")]
+            #[sqlx::test(migrator = "crate::migration::SQLITE_MIGRATOR" $(, fixtures($($fixture),*))?)]
+            async fn sqlite(db_pool: sqlx::sqlite::SqlitePool) {
+                let $state = $crate::state::AppState::sqlite(db_pool);
+                $body
+            }
+
+            #[actix_rt::test]
+            async fn memory() {
+                let $state = $crate::state::AppState::in_memory();
+                $crate::test_support::seed_memory(&$state, &[$($($fixture),*)?]).await;
+                $body
+            }
+        }
+    };
+}
+pub(crate) use db_test;
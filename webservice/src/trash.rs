@@ -0,0 +1,87 @@
+use actix_rt::time::interval;
+use actix_web::web;
+use chrono::Duration;
+
+use crate::config::TrashSettings;
+use crate::errors::AppError;
+use crate::state::AppState;
+
+// 彻底删除在回收站中超过保留时间的课程和老师, 返回删除的 (课程数, 老师数)
+// 先删除课程, 老师的课程全部删除后才会删除老师
+pub async fn purge_expired(app_state: &AppState, retention: Duration) -> Result<(u64, u64), AppError> {
+    let courses = app_state.courses.purge_deleted_courses(retention).await?;
+    let teachers = app_state.teachers.purge_deleted_teachers(retention).await?;
+    Ok((courses, teachers))
+}
+
+// 按 purge_interval_secs 定期清理回收站, 启动时先执行一次
+pub async fn run_purge_task(app_state: web::Data<AppState>, settings: TrashSettings) {
+    let mut ticker = interval(settings.purge_interval());
+    loop {
+        ticker.tick().await;
+        match purge_expired(&app_state, settings.retention()).await {
+            Ok((0, 0)) => {}
+            Ok((courses, teachers)) => {
+                tracing::info!(courses, teachers, "Purged expired trash")
+            }
+            Err(e) => tracing::warn!(error = %e, "Failed to purge trash"),
+        }
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use crate::etag::IfMatch;
+    use crate::models::course::CreateCourse;
+    use crate::models::teacher::CreateTeacher;
+
+    use super::*;
+
+    #[actix_rt::test]
+    async fn purge_removes_courses_before_teachers() {
+        let app_state = AppState::in_memory();
+        let teacher = app_state
+            .teachers
+            .post_new_teacher(
+                CreateTeacher {
+                    name: "Dave".into(),
+                    picture_url: "http://unicorn.pro".into(),
+                    profile: "A teacher in the trash".into(),
+                    email: None,
+                    password: None,
+                },
+                None,
+            )
+            .await
+            .unwrap();
+        let course = app_state
+            .courses
+            .post_new_course(CreateCourse {
+                teacher_id: teacher.id,
+                name: "Rust".into(),
+                description: None,
+                format: None,
+                structure: None,
+                duration: None,
+                price: None,
+                language: None,
+                level: None,
+                capacity: None,
+            })
+            .await
+            .unwrap();
+        app_state.courses.delete_course(teacher.id, course.id, &IfMatch::ANY).await.unwrap();
+        app_state.teachers.delete_teacher(teacher.id, &IfMatch::ANY, false).await.unwrap();
+
+        // 还在保留时间内
+        let purged = purge_expired(&app_state, Duration::days(30)).await.unwrap();
+        assert_eq!(purged, (0, 0));
+        assert_eq!(app_state.teachers.get_deleted_teachers().await.unwrap().len(), 1);
+
+        // 负的保留时间使刚删除的数据也已过期
+        let purged = purge_expired(&app_state, Duration::seconds(-1)).await.unwrap();
+        assert_eq!(purged, (1, 1));
+        assert!(app_state.courses.get_deleted_courses(teacher.id).await.unwrap().is_empty());
+        assert!(app_state.teachers.get_deleted_teachers().await.unwrap().is_empty());
+    }
+}
@@ -0,0 +1,141 @@
+// 启动编译好的 main, 在请求处理过程中发送 SIGTERM, 验证正在处理的请求可以完成且进程正常退出
+#![cfg(unix)]
+
+use std::io::{BufRead, BufReader, Read, Write};
+use std::net::{TcpListener, TcpStream};
+use std::process::{Child, Command, Stdio};
+use std::thread;
+use std::time::{Duration, Instant};
+
+fn free_port() -> u16 {
+    TcpListener::bind("127.0.0.1:0")
+        .unwrap()
+        .local_addr()
+        .unwrap()
+        .port()
+}
+
+// 发送一个请求并读取完整的响应(连接保持打开)
+fn send_request(stream: &mut TcpStream, request: &str) -> String {
+    stream.write_all(request.as_bytes()).unwrap();
+    read_response(stream)
+}
+
+fn read_response(stream: &mut TcpStream) -> String {
+    let mut reader = BufReader::new(stream);
+    let mut head = String::new();
+    loop {
+        let mut line = String::new();
+        reader.read_line(&mut line).unwrap();
+        if line == "\r\n" || line.is_empty() {
+            break;
+        }
+        head.push_str(&line);
+    }
+    let length: usize = head
+        .lines()
+        .find_map(|line| {
+            let (name, value) = line.split_once(':')?;
+            name.eq_ignore_ascii_case("content-length")
+                .then(|| value.trim().parse().ok())?
+        })
+        .unwrap_or(0);
+    let mut body = vec![0; length];
+    reader.read_exact(&mut body).unwrap();
+    head + "\r\n" + &String::from_utf8_lossy(&body)
+}
+
+fn get(path: &str) -> String {
+    format!("GET {} HTTP/1.1\r\nHost: localhost\r\n\r\n", path)
+}
+
+fn wait_until_live(port: u16, child: &mut Child) {
+    let deadline = Instant::now() + Duration::from_secs(30);
+    while Instant::now() < deadline {
+        assert!(child.try_wait().unwrap().is_none(), "server exited during startup");
+        if let Ok(mut stream) = TcpStream::connect(("127.0.0.1", port)) {
+            if send_request(&mut stream, &get("/health/live")).starts_with("HTTP/1.1 200") {
+                return;
+            }
+        }
+        thread::sleep(Duration::from_millis(100));
+    }
+    panic!("server did not start on port {}", port);
+}
+
+fn wait_for_exit(child: &mut Child, timeout: Duration) -> std::process::ExitStatus {
+    let deadline = Instant::now() + timeout;
+    loop {
+        if let Some(status) = child.try_wait().unwrap() {
+            return status;
+        }
+        if Instant::now() > deadline {
+            child.kill().ok();
+            panic!("server did not exit within {:?}", timeout);
+        }
+        thread::sleep(Duration::from_millis(50));
+    }
+}
+
+#[test]
+fn sigterm_drains_in_flight_requests() {
+    let port = free_port();
+    let mut child = Command::new(env!("CARGO_BIN_EXE_main"))
+        .args(["--bind", &format!("127.0.0.1:{}", port), "--workers", "1"])
+        .env("WEBSERVICE__SERVER__SHUTDOWN_DELAY_SECS", "1")
+        .env("WEBSERVICE__SERVER__SHUTDOWN_TIMEOUT_SECS", "10")
+        .env("JWT_SECRET", "graceful-shutdown-test-secret")
+        .stdout(Stdio::piped())
+        .stderr(Stdio::null())
+        .spawn()
+        .unwrap();
+    wait_until_live(port, &mut child);
+
+    let mut probe = TcpStream::connect(("127.0.0.1", port)).unwrap();
+    assert!(send_request(&mut probe, &get("/health/ready")).starts_with("HTTP/1.1 200"));
+    drop(probe);
+
+    // 只发送一半的请求体, 让这个请求停留在处理中
+    let body = r#"{"email":"nobody@example.com","password":"not-a-password"}"#;
+    let (first, rest) = body.split_at(body.len() / 2);
+    let mut in_flight = TcpStream::connect(("127.0.0.1", port)).unwrap();
+    write!(
+        in_flight,
+        "POST /auth/login HTTP/1.1\r\nHost: localhost\r\nContent-Type: application/json\r\nContent-Length: {}\r\n\r\n{}",
+        body.len(),
+        first
+    )
+    .unwrap();
+    thread::sleep(Duration::from_millis(200));
+
+    let killed = Command::new("kill")
+        .args(["-TERM", &child.id().to_string()])
+        .status()
+        .unwrap();
+    assert!(killed.success());
+    thread::sleep(Duration::from_millis(300));
+
+    // shutdown_delay_secs 期间仍然处理请求, 但就绪检查返回 503
+    let mut probe = TcpStream::connect(("127.0.0.1", port)).unwrap();
+    let ready = send_request(&mut probe, &get("/health/ready"));
+    assert!(ready.starts_with("HTTP/1.1 503"), "{}", ready);
+    assert!(ready.contains(r#""shutting_down":true"#), "{}", ready);
+    drop(probe);
+
+    // 等到停止接受新连接之后再补齐请求体, 正在处理的请求仍然可以得到完整的响应
+    thread::sleep(Duration::from_millis(1200));
+    assert!(TcpStream::connect(("127.0.0.1", port)).is_err());
+    in_flight.write_all(rest.as_bytes()).unwrap();
+    let response = read_response(&mut in_flight);
+    assert!(response.starts_with("HTTP/1.1 401"), "{}", response);
+    assert!(response.contains("unauthorized"), "{}", response);
+    drop(in_flight);
+
+    let status = wait_for_exit(&mut child, Duration::from_secs(15));
+    assert!(status.success(), "server exited with {}", status);
+
+    let mut logs = String::new();
+    child.stdout.take().unwrap().read_to_string(&mut logs).unwrap();
+    assert!(logs.contains("Shutdown started"), "{}", logs);
+    assert!(logs.contains("Shutdown complete"), "{}", logs);
+}
@@ -1,9 +1,27 @@
-use serde::Serialize;
+use serde::{Deserialize, Serialize};
 use wasm_bindgen::JsValue;
 
 #[derive(Debug, Serialize)]
 pub enum AppError {
-    SomeError(String)
+    SomeError(String),
+    // webservice 返回的错误, code 如 course_not_found, validation_failed
+    ServiceError { code: String, message: String },
+}
+
+// webservice 返回的错误响应体
+#[derive(Debug, Deserialize)]
+pub struct ErrorResponse {
+    pub code: String,
+    pub error_message: String,
+}
+
+impl From<ErrorResponse> for AppError {
+    fn from(value: ErrorResponse) -> Self {
+        AppError::ServiceError {
+            code: value.code,
+            message: value.error_message,
+        }
+    }
 }
 
 
@@ -2,19 +2,13 @@ mod utils;
 mod models;
 mod errors;
 
+use errors::AppError;
 use models::course:: {delete_course, get_course_by_teacher };
 use wasm_bindgen::prelude::*;
 use wasm_bindgen_futures::spawn_local;
 use web_sys::{window, HtmlButtonElement};
 
 
-// when the `wee_alloc` feature is enabled, use `wee_alloc` as the global
-// allocator.
-#[cfg(feature = "wee_alloc")]
-#[global_allocator]
-static ALLOC: wee_alloc::WeeAlloc = wee_alloc::WeeAlloc::INIT;
-
-
 #[wasm_bindgen]
 extern "C" {
 
@@ -38,6 +32,8 @@ pub fn greet(name: &str) {
 // main 函数 就相当于浏览器执行的入口
 #[wasm_bindgen(start)]
 pub async fn main() -> Result<(), JsValue> {
+    utils::set_panic_hook();
+
     let window = window().expect("no global window exists");
     let document = window.document().expect("no global document exists");
 
@@ -75,20 +71,27 @@ pub async fn main() -> Result<(), JsValue> {
                 .unwrap();
 
         let cid = c.id;
+        let version = c.version;
 
         // 目前异步闭包函数不稳定, 只能通过其他方式来支持闭包异步函数
         //
         let click_closure = Closure::wrap(Box::new(move |_event: web_sys::MouseEvent| {
-            let r = confirm(format!("确认删除课程{} 吗?", cid).as_str());
-            match r {
-                true => {
-                    // 异步函数, 当异步闭包函数不稳定时使用 spawn_local 以执行异步操作
-                    spawn_local(delete_course(1, cid));
-                    // delete_course(1, cid);
-                    alert("删除成功!");
-                    web_sys::window().unwrap().location().reload().unwrap();
-                }
-                _ => {}
+            let r = confirm(format!("确认删除课程{} 吗? 删除后课程会移入回收站, 可以恢复", cid).as_str());
+            if r {
+                // 异步函数, 当异步闭包函数不稳定时使用 spawn_local 以执行异步操作
+                // 等待删除完成后再提示并刷新页面
+                spawn_local(async move {
+                    match delete_course(1, cid, version).await {
+                        Ok(()) => {
+                            alert("已移入回收站!");
+                            web_sys::window().unwrap().location().reload().unwrap();
+                        }
+                        Err(AppError::ServiceError { message, .. }) => {
+                            alert(format!("删除失败: {}", message).as_str());
+                        }
+                        Err(AppError::SomeError(e)) => alert(format!("删除失败: {}", e).as_str()),
+                    }
+                });
             }
         }) as Box<dyn Fn(_)>); 
 
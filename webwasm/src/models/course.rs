@@ -5,9 +5,17 @@ use wasm_bindgen::{prelude::wasm_bindgen, JsCast, JsValue};
 use wasm_bindgen_futures::JsFuture;
 use web_sys::{Request, RequestInit, RequestMode, Response};
 
-use crate::errors::AppError;
+use crate::errors::{AppError, ErrorResponse};
+use crate::utils::bearer_token;
 
 
+// 与服务端的 Money 一致, amount 以最小货币单位表示, currency 为 ISO 4217 代码
+#[derive(Debug, Clone, PartialEq, Deserialize, Serialize)]
+pub struct Money {
+    pub amount: i32,
+    pub currency: String,
+}
+
 #[derive(Debug, Deserialize, Serialize)]
 pub struct Course {
     pub teacher_id: i32,
@@ -18,9 +26,18 @@ pub struct Course {
     pub format: Option<String>,
     pub structure: Option<String>,
     pub duration: Option<String>,
-    pub price: Option<String>,
+    pub price: Option<Money>,
     pub language: Option<String>,
     pub level: Option<String>,
+    // 删除时作为 If-Match 发送, 课程在页面加载后被修改过时服务端返回 412
+    #[serde(default)]
+    pub version: i32,
+}
+
+// 服务端分页返回的课程列表, 这里只用到当前页的课程
+#[derive(Debug, Deserialize)]
+pub struct CoursePage {
+    pub items: Vec<Course>,
 }
 
 
@@ -29,7 +46,7 @@ pub async fn get_course_by_teacher(
 ) -> Result<Vec<Course>, AppError> {
 
     // 用于在 wasm 中完成 HTTP 请求
-    let mut opts = RequestInit::new();
+    let opts = RequestInit::new();
     opts.set_method("GET");
     opts.set_mode(RequestMode::Cors);
 
@@ -46,44 +63,56 @@ pub async fn get_course_by_teacher(
     let resp: Response = resp_value.dyn_into().unwrap();
 
     // 获取到请求的结果
-    let json = JsFuture::from(resp.json()?).await?;
-    let course: Vec<Course> = json.into_serde().unwrap();
-
-    Ok(course)
+    let text = JsFuture::from(resp.text()?).await?;
+    if !resp.ok() {
+        let error: ErrorResponse = serde_json::from_str(&text.as_string().unwrap_or_default())
+            .map_err(|e| e.to_string())?;
+        return Err(error.into());
+    }
+    let page: CoursePage = serde_json::from_str(&text.as_string().unwrap_or_default())
+        .map_err(|e| e.to_string())?;
+
+    Ok(page.items)
 }
 
 
 
-pub async fn delete_course(teacher_id: i32, course_id: i32) -> () {
+// 删除成功时服务端返回 204, 没有响应体
+pub async fn delete_course(teacher_id: i32, course_id: i32, version: i32) -> Result<(), AppError> {
 
-    let mut opts = RequestInit::new();
+    let opts = RequestInit::new();
     opts.set_method("DELETE");
     opts.set_mode(RequestMode::Cors);
 
     let url = format!("http://localhost:3000/courses/{}/{}", teacher_id, course_id);
 
-    let request = Request::new_with_str_and_init(&url, &opts).unwrap();
-    request.headers().set("Accept", "application/json;").unwrap();
+    let request = Request::new_with_str_and_init(&url, &opts)?;
+    request.headers().set("Accept", "application/json;")?;
+    request.headers().set("If-Match", &format!("\"{}\"", version))?;
+    if let Some(token) = bearer_token() {
+        request.headers().set("Authorization", &token)?;
+    }
 
-    let window = web_sys::window().unwrap();
+    let window = web_sys::window().ok_or("no window exists".to_string())?;
 
-    let resp = JsFuture::from(window.fetch_with_request(&request))
-        .await
-        .unwrap();
+    let resp = JsFuture::from(window.fetch_with_request(&request)).await?;
 
     assert!(resp.is_instance_of::<Response>());
     let resp: Response = resp.dyn_into().unwrap();
 
-    let json = JsFuture::from(resp.json().unwrap()).await.unwrap();
-
-    let _course: Course = json.into_serde().unwrap();
-
+    if !resp.ok() {
+        let text = JsFuture::from(resp.text()?).await?;
+        let error: ErrorResponse = serde_json::from_str(&text.as_string().unwrap_or_default())
+            .map_err(|e| e.to_string())?;
+        return Err(error.into());
+    }
+    Ok(())
 }
 
 
 #[wasm_bindgen] 
 pub async fn add_course(name: String, description: String) -> Result<Promise, JsValue> {
-    let mut opts = RequestInit::new();
+    let opts = RequestInit::new();
     opts.set_method("POST");
     opts.set_mode(RequestMode::Cors);
     let str_json = format!(
@@ -101,9 +130,12 @@ pub async fn add_course(name: String, description: String) -> Result<Promise, Js
     opts.set_body(&JsValue::from_str(str_json.as_str()));
     let url = "http://localhost:3000/courses/";
 
-    let request = Request::new_with_str_and_init(&url, &opts)?;
+    let request = Request::new_with_str_and_init(url, &opts)?;
     request.headers().set("Accept", "application/json;")?;
     request.headers().set("Content-Type", "application/json")?;
+    if let Some(token) = bearer_token() {
+        request.headers().set("Authorization", &token)?;
+    }
 
     let window = web_sys::window().ok_or("no window exists".to_string())?;
     let resp_value = JsFuture::from(window.fetch_with_request(&request))
@@ -112,5 +144,5 @@ pub async fn add_course(name: String, description: String) -> Result<Promise, Js
     assert!(resp_value.is_instance_of::<Response>());
 
     let resp: Response = resp_value.dyn_into().unwrap();
-    Ok(resp.json()?)
+    resp.json()
 }
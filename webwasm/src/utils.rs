@@ -8,3 +8,13 @@ pub fn set_panic_hook() {
     #[cfg(feature = "console_error_panic_hook")]
     console_error_panic_hook::set_once();
 }
+
+// 登录后保存在 localStorage 中的 token, 修改数据的请求需要携带
+pub fn bearer_token() -> Option<String> {
+    web_sys::window()?
+        .local_storage()
+        .ok()??
+        .get_item("access_token")
+        .ok()?
+        .map(|token| format!("Bearer {}", token))
+}